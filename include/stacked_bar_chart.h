@@ -0,0 +1,25 @@
+/* C interface to the stacked bar chart renderer, built with the 'capi' feature */
+#ifndef STACKED_BAR_CHART_H
+#define STACKED_BAR_CHART_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Render a chart given as JSON5 to an SVG document, with the options in its options block and
+ * then those in options_json, which can be NULL, in their place. Returns NULL on error, when
+ * sbc_last_error gives the reason. The result must be freed with sbc_free. */
+char *sbc_render(const char *input_json, const char *options_json);
+
+/* The reason the last call to sbc_render on this thread failed, or NULL if it succeeded. The
+ * string is owned by the library and valid until the next call to sbc_render. */
+const char *sbc_last_error(void);
+
+/* Free a string returned by sbc_render */
+void sbc_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,108 @@
+use crate::{pattern, Cli, OutputFormat, StackedBarChartError, StackedBarChartTool};
+use easy_error::ResultExt;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+impl OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Html => "html",
+            OutputFormat::Png => "png",
+            OutputFormat::Eps => "eps",
+            OutputFormat::Term => "txt",
+        }
+    }
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Render every file matching `pattern` into the output directory, with the extension of
+    /// the output format in place of that of the input. Files which fail are reported and
+    /// skipped, so that one bad chart doesn't stop the rest, and then listed in the error.
+    pub(crate) fn render_batch(
+        self: &Self,
+        cli: &Cli,
+        format: OutputFormat,
+        pattern: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let out_dir = cli.out_dir.as_deref().unwrap_or(Path::new("."));
+        let paths = Self::expand_glob(pattern)?;
+        let mut failed = vec![];
+
+        std::fs::create_dir_all(out_dir).context(format!(
+            "Unable to create directory '{}'",
+            out_dir.to_string_lossy()
+        ))?;
+
+        for path in paths.iter() {
+            let output_file = out_dir
+                .join(path.file_stem().unwrap_or_default())
+                .with_extension(format.extension());
+
+            if let Err(err) = self.render_input(cli, format, Some(path), Some(&output_file)) {
+                self.report_file_error(&path.to_string_lossy(), err);
+                failed.push(path.to_string_lossy());
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(StackedBarChartError::Render(format!(
+                "{} of {} charts failed to render:\n  {}",
+                failed.len(),
+                paths.len(),
+                failed.join("\n  ")
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// The files matching a pattern with wildcards in the file name, in order
+    fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let path = Path::new(pattern);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        if dir.to_string_lossy().contains(['*', '?']) {
+            return Err(StackedBarChartError::Usage(format!(
+                "Only the file name in '{}' can have wildcards",
+                pattern
+            ))
+            .into());
+        }
+
+        let regex = pattern::compile(&name)?;
+        let mut paths = vec![];
+
+        for entry in std::fs::read_dir(dir).context(format!(
+            "Unable to read directory '{}'",
+            dir.to_string_lossy()
+        ))? {
+            let path = entry?.path();
+
+            if path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|name| regex.is_match(&name.to_string_lossy()))
+            {
+                paths.push(path);
+            }
+        }
+
+        if paths.is_empty() {
+            return Err(
+                StackedBarChartError::Usage(format!("No files match '{}'", pattern)).into(),
+            );
+        }
+
+        paths.sort();
+
+        Ok(paths)
+    }
+}
@@ -1,5 +1,7 @@
 use core::fmt::Arguments;
-use stacked_bar_chart::{error, StackedBarChartLog, StackedBarChartTool};
+use stacked_bar_chart::{
+    Diagnostic, ErrorFormat, Severity, StackedBarChartLog, StackedBarChartTool,
+};
 use yansi::Paint;
 
 struct StackedBarChartLogger;
@@ -20,13 +22,27 @@ impl StackedBarChartLog for StackedBarChartLogger {
     fn error(self: &Self, args: Arguments) {
         eprintln!("{}", Paint::red(&format!("error: {}", args)));
     }
+    fn info(self: &Self, args: Arguments) {
+        eprintln!("{}", Paint::dim(&format!("info: {}", args)));
+    }
+    fn diagnostic(self: &Self, diagnostic: &Diagnostic, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Json => eprintln!("{}", diagnostic.to_json()),
+            _ => match diagnostic.severity {
+                Severity::Warning => self.warning(format_args!("{}", diagnostic.message)),
+                Severity::Error => self.error(format_args!("{}", diagnostic.message)),
+            },
+        }
+    }
 }
 
 fn main() {
     let logger = StackedBarChartLogger::new();
 
-    if let Err(error) = StackedBarChartTool::new(&logger).run(std::env::args_os()) {
-        error!(logger, "{}", error);
-        std::process::exit(1);
+    let mut tool = StackedBarChartTool::new(&logger);
+
+    if let Err(error) = tool.run(std::env::args_os()) {
+        tool.report(&error);
+        std::process::exit(error.exit_code());
     }
 }
@@ -0,0 +1,160 @@
+use crate::{
+    Bucket, ChartOptions, FillStyle, LegendPosition, Length, LogoPosition, MarginData,
+    MissingValues, NumberFormat, Orientation, PixelDensity, SortOrder, StackOrder,
+    StackedBarChartError, SummaryBar, Transform,
+};
+use std::{error::Error, path::PathBuf};
+
+/// Builds `ChartOptions` one setting at a time, checking them together in `build()`, e.g.
+/// `ChartOptions::builder().font_size(12.0).plot_height(200.0).build()?`. Each method sets the
+/// option of the same name, which is documented on `ChartOptions`.
+#[derive(Clone, Debug, Default)]
+pub struct ChartOptionsBuilder {
+    options: ChartOptions,
+}
+
+macro_rules! setters {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set `ChartOptions::", stringify!($name), "`")]
+            pub fn $name(mut self, $name: impl Into<$ty>) -> Self {
+                self.options.$name = $name.into();
+                self
+            }
+        )*
+    };
+}
+
+macro_rules! optional_setters {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set `ChartOptions::", stringify!($name), "`")]
+            pub fn $name(mut self, $name: impl Into<$ty>) -> Self {
+                self.options.$name = Some($name.into());
+                self
+            }
+        )*
+    };
+}
+
+macro_rules! list_setters {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set `ChartOptions::", stringify!($name), "`")]
+            pub fn $name(mut self, $name: impl IntoIterator<Item = impl Into<$ty>>) -> Self {
+                self.options.$name = $name.into_iter().map(Into::into).collect();
+                self
+            }
+        )*
+    };
+}
+
+impl ChartOptions {
+    /// A builder starting from the default options
+    pub fn builder() -> ChartOptionsBuilder {
+        ChartOptionsBuilder::default()
+    }
+}
+
+impl ChartOptionsBuilder {
+    setters! {
+        segment_separator: f64,
+        segment_radius: f64,
+        bar_radius: f64,
+        fill: FillStyle,
+        zebra: bool,
+        gridlines: bool,
+        gradient: bool,
+        delta: bool,
+        tooltip: String,
+        theme: String,
+        hidden_in_totals: bool,
+        missing: MissingValues,
+        replace_css: bool,
+        font_family: String,
+        font_size: f64,
+        embed_data: bool,
+        locale: String,
+        descending: bool,
+        stack_order: StackOrder,
+        change_labels: bool,
+        legend: LegendPosition,
+        legend_totals: bool,
+        legend_percent: bool,
+        animate: bool,
+        animation_duration: f64,
+        animation_stagger: f64,
+        fragment: bool,
+        responsive: bool,
+        rtl: bool,
+        orientation: Orientation,
+        plot_height: f64,
+        slot_width: f64,
+        legend_swatch: f64,
+        logo_position: LogoPosition,
+        logo_opacity: f64,
+        logo_size: f64,
+        scale: f64,
+        max_width: f64,
+        max_height: f64,
+        strict: bool,
+    }
+
+    optional_setters! {
+        seed: u64,
+        number_format: NumberFormat,
+        background: String,
+        color: String,
+        css_file: PathBuf,
+        embed_font: PathBuf,
+        template: PathBuf,
+        logo: PathBuf,
+        date_format: String,
+        key_format: String,
+        bucket: Bucket,
+        max_label_chars: usize,
+        transform: Transform,
+        summary_bar: SummaryBar,
+        filter_key: String,
+        sort: SortOrder,
+        top: usize,
+        top_categories: usize,
+        width: f64,
+        height: f64,
+        margin_top: f64,
+        margin_right: f64,
+        margin_bottom: f64,
+        margin_left: f64,
+        bar_width: Length,
+        bar_gap: Length,
+        dpi: PixelDensity,
+    }
+
+    list_setters! {
+        include_category: String,
+        exclude_category: String,
+        highlight: String,
+        palette: String,
+        margin: f64,
+    }
+
+    /// The options, or an error if they can't be used together, such as a bar too wide for its
+    /// slot or a pattern which doesn't compile
+    pub fn build(self) -> Result<ChartOptions, StackedBarChartError> {
+        let options = self.options;
+        let check = || -> Result<(), Box<dyn Error>> {
+            options.check_sizes()?;
+            options.get_bar_size()?;
+            options.get_margins(&MarginData::default())?;
+            options.get_category_filters()?;
+            options.get_key_filter()?;
+            options.get_theme()?;
+
+            Ok(())
+        };
+
+        check().map_err(|e| StackedBarChartError::classify(e, StackedBarChartError::validation))?;
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{ChartData, ChartOptions, StackedBarChartLog, StackedBarChartTool};
+use core::fmt::Arguments;
+use std::{
+    any::Any,
+    cell::RefCell,
+    error::Error,
+    ffi::{c_char, CStr, CString},
+    panic, ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Drops messages, as callers only see the result or the error
+struct NullLog;
+
+impl StackedBarChartLog for NullLog {
+    fn output(self: &Self, _args: Arguments) {}
+    fn warning(self: &Self, _args: Arguments) {}
+    fn error(self: &Self, _args: Arguments) {}
+}
+
+/// Render a chart given as JSON5 to an SVG document, with the options in its options block and
+/// then those in `options_json`, which can be null, in their place. Returns null on error, when
+/// `sbc_last_error` gives the reason. The result must be freed with `sbc_free`.
+///
+/// # Safety
+///
+/// `input_json` and `options_json` must be null or point to NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn sbc_render(
+    input_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    // Unwinding across the C boundary would abort the host process
+    let result = match panic::catch_unwind(|| render(input_json, options_json)) {
+        Ok(result) => result.and_then(|svg| Ok(CString::new(svg)?)),
+        Err(payload) => Err(panic_message(payload).into()),
+    };
+
+    LAST_ERROR.with(|last_error| match result {
+        Ok(svg) => {
+            *last_error.borrow_mut() = None;
+            svg.into_raw()
+        }
+        Err(err) => {
+            *last_error.borrow_mut() = CString::new(err.to_string()).ok();
+            ptr::null_mut()
+        }
+    })
+}
+
+/// The reason the last call to `sbc_render` on this thread failed, or null if it succeeded.
+/// The string is owned by the library and valid until the next call to `sbc_render`.
+#[no_mangle]
+pub extern "C" fn sbc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match last_error.borrow().as_ref() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Free a string returned by `sbc_render`
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by `sbc_render` which hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sbc_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The message a panic was raised with, as `sbc_last_error` reports it
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown cause".to_string(),
+        },
+    };
+
+    format!("The renderer panicked: {}", message)
+}
+
+unsafe fn render(
+    input_json: *const c_char,
+    options_json: *const c_char,
+) -> Result<String, Box<dyn Error>> {
+    if input_json.is_null() {
+        return Err("The input is null".into());
+    }
+
+    let mut chart: ChartData = json5::from_str(CStr::from_ptr(input_json).to_str()?)?;
+    let mut options = serde_json::to_value(chart.options.take().unwrap_or_default())?;
+
+    if !options_json.is_null() {
+        let overrides: serde_json::Map<String, serde_json::Value> =
+            json5::from_str(CStr::from_ptr(options_json).to_str()?)?;
+
+        for (key, value) in overrides {
+            options[key] = value;
+        }
+    }
+
+    let options: ChartOptions = serde_json::from_value(options)?;
+
+    Ok(StackedBarChartTool::new(&NullLog).render(&chart, &options)?)
+}
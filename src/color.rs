@@ -0,0 +1,243 @@
+use crate::theme::Theme;
+
+/// Used to space the hues so that neighboring categories are far apart around the color wheel
+const GOLDEN_RATIO_CONJUGATE: f64 = 0.618033988749895;
+/// The smallest OKLab distance between neighboring categories, several times the smallest
+/// difference that can be seen
+pub(crate) const MIN_DELTA_E: f64 = 0.1;
+/// The smallest WCAG contrast ratio between a category and the background, as for graphics
+pub(crate) const MIN_CONTRAST: f64 = 3.0;
+/// Categories beyond this many are also told apart by lightness, as hue alone isn't enough
+const MAX_HUES: usize = 8;
+/// How far the lightness of each tier is from the theme lightness
+const LIGHTNESS_TIER: f64 = 0.1;
+/// How far lightness is stepped when a color is too close to the background or its neighbor
+const LIGHTNESS_STEP: f64 = 0.02;
+
+/// A color in OKLCH, the polar form of the perceptually uniform OKLab, with the hue in degrees
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Oklch {
+    l: f64,
+    c: f64,
+    h: f64,
+}
+
+impl Oklch {
+    fn lab(&self) -> [f64; 3] {
+        let h = self.h.to_radians();
+
+        [self.l, self.c * h.cos(), self.c * h.sin()]
+    }
+
+    /// Linear sRGB, following https://bottosson.github.io/posts/oklab/
+    fn linear_rgb(&self) -> [f64; 3] {
+        let [l, a, b] = self.lab();
+        let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+        let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+        let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+        [
+            4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+            -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+            -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+        ]
+    }
+
+    fn in_gamut(&self) -> bool {
+        self.linear_rgb()
+            .iter()
+            .all(|c| (-1e-6..=1.0 + 1e-6).contains(c))
+    }
+
+    /// The color with its lightness set to `l` and its chroma reduced until it is in the sRGB
+    /// gamut, keeping the hue
+    fn with_lightness(&self, l: f64, chroma: f64) -> Oklch {
+        let mut color = Oklch {
+            l: l.clamp(0.0, 1.0),
+            c: chroma,
+            h: self.h,
+        };
+
+        if !color.in_gamut() {
+            let (mut low, mut high) = (0.0, chroma);
+
+            for _ in 0..24 {
+                color.c = (low + high) / 2.0;
+
+                if color.in_gamut() {
+                    low = color.c;
+                } else {
+                    high = color.c;
+                }
+            }
+
+            color.c = low;
+        }
+
+        color
+    }
+
+    fn delta_e(&self, other: &Oklch) -> f64 {
+        let ([l1, a1, b1], [l2, a2, b2]) = (self.lab(), other.lab());
+
+        ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+    }
+
+    fn luminance(&self) -> f64 {
+        luminance(self.linear_rgb().map(|c| c.clamp(0.0, 1.0)))
+    }
+
+    fn rgb(&self) -> u32 {
+        self.linear_rgb().iter().fold(0, |rgb, c| {
+            let c = c.clamp(0.0, 1.0);
+            let c = if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+
+            rgb << 8 | (c * 255.0).round() as u32
+        })
+    }
+}
+
+/// WCAG relative luminance of a linear sRGB color
+fn luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Linear sRGB of a `0xrrggbb` color
+fn linear_rgb(rgb: u32) -> [f64; 3] {
+    [rgb >> 16, rgb >> 8, rgb].map(|c| {
+        let c = (c & 0xff) as f64 / 255.0;
+
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+/// WCAG relative luminance of a `0xrrggbb` color
+fn rgb_luminance(rgb: u32) -> f64 {
+    luminance(linear_rgb(rgb))
+}
+
+fn contrast(a: f64, b: f64) -> f64 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// WCAG contrast ratio between two `0xrrggbb` colors
+#[cfg(all(test, feature = "cli"))]
+pub(crate) fn rgb_contrast(a: u32, b: u32) -> f64 {
+    contrast(rgb_luminance(a), rgb_luminance(b))
+}
+
+/// OKLab distance between two `0xrrggbb` colors
+#[cfg(all(test, feature = "cli"))]
+pub(crate) fn rgb_delta_e(a: u32, b: u32) -> f64 {
+    let lab = |rgb: u32| {
+        let [r, g, b] = linear_rgb(rgb);
+        let l_ = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m_ = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s_ = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+        [
+            0.2104542553 * l_ + 0.7936177850 * m_ - 0.0040720468 * s_,
+            1.9779984951 * l_ - 2.4285922050 * m_ + 0.4505937099 * s_,
+            0.0259040371 * l_ + 0.7827717662 * m_ - 0.8086757660 * s_,
+        ]
+    };
+    let ([l1, a1, b1], [l2, a2, b2]) = (lab(a), lab(b));
+
+    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+}
+
+/// How many hue steps there are between neighboring categories, the nearest to a golden ratio
+/// of the way around that visits every hue
+fn hue_stride(count: usize) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    let stride = (count as f64 * (1.0 - GOLDEN_RATIO_CONJUGATE)).round() as usize;
+
+    (0..count)
+        .flat_map(|offset| [stride + offset, stride.saturating_sub(offset)])
+        .find(|stride| *stride > 0 && gcd(*stride, count) == 1)
+        .unwrap_or(1)
+}
+
+/// Colors for `count` categories, as `0xrrggbb`, with hues evenly spaced around OKLCH from
+/// `start_hue` at the theme lightness and chroma. Neighboring categories are a golden ratio
+/// apart in hue, and beyond eight categories they also alternate in lightness. Each color is
+/// made darker or lighter until it contrasts with `background` and is far enough from the one
+/// before it, going back toward the background if it reaches black or white first.
+pub(crate) fn palette(count: usize, start_hue: f64, theme: &Theme, background: u32) -> Vec<u32> {
+    let stride = hue_stride(count);
+    let background = rgb_luminance(background);
+    // Darker on light backgrounds and lighter on dark ones, where black and white contrast equally
+    let away = if background > 0.18 { -1.0 } else { 1.0 };
+    let tiers: &[f64] = if count > MAX_HUES {
+        &[0.0, -1.0, 1.0]
+    } else {
+        &[0.0]
+    };
+    let mut colors: Vec<Oklch> = vec![];
+
+    for index in 0..count {
+        let base = Oklch {
+            l: theme.lightness,
+            c: theme.chroma,
+            h: (start_hue + (index * stride % count) as f64 * 360.0 / count as f64) % 360.0,
+        };
+        let mut color = base.with_lightness(
+            theme.lightness + tiers[index % tiers.len()] * LIGHTNESS_TIER,
+            theme.chroma,
+        );
+        let step = |color: &Oklch, direction: f64| {
+            base.with_lightness(color.l + direction * LIGHTNESS_STEP, theme.chroma)
+        };
+
+        // Black or white always contrast enough with a background on the other side of 0.18
+        while contrast(color.luminance(), background) < MIN_CONTRAST
+            && 0.0 < color.l
+            && color.l < 1.0
+        {
+            color = step(&color, away);
+        }
+
+        if let Some(previous) = colors.last() {
+            let start = color;
+
+            while color.delta_e(previous) < MIN_DELTA_E && 0.0 < color.l && color.l < 1.0 {
+                color = step(&color, away);
+            }
+
+            // Stopped at black or white, so go back toward the background instead for as long
+            // as the color still contrasts with it
+            if color.delta_e(previous) < MIN_DELTA_E {
+                color = start;
+
+                while color.delta_e(previous) < MIN_DELTA_E {
+                    let next = step(&color, -away);
+
+                    if next.l == color.l || contrast(next.luminance(), background) < MIN_CONTRAST {
+                        break;
+                    }
+
+                    color = next;
+                }
+            }
+        }
+
+        colors.push(color);
+    }
+
+    colors.iter().map(Oklch::rgb).collect()
+}
@@ -0,0 +1,65 @@
+use crate::{ChartOptions, Cli, StackedBarChartError};
+use easy_error::ResultExt;
+use std::{error::Error, path::PathBuf};
+
+impl Cli {
+    /// The config file given with --config, or else the user's config file if there is one
+    fn config_path(&self) -> Option<PathBuf> {
+        if self.config.is_some() {
+            return self.config.clone();
+        }
+
+        // Tests only read the config files they name, not the developer's own
+        if cfg!(test) {
+            return None;
+        }
+
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        let path = config_dir.join("stacked_bar_chart").join("config.toml");
+
+        path.is_file().then_some(path)
+    }
+
+    /// Use the options in the config file for any not given on the command line
+    pub(crate) fn apply_config(&mut self) -> Result<(), Box<dyn Error>> {
+        let path = match self.config_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let content = std::fs::read_to_string(&path).context(format!(
+            "Unable to read config file '{}'",
+            path.to_string_lossy()
+        ))?;
+        let table: toml::Table = match content.parse() {
+            Ok(table) => table,
+            Err(err) => {
+                return Err(StackedBarChartError::Parse {
+                    line: None,
+                    col: None,
+                    message: format!("Config file '{}': {}", path.to_string_lossy(), err),
+                }
+                .into())
+            }
+        };
+
+        let keys: Vec<String> = table.keys().cloned().collect();
+        let options: ChartOptions = match table.try_into() {
+            Ok(options) => options,
+            Err(err) => {
+                return Err(StackedBarChartError::Parse {
+                    line: None,
+                    col: None,
+                    message: format!("Config file '{}': {}", path.to_string_lossy(), err),
+                }
+                .into())
+            }
+        };
+
+        self.options = self.overlay_options(&options, &keys)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use crate::Bucket;
+
+/// Month and weekday names for one language, with weekdays starting on Monday
+pub struct Locale {
+    pub name: &'static str,
+    pub months: [&'static str; 12],
+    pub short_months: [&'static str; 12],
+    pub weekdays: [&'static str; 7],
+    pub short_weekdays: [&'static str; 7],
+}
+
+/// All built-in locales. The first entry is the default.
+#[rustfmt::skip]
+pub const LOCALES: &[Locale] = &[
+    Locale {
+        name: "en",
+        months: [
+            "January", "February", "March", "April", "May", "June", "July", "August", "September",
+            "October", "November", "December",
+        ],
+        short_months: [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ],
+        weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+        short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    },
+    Locale {
+        name: "de",
+        months: [
+            "Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September",
+            "Oktober", "November", "Dezember",
+        ],
+        short_months: [
+            "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+        ],
+        weekdays: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+        short_weekdays: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    },
+    Locale {
+        name: "es",
+        months: [
+            "enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre",
+            "octubre", "noviembre", "diciembre",
+        ],
+        short_months: [
+            "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+        ],
+        weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+        short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    },
+    Locale {
+        name: "fr",
+        months: [
+            "janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre",
+            "octobre", "novembre", "décembre",
+        ],
+        short_months: [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+            "nov.", "déc.",
+        ],
+        weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+        short_weekdays: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    },
+    Locale {
+        name: "it",
+        months: [
+            "gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto",
+            "settembre", "ottobre", "novembre", "dicembre",
+        ],
+        short_months: [
+            "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+        ],
+        weekdays: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+        short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    },
+    Locale {
+        name: "pt",
+        months: [
+            "janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto",
+            "setembro", "outubro", "novembro", "dezembro",
+        ],
+        short_months: [
+            "jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez",
+        ],
+        weekdays: [
+            "segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado",
+            "domingo",
+        ],
+        short_weekdays: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+    },
+];
+
+impl Locale {
+    /// Find a locale by language, ignoring any region so that 'fr-CA' finds 'fr'
+    pub fn find(name: &str) -> Option<&'static Locale> {
+        let language = name.split(['-', '_']).next().unwrap_or(name);
+
+        LOCALES
+            .iter()
+            .find(|locale| locale.name.eq_ignore_ascii_case(language))
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        LOCALES.iter().map(|locale| locale.name).collect()
+    }
+}
+
+/// A calendar date, with the day missing for keys that only give a month
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: Option<u32>,
+}
+
+impl Date {
+    /// Parse an ISO 8601 date such as '2024-03-15', or a month such as '2024-03'
+    pub fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.trim().split('-');
+        let year = parts.next().filter(|s| s.len() == 4)?.parse().ok()?;
+        let month = parts.next().filter(|s| s.len() == 2)?.parse().ok()?;
+        let day = match parts.next() {
+            Some(s) if s.len() == 2 => Some(s.parse().ok()?),
+            Some(_) => return None,
+            None => None,
+        };
+
+        if parts.next().is_some() || !(1..=12).contains(&month) {
+            return None;
+        }
+
+        if day.is_some_and(|day| day < 1 || day > Self::days_in_month(year, month)) {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    /// Parse a date in `format`, using `%Y`, `%y`, `%m`, `%d`, `%e`, `%b`, `%B` and `%%` as in
+    /// strftime with month names from `locale`. Other characters must match exactly.
+    pub fn parse_format(text: &str, format: &str, locale: &Locale) -> Option<Date> {
+        let (mut year, mut month, mut day) = (None, None, None);
+        let mut text = text.trim();
+        let mut chars = format.chars();
+
+        fn number(text: &mut &str, min: usize, max: usize) -> Option<u32> {
+            let len = text
+                .chars()
+                .take(max)
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+
+            if len < min {
+                return None;
+            }
+
+            let n = text[..len].parse().ok()?;
+
+            *text = &text[len..];
+
+            Some(n)
+        }
+
+        fn name(text: &mut &str, names: &[&str]) -> Option<u32> {
+            // Try longer names first so that 'June' isn't taken for 'Jun'
+            let mut names: Vec<(usize, &&str)> = names.iter().enumerate().collect();
+
+            names.sort_by_key(|(_, name)| std::cmp::Reverse(name.len()));
+
+            let (i, name) = names.into_iter().find(|(_, name)| {
+                text.get(..name.len())
+                    .is_some_and(|prefix| prefix.to_lowercase() == name.to_lowercase())
+            })?;
+
+            *text = &text[name.len()..];
+
+            Some(i as u32 % 12 + 1)
+        }
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text = text.strip_prefix(c)?;
+                continue;
+            }
+
+            match chars.next()? {
+                'Y' => year = Some(number(&mut text, 4, 4)? as i32),
+                'y' => year = Some(2000 + number(&mut text, 2, 2)? as i32),
+                'm' => month = Some(number(&mut text, 1, 2)?),
+                'd' => day = Some(number(&mut text, 1, 2)?),
+                'e' => {
+                    text = text.trim_start();
+                    day = Some(number(&mut text, 1, 2)?);
+                }
+                'b' | 'B' => {
+                    let names: Vec<&str> = locale
+                        .months
+                        .iter()
+                        .chain(locale.short_months.iter())
+                        .copied()
+                        .collect();
+
+                    month = Some(name(&mut text, &names)?);
+                }
+                '%' => text = text.strip_prefix('%')?,
+                _ => return None,
+            }
+        }
+
+        if !text.is_empty() {
+            return None;
+        }
+
+        let (year, month) = (year?, month?);
+
+        if !(1..=12).contains(&month)
+            || day.is_some_and(|day| day < 1 || day > Self::days_in_month(year, month))
+        {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    pub fn days_in_month(year: i32, month: u32) -> u32 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Day of the week from 0 for Monday to 6 for Sunday, using Sakamoto's method
+    pub fn weekday(&self) -> usize {
+        const OFFSETS: [i32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+        let year = if self.month < 3 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let day = self.day.unwrap_or(1) as i32;
+        let sunday_based =
+            (year + year / 4 - year / 100 + year / 400 + OFFSETS[self.month as usize - 1] + day)
+                .rem_euclid(7);
+
+        ((sunday_based + 6) % 7) as usize
+    }
+
+    /// Days since 1970-01-01, using the first of the month when there is no day
+    fn days(&self) -> i64 {
+        // Count from March so that the leap day is at the end of the year
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year =
+            (153 * ((self.month as i64 + 9) % 12) + 2) / 5 + self.day.unwrap_or(1) as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146097 + day_of_era - 719468
+    }
+
+    fn from_days(days: i64) -> Date {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let march_month = (5 * day_of_year + 2) / 153;
+        let month = if march_month < 10 {
+            march_month + 3
+        } else {
+            march_month - 9
+        };
+
+        Date {
+            year: (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }) as i32,
+            month: month as u32,
+            day: Some((day_of_year - (153 * march_month + 2) / 5 + 1) as u32),
+        }
+    }
+
+    /// The start of the day, week, month, quarter or year containing this date, with weeks
+    /// starting on Monday
+    pub fn bucket(&self, bucket: Bucket) -> Date {
+        match bucket {
+            Bucket::Day => *self,
+            Bucket::Week => Date::from_days(self.days() - self.weekday() as i64),
+            Bucket::Month => Date { day: None, ..*self },
+            Bucket::Quarter => Date {
+                month: (self.month - 1) / 3 * 3 + 1,
+                day: None,
+                ..*self
+            },
+            Bucket::Year => Date {
+                month: 1,
+                day: None,
+                ..*self
+            },
+        }
+    }
+
+    /// The date in ISO 8601 form, which `parse` reads back
+    pub fn iso(&self) -> String {
+        match self.day {
+            Some(day) => format!("{:04}-{:02}-{:02}", self.year, self.month, day),
+            None => format!("{:04}-{:02}", self.year, self.month),
+        }
+    }
+
+    /// Format the date using `%Y`, `%y`, `%m`, `%d`, `%e`, `%b`, `%B`, `%a`, `%A`, `%q` for the
+    /// quarter and `%%` as in strftime, with names from `locale`
+    pub fn format(&self, format: &str, locale: &Locale) -> String {
+        let mut result = String::new();
+        let mut chars = format.chars();
+        let month = self.month as usize - 1;
+        let day = self.day.unwrap_or(1);
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => result += &self.year.to_string(),
+                Some('y') => result += &format!("{:02}", self.year.rem_euclid(100)),
+                Some('m') => result += &format!("{:02}", self.month),
+                Some('d') => result += &format!("{:02}", day),
+                Some('e') => result += &day.to_string(),
+                Some('b') => result += locale.short_months[month],
+                Some('B') => result += locale.months[month],
+                Some('a') => result += locale.short_weekdays[self.weekday()],
+                Some('A') => result += locale.weekdays[self.weekday()],
+                Some('q') => result += &((self.month - 1) / 3 + 1).to_string(),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
+        }
+
+        result
+    }
+}
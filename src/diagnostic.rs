@@ -0,0 +1,192 @@
+use crate::{LogLevel, StackedBarChartError, StackedBarChartTool};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use serde::Serialize;
+#[cfg(feature = "cli")]
+use std::error::Error;
+use std::fmt::Arguments;
+
+/// How warnings and errors are reported
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// A line of text for each
+    #[default]
+    Text,
+    /// A line of JSON for each, with a code, message and location, for CI and editors
+    Json,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Where in the input a warning or error is, as far as is known
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// One-based line number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// One-based column number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Key of the item at fault
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+}
+
+/// A warning or error with a stable code, such as 'parse' or 'chart-too-wide', that tools can
+/// match on
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    /// The diagnostic as a single line of JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl StackedBarChartError {
+    /// The code for the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            StackedBarChartError::Usage(_) => "usage",
+            StackedBarChartError::Io { .. } => "io",
+            StackedBarChartError::Parse { .. } => "parse",
+            StackedBarChartError::Validation { .. } => "validation",
+            StackedBarChartError::Render(_) => "render",
+        }
+    }
+
+    /// The error as a diagnostic, located in `file` when it is known
+    pub fn diagnostic(&self, file: Option<String>) -> Diagnostic {
+        let mut location = Location {
+            file,
+            ..Location::default()
+        };
+
+        match self {
+            StackedBarChartError::Parse { line, col, .. } => {
+                location.line = *line;
+                location.column = *col;
+            }
+            StackedBarChartError::Validation { item, .. } => location.item = item.clone(),
+            _ => (),
+        }
+
+        Diagnostic {
+            severity: Severity::Error,
+            code: self.code(),
+            message: self.to_string(),
+            location: (location != Location::default()).then_some(location),
+        }
+    }
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Report an error returned by `run`, in the format given with --error-format, located in
+    /// the input file when the error is in its contents
+    pub fn report(self: &Self, error: &StackedBarChartError) {
+        let file = match error {
+            StackedBarChartError::Parse { .. } | StackedBarChartError::Validation { .. } => {
+                self.input_file.clone()
+            }
+            _ => None,
+        };
+
+        self.log_error(error.diagnostic(file));
+    }
+
+    /// Report an error in one input file, carrying on with the rest
+    #[cfg(feature = "cli")]
+    pub(crate) fn report_file_error(self: &Self, file: &str, error: Box<dyn Error>) {
+        let error = StackedBarChartError::classify(error, StackedBarChartError::validation);
+        let mut diagnostic = error.diagnostic(Some(file.to_string()));
+
+        if self.error_format == ErrorFormat::Text {
+            diagnostic.message = format!("{}: {}", file, diagnostic.message);
+        }
+
+        self.log_error(diagnostic);
+    }
+
+    /// The --error-format in `args`, found without parsing them so that it applies to errors
+    /// in the arguments too
+    #[cfg(feature = "cli")]
+    pub(crate) fn raw_error_format(args: &[std::ffi::OsString]) -> ErrorFormat {
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let mut format = ErrorFormat::Text;
+
+        for (i, arg) in args.iter().enumerate() {
+            let value = match arg.strip_prefix("--error-format") {
+                Some("") => args.get(i + 1).map(String::as_str),
+                Some(value) => value.strip_prefix('='),
+                None => None,
+            };
+
+            if let Some(Ok(value)) = value.map(|value| ErrorFormat::from_str(value, false)) {
+                format = value;
+            }
+        }
+
+        format
+    }
+
+    /// Log an error, on one line for JSON. Parse errors show the input around the problem on
+    /// the lines before the reason, which only fits in text.
+    fn log_error(self: &Self, mut diagnostic: Diagnostic) {
+        if self.error_format == ErrorFormat::Json {
+            let message = &diagnostic.message;
+
+            diagnostic.message = message
+                .lines()
+                .find_map(|line| line.trim_start().strip_prefix("= "))
+                .or_else(|| message.lines().next())
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+        }
+
+        self.log.diagnostic(&diagnostic, self.error_format);
+    }
+
+    pub(crate) fn report_warning(
+        self: &Self,
+        code: &'static str,
+        item: Option<&str>,
+        args: Arguments,
+    ) {
+        if self.log_level == LogLevel::Quiet {
+            return;
+        }
+
+        self.log.diagnostic(
+            &Diagnostic {
+                severity: Severity::Warning,
+                code,
+                message: args.to_string(),
+                location: item.map(|item| Location {
+                    item: Some(item.to_string()),
+                    ..Location::default()
+                }),
+            },
+            self.error_format,
+        );
+    }
+}
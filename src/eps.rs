@@ -0,0 +1,397 @@
+use crate::{text, theme, RenderData, StackedBarChartError, StackedBarChartTool, X_LABEL_OFFSET};
+use std::error::Error;
+use svg::node::element::path::{Command, Data, Position};
+
+/// Draw text left, center or right aligned, and set the font size, using a copy of the chosen
+/// font re-encoded as Latin-1 so that accented labels print correctly
+const PROLOG: &str = "/L { moveto show } bind def
+/C { moveto dup stringwidth pop 2 div neg 0 rmoveto show } bind def
+/R { moveto dup stringwidth pop neg 0 rmoveto show } bind def
+/F { /ChartFont findfont exch scalefont setfont } bind def
+";
+
+/// The standard PostScript font closest to a CSS font family
+fn postscript_font(family: &str) -> &'static str {
+    let family = family.to_lowercase();
+
+    if family.contains("courier") || family.contains("mono") {
+        "Courier"
+    } else if family.contains("times") || family.starts_with("serif") || family.contains(" serif") {
+        "Times-Roman"
+    } else {
+        "Helvetica"
+    }
+}
+
+/// Convert a theme or background color to PostScript
+fn css_color(color: &str) -> Result<String, Box<dyn Error>> {
+    match theme::parse_color(color) {
+        Some(rgb) => Ok(rgb_color(rgb)),
+        None => Err(StackedBarChartError::Usage(format!(
+            "Color '{}' cannot be used in EPS output, which needs rgb() or # colors",
+            color.trim()
+        ))
+        .into()),
+    }
+}
+
+fn rgb_color(rgb: u32) -> String {
+    let component = |shift: u32| format!("{:.3}", (rgb >> shift & 0xff) as f64 / 255.0);
+
+    format!(
+        "{} {} {} setrgbcolor",
+        component(16),
+        component(8),
+        component(0)
+    )
+}
+
+/// A number rounded to hundredths, which is finer than any printer can show
+fn number(n: f64) -> String {
+    let rounded = format!("{:.2}", n);
+
+    match rounded.trim_end_matches('0').trim_end_matches('.') {
+        "-0" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// A PostScript string literal, with characters outside Latin-1 replaced by '?'
+fn string(text: &str) -> String {
+    let mut literal = String::from("(");
+
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            ' '..='~' => literal.push(c),
+            '\u{a0}'..='\u{ff}' => literal += &format!("\\{:03o}", c as u32),
+            _ => literal.push('?'),
+        }
+    }
+
+    literal + ")"
+}
+
+/// Text for a DSC comment such as '%%Title:', which must stay on one line of printable ASCII
+/// no longer than 255 characters, so anything else is replaced by '?'
+fn comment(keyword: &str, text: &str) -> String {
+    let text: String = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => c,
+            _ => '?',
+        })
+        .take(255 - keyword.len() - 1)
+        .collect();
+
+    format!("{} {}", keyword, text)
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Render the chart as Encapsulated PostScript. Patterns, gradients, animation and custom
+    /// CSS have no equivalent so segments are filled with their solid category colors.
+    pub(crate) fn render_eps(rd: &RenderData) -> Result<String, Box<dyn Error>> {
+        if rd.horizontal {
+            return Err(StackedBarChartError::Usage(
+                "EPS output can only draw vertical bars".to_string(),
+            )
+            .into());
+        }
+
+        let (width, height) = (rd.width(), rd.height());
+        let (canvas_width, canvas_height) = rd.canvas_size();
+        let foreground = css_color(rd.theme.foreground)?;
+        // PostScript has its origin at the bottom left
+        let flip = |y: f64| height - y;
+        let mut eps = format!(
+            "%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 {} {}\n\
+            %%HiResBoundingBox: 0 0 {:.2} {:.2}\n{}\n%%Creator: stacked-bar-chart\n\
+            %%EndComments\n{}/ChartFont /{} findfont dup length dict begin\n\
+            {{ 1 index /FID ne {{ def }} {{ pop pop }} ifelse }} forall\n\
+            /Encoding ISOLatin1Encoding def currentdict end definefont pop\n\
+            gsave\n{} {} scale\n",
+            canvas_width.ceil(),
+            canvas_height.ceil(),
+            canvas_width,
+            canvas_height,
+            comment("%%Title:", &rd.title_lines.join(" ")),
+            PROLOG,
+            postscript_font(&rd.font_family),
+            canvas_width / width,
+            canvas_height / height,
+        );
+
+        if let Some(background) = &rd.background {
+            eps += &format!(
+                "{}\n0 0 {} {} rectfill\n",
+                css_color(background)?,
+                width,
+                height
+            );
+        }
+
+        let num_y_labels =
+            ((rd.y_axis_range.1 - rd.y_axis_range.0) / rd.y_axis_interval) as usize + 1;
+
+        if rd.zebra {
+            eps += &format!("{}\n", css_color(rd.theme.zebra)?);
+
+            for i in (1..num_y_labels - 1).step_by(2) {
+                let y = rd.gutter.top + rd.y_axis_height
+                    - rd.scale((i + 1) as f64 * rd.y_axis_interval);
+                let stripe_height = rd.scale(rd.y_axis_interval);
+
+                eps += &format!(
+                    "{} {} {} {} rectfill\n",
+                    rd.gutter.left,
+                    flip(y + stripe_height),
+                    rd.x_axis_width,
+                    stripe_height
+                );
+            }
+        }
+
+        if let Some(rgb) = rd.gridlines {
+            eps += &format!("{}\n1 setlinewidth\n", rgb_color(rgb));
+
+            for i in 1..num_y_labels {
+                let y = rd.gutter.top + rd.y_axis_height
+                    - f64::floor(rd.scale(i as f64 * rd.y_axis_interval));
+
+                eps += &format!(
+                    "newpath {} {} moveto {} {} lineto stroke\n",
+                    rd.gutter.left,
+                    flip(y),
+                    rd.plot_right(),
+                    flip(y)
+                );
+            }
+        }
+
+        let positive = css_color(rd.theme.positive)?;
+        let negative = css_color(rd.theme.negative)?;
+
+        for bar in &rd.bar_data {
+            let (segments, separators) = rd.bar_segments(bar);
+
+            for segment in segments.iter().filter(|segment| segment.height > 0.0) {
+                let color = match (rd.delta, segment.negative) {
+                    _ if segment.missing => css_color(rd.theme.zebra)?,
+                    (false, _) => rgb_color(rd.category_colors[segment.category]),
+                    (true, false) => positive.clone(),
+                    (true, true) => negative.clone(),
+                };
+                let data = Self::segment_path(
+                    segment.x,
+                    segment.y,
+                    segment.width,
+                    segment.height,
+                    segment.top_radius,
+                    segment.bottom_radius,
+                );
+
+                eps += &format!("{}\n{}fill\n", color, Self::eps_path(&data, height));
+            }
+
+            if rd.segment_separator_width > 0.0 {
+                eps += &format!(
+                    "{}\n{} setlinewidth\n",
+                    css_color(rd.theme.separator)?,
+                    rd.segment_separator_width
+                );
+
+                for (x, y, separator_width) in separators {
+                    eps += &format!(
+                        "newpath {} {} moveto {} 0 rlineto stroke\n",
+                        x,
+                        flip(y),
+                        separator_width
+                    );
+                }
+            }
+        }
+
+        let axis_bottom = flip(rd.gutter.top + rd.y_axis_height);
+
+        eps += &format!(
+            "{}\n1 setlinewidth\nnewpath {} {} moveto {} {} lineto {} {} lineto stroke\n",
+            foreground,
+            rd.y_axis_x(),
+            flip(rd.gutter.top),
+            rd.y_axis_x(),
+            axis_bottom,
+            rd.gutter.left + rd.plot_right() - rd.y_axis_x(),
+            axis_bottom
+        );
+
+        if rd.y_axis_range.0 < 0.0 {
+            eps += &format!(
+                "newpath {} {} moveto {} {} lineto stroke\n",
+                rd.gutter.left,
+                flip(rd.baseline()),
+                rd.plot_right(),
+                flip(rd.baseline())
+            );
+        }
+
+        eps += &format!("{} F\n", rd.font_size);
+
+        // Right to left, item labels hang down to the left, ending at the bar
+        let (rotation, align) = if rd.rtl { (45, "R") } else { (-45, "L") };
+
+        for bar in &rd.bar_data {
+            let label = text::truncate(&bar.label, rd.max_label_chars);
+
+            eps += &format!(
+                "gsave {} {} translate {} rotate {} 0 0 {} grestore\n",
+                rd.gutter.left + bar.x + bar.width / 2.0,
+                flip(rd.gutter.top + rd.y_axis_height + X_LABEL_OFFSET),
+                rotation,
+                string(label.as_deref().unwrap_or(&bar.label)),
+                align
+            );
+        }
+
+        for i in 0..num_y_labels {
+            let n = i as f64 * rd.y_axis_interval;
+
+            eps += &format!(
+                "{} {} {} {}\n",
+                string(&rd.y_label(n + rd.y_axis_range.0)),
+                if rd.rtl {
+                    rd.y_axis_x() + 10.0
+                } else {
+                    rd.y_axis_x() - 10.0
+                },
+                flip(rd.gutter.top + rd.y_axis_height - f64::floor(rd.scale(n)) + 5.0),
+                if rd.rtl { "L" } else { "R" }
+            );
+        }
+
+        eps += &format!("{} F\n", rd.font_size * 1.2);
+
+        let title_top =
+            rd.title_area() / 2.0 - (rd.title_lines.len() - 1) as f64 * rd.title_line_height / 2.0;
+
+        for (i, line) in rd.title_lines.iter().enumerate() {
+            eps += &format!(
+                "{} {} {} C\n",
+                string(line),
+                width / 2.0,
+                flip(title_top + i as f64 * rd.title_line_height)
+            );
+        }
+
+        // The legend font size is given in points, which are larger than pixels
+        eps += &format!("{} F\n", rd.font_size * 1.2 * 4.0 / 3.0);
+
+        let (left, top) = rd.legend_origin();
+
+        for i in 0..rd.legend_labels.len() {
+            let x = left + rd.legend_entries[i].0;
+            let y = top + rd.legend_entries[i].1;
+            let class = &rd.legend_classes[i];
+            let color = if class.contains("negative") {
+                negative.clone()
+            } else if class.contains("positive") {
+                positive.clone()
+            } else {
+                let index = class
+                    .trim_start_matches("category-")
+                    .parse::<usize>()
+                    .unwrap_or(0);
+
+                rgb_color(rd.category_colors[index])
+            };
+            let block = Self::segment_path(
+                x,
+                y + rd.legend_rect_size,
+                rd.legend_rect_size,
+                rd.legend_rect_size,
+                rd.legend_rect_corner_radius,
+                rd.legend_rect_corner_radius,
+            );
+            let label = text::truncate(&rd.legend_labels[i], rd.max_label_chars)
+                .unwrap_or_else(|| rd.legend_labels[i].clone());
+
+            eps += &format!(
+                "{}\n{}fill\n{}\n{} {} {} {}\n",
+                color,
+                Self::eps_path(&block, height),
+                foreground,
+                string(&(label + &rd.legend_suffixes[i])),
+                rd.legend_text_x(x),
+                flip(y + rd.legend_rect_size * 0.75),
+                if rd.rtl { "R" } else { "L" }
+            );
+        }
+
+        if let Some(caption) = &rd.caption {
+            eps += &format!(
+                "{} F\n{} {} {} {}\n",
+                rd.font_size * 0.9,
+                string(caption),
+                rd.caption_x(),
+                flip(height - rd.legend_gutter.bottom),
+                if rd.rtl { "L" } else { "R" }
+            );
+        }
+
+        eps += "grestore\nshowpage\n%%EOF\n";
+
+        Ok(eps)
+    }
+
+    /// Convert SVG path data made of moves, lines and quadratic curves to a PostScript path,
+    /// flipping it vertically within `height`
+    fn eps_path(data: &Data, height: f64) -> String {
+        let mut path = String::from("newpath");
+        let (mut x, mut y) = (0.0, 0.0);
+        let point = |x: f64, y: f64| format!(" {} {}", number(x), number(height - y));
+
+        for command in data.iter() {
+            let (position, parameters) = match command {
+                Command::Move(position, parameters)
+                | Command::Line(position, parameters)
+                | Command::QuadraticCurve(position, parameters) => (position, parameters),
+                _ => {
+                    path += " closepath";
+                    continue;
+                }
+            };
+            let numbers: Vec<f64> = parameters.iter().map(|n| *n as f64).collect();
+            let (dx, dy) = match position {
+                Position::Absolute => (0.0, 0.0),
+                Position::Relative => (x, y),
+            };
+
+            match (command, numbers.as_slice()) {
+                (Command::QuadraticCurve(..), [cx, cy, ex, ey]) => {
+                    // Raise the quadratic curve to the cubic one PostScript draws
+                    let (cx, cy, ex, ey) = (cx + dx, cy + dy, ex + dx, ey + dy);
+
+                    path += &point(x + (cx - x) * 2.0 / 3.0, y + (cy - y) * 2.0 / 3.0);
+                    path += &point(ex + (cx - ex) * 2.0 / 3.0, ey + (cy - ey) * 2.0 / 3.0);
+                    path += &point(ex, ey);
+                    path += " curveto";
+                    (x, y) = (ex, ey);
+                }
+                (_, [px, py]) => {
+                    (x, y) = (px + dx, py + dy);
+                    path += &point(x, y);
+                    path += if matches!(command, Command::Move(..)) {
+                        " moveto"
+                    } else {
+                        " lineto"
+                    };
+                }
+                _ => (),
+            }
+        }
+
+        path + "\n"
+    }
+}
@@ -0,0 +1,112 @@
+use std::{error::Error, fmt, io};
+
+/// The kinds of error returned by the library, so that callers can tell bad input from a failure
+/// to read or write a file. Internally errors are boxed, and are sorted into these kinds when
+/// they are returned from the public API.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StackedBarChartError {
+    /// The command line arguments are not valid
+    Usage(String),
+    /// A file could not be read or written
+    Io { message: String, source: io::Error },
+    /// A chart or config file is not valid JSON5, JSON or TOML, with the one-based line and
+    /// column of the problem when known
+    Parse {
+        line: Option<usize>,
+        col: Option<usize>,
+        message: String,
+    },
+    /// The chart data or options cannot be charted, with the key of the item at fault when there
+    /// is just one
+    Validation {
+        item: Option<String>,
+        reason: String,
+    },
+    /// The chart could not be rendered or failed the output checks
+    Render(String),
+}
+
+impl StackedBarChartError {
+    /// Sort a boxed error into a kind, using `fallback` for errors with only a message
+    pub(crate) fn classify(error: Box<dyn Error>, fallback: fn(String) -> Self) -> Self {
+        let error = match error.downcast::<StackedBarChartError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        // easy_error adds where in the source the error was made, which is no use to users
+        let message = match error.downcast_ref::<easy_error::Error>() {
+            Some(error) => error.ctx.clone(),
+            None => error.to_string(),
+        };
+
+        if let Some(json5::Error::Message { location, .. }) = error.downcast_ref::<json5::Error>() {
+            return StackedBarChartError::Parse {
+                line: location.as_ref().map(|location| location.line),
+                col: location.as_ref().map(|location| location.column),
+                message,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<serde_json::Error>() {
+            if !error.is_io() {
+                return StackedBarChartError::Parse {
+                    line: Some(error.line()),
+                    col: Some(error.column()),
+                    message,
+                };
+            }
+        }
+
+        // Errors given context with easy_error keep the underlying I/O error as their source
+        let io_error = error
+            .downcast_ref::<io::Error>()
+            .or_else(|| error.source().and_then(|source| source.downcast_ref()));
+
+        match io_error {
+            Some(io_error) => StackedBarChartError::Io {
+                message,
+                source: io::Error::new(io_error.kind(), io_error.to_string()),
+            },
+            None => fallback(message),
+        }
+    }
+
+    /// A validation error not tied to a single item
+    pub(crate) fn validation(reason: String) -> Self {
+        StackedBarChartError::Validation { item: None, reason }
+    }
+
+    /// The process exit code for the error, following the BSD sysexits conventions
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StackedBarChartError::Usage(_) => 64,
+            StackedBarChartError::Parse { .. } | StackedBarChartError::Validation { .. } => 65,
+            StackedBarChartError::Render(_) => 70,
+            StackedBarChartError::Io { .. } => 74,
+        }
+    }
+}
+
+impl fmt::Display for StackedBarChartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackedBarChartError::Usage(message)
+            | StackedBarChartError::Io { message, .. }
+            | StackedBarChartError::Parse { message, .. }
+            | StackedBarChartError::Validation {
+                reason: message, ..
+            }
+            | StackedBarChartError::Render(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for StackedBarChartError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StackedBarChartError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
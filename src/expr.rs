@@ -0,0 +1,201 @@
+use crate::FromStrVisitor;
+use serde::{Deserialize, Serialize};
+use std::{iter::Peekable, str::CharIndices};
+
+/// An arithmetic expression in `v`, such as `v / 1024 / 1024`, applied to each value before it
+/// is charted. Numbers, `v`, `+`, `-`, `*`, `/` and parentheses are all that are allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Value,
+    Number(f64),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+impl Transform {
+    /// The expression evaluated with `v` as `value`
+    pub fn apply(&self, value: f64) -> f64 {
+        self.expr.eval(value)
+    }
+}
+
+impl Expr {
+    fn eval(&self, value: f64) -> f64 {
+        match self {
+            Expr::Value => value,
+            Expr::Number(n) => *n,
+            Expr::Negate(expr) => -expr.eval(value),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(value), right.eval(value));
+
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ => left / right,
+                }
+            }
+        }
+    }
+}
+
+/// A recursive descent parser for the expression grammar:
+///
+/// ```text
+/// sum     = product (('+' | '-') product)*
+/// product = unary (('*' | '/') unary)*
+/// unary   = '-' unary | primary
+/// primary = number | 'v' | '(' sum ')'
+/// ```
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// The next character that isn't white space, without consuming it
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn error(&mut self, expected: &str) -> String {
+        match self.chars.peek() {
+            Some((i, c)) => format!(
+                "Transform '{}' expected {} but found '{}' at column {}",
+                self.source,
+                expected,
+                c,
+                i + 1
+            ),
+            None => format!(
+                "Transform '{}' expected {} but ended",
+                self.source, expected
+            ),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some('-') {
+            self.chars.next();
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('v') => {
+                self.chars.next();
+                Ok(Expr::Value)
+            }
+            Some('(') => {
+                self.chars.next();
+                let expr = self.sum()?;
+
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(expr)
+                    }
+                    _ => Err(self.error("')'")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let source = self.source;
+                let start = self.chars.peek().map_or(0, |(i, _)| *i);
+                let mut end = start;
+
+                // Digits, a decimal point and an exponent such as 'e-9'
+                while let Some((i, c)) = self.chars.next_if(|(i, c)| {
+                    c.is_ascii_alphanumeric()
+                        || *c == '.'
+                        || (matches!(c, '+' | '-')
+                            && matches!(source[..*i].chars().last(), Some('e' | 'E')))
+                }) {
+                    end = i + c.len_utf8();
+                }
+
+                match source[start..end].parse() {
+                    Ok(n) => Ok(Expr::Number(n)),
+                    Err(_) => Err(format!(
+                        "Transform '{}' has a bad number '{}' at column {}",
+                        source,
+                        &source[start..end],
+                        start + 1
+                    )),
+                }
+            }
+            _ => Err(self.error("a number, 'v' or '('")),
+        }
+    }
+}
+
+impl std::str::FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source: s,
+            chars: s.char_indices().peekable(),
+        };
+        let expr = parser.sum()?;
+
+        if parser.peek().is_some() {
+            return Err(parser.error("an operator"));
+        }
+
+        Ok(Transform {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Serialize for Transform {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FromStrVisitor(std::marker::PhantomData))
+    }
+}
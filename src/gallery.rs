@@ -0,0 +1,90 @@
+use crate::{text::escape_html, ChartOptions, ChartSpec, StackedBarChartTool, Theme, THEMES};
+use base64::prelude::*;
+use easy_error::ResultExt;
+use std::{error::Error, path::Path};
+
+const GALLERY_STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+section{margin-bottom:3em}\
+.charts{display:flex;flex-wrap:wrap;gap:1em}\
+figure{margin:0;max-width:320px}\
+figure img{width:100%;border:1px solid #ccc}\
+.error{color:#c00}\
+pre{background:#f4f4f4;padding:1em;overflow:auto;max-height:20em}";
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Render every chart file in `dir` once for each theme into a single HTML page, with the
+    /// source of each chart beneath its thumbnails. Charts which fail to render show their
+    /// error in place of a thumbnail so that the rest of the gallery can still be reviewed.
+    pub(crate) fn render_gallery(self: &Self, dir: &Path) -> Result<String, Box<dyn Error>> {
+        let mut paths = vec![];
+
+        for entry in std::fs::read_dir(dir).context(format!(
+            "Unable to read directory '{}'",
+            dir.to_string_lossy()
+        ))? {
+            let path = entry?.path();
+
+            if path
+                .extension()
+                .is_some_and(|ext| ext == "json5" || ext == "json")
+            {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>Stacked Bar Chart Gallery</title>\n<style>{}</style>\n</head>\n<body>\n\
+            <h1>Stacked Bar Chart Gallery</h1>\n",
+            GALLERY_STYLE
+        );
+
+        for path in paths {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let source = std::fs::read_to_string(&path)
+                .context(format!("Unable to read file '{}'", path.to_string_lossy()))?;
+
+            html += &format!(
+                "<section>\n<h2>{}</h2>\n<div class=\"charts\">\n",
+                escape_html(&name)
+            );
+
+            for theme in THEMES {
+                let figure = match self.render_source(&source, theme) {
+                    Ok(svg) => format!(
+                        "<img src=\"data:image/svg+xml;base64,{}\" alt=\"{} with the {} theme\">",
+                        BASE64_STANDARD.encode(svg),
+                        escape_html(&name),
+                        theme.name
+                    ),
+                    Err(error) => {
+                        format!("<p class=\"error\">{}</p>", escape_html(&error.to_string()))
+                    }
+                };
+
+                html += &format!(
+                    "<figure>\n{}\n<figcaption>{}</figcaption>\n</figure>\n",
+                    figure, theme.name
+                );
+            }
+
+            html += &format!("</div>\n<pre>{}</pre>\n</section>\n", escape_html(&source));
+        }
+
+        html += "</body>\n</html>\n";
+
+        Ok(html)
+    }
+
+    fn render_source(self: &Self, source: &str, theme: &Theme) -> Result<String, Box<dyn Error>> {
+        let mut spec = ChartSpec::new(json5::from_str(source)?, ChartOptions::default())?;
+
+        spec.theme = theme.clone();
+
+        let render_data = self.process_chart_data(&spec)?;
+
+        Ok(self.render_chart(&render_data)?.to_string())
+    }
+}
@@ -0,0 +1,101 @@
+use crate::text::escape_html;
+
+const PAGE_STYLE: &str = "body{margin:2em;font-family:sans-serif}";
+
+const INTERACTIVE_STYLE: &str = "svg path[class*=category-]{transition:opacity 0.2s}\
+svg .dimmed{opacity:0.25}\
+svg [aria-label=Legend] > *{cursor:pointer}\
+svg [aria-label=Legend] .off{opacity:0.35}";
+
+/// Highlights the category under the pointer in every bar, and toggles categories when their
+/// legend entry is clicked, moving the segments above a hidden one down to close the gap
+const PAGE_SCRIPT: &str = r#"(function () {
+  const svg = document.querySelector("svg");
+  const bars = svg.querySelectorAll("[aria-label=Bars] > g");
+  const legend = svg.querySelector("[aria-label=Legend]");
+  const hidden = new Set();
+  const categoryOf = (element) => {
+    const match = /category-(\d+)/.exec(element.getAttribute("class") || "");
+    return match ? match[1] : null;
+  };
+  const segments = svg.querySelectorAll("[aria-label=Bars] path");
+
+  segments.forEach((segment) => {
+    segment.dataset.height = segment.getBBox().height;
+    segment.addEventListener("mouseenter", () => {
+      const category = categoryOf(segment);
+      segments.forEach((other) =>
+        other.classList.toggle("dimmed", categoryOf(other) !== category)
+      );
+    });
+    segment.addEventListener("mouseleave", () =>
+      segments.forEach((other) => other.classList.remove("dimmed"))
+    );
+  });
+
+  const restack = () => {
+    bars.forEach((bar) => {
+      const shifts = {};
+      bar.querySelectorAll("path").forEach((segment) => {
+        const negative = segment.classList.contains("negative");
+        const key = segment.getBBox().x + (negative ? "-" : "+");
+        const shift = shifts[key] || 0;
+        const isHidden = hidden.has(categoryOf(segment));
+        segment.style.display = isHidden ? "none" : "";
+        segment.setAttribute("transform", "translate(0," + shift + ")");
+        if (isHidden) {
+          const height = parseFloat(segment.dataset.height);
+          shifts[key] = shift + (negative ? -height : height);
+        }
+      });
+      bar.querySelectorAll(".separator").forEach((line) => {
+        line.style.display = hidden.size ? "none" : "";
+      });
+    });
+  };
+
+  if (legend) {
+    const children = Array.from(legend.children);
+    children.forEach((child, index) => {
+      const swatch = child.tagName === "rect" ? child : children[index - 1];
+      const category = swatch && categoryOf(swatch);
+      if (!swatch || category === null) {
+        return;
+      }
+      child.addEventListener("click", () => {
+        if (hidden.has(category)) {
+          hidden.delete(category);
+        } else {
+          hidden.add(category);
+        }
+        [swatch, swatch.nextElementSibling].forEach((element) =>
+          element.classList.toggle("off", hidden.has(category))
+        );
+        restack();
+      });
+    });
+  }
+})();"#;
+
+/// Wrap a rendered chart in a standalone HTML page titled with the chart title. When
+/// `interactive` the page has a script which highlights a category on hover and shows or hides
+/// categories when their legend entries are clicked.
+pub fn page(title: &str, svg: &str, interactive: bool) -> String {
+    let (style, script) = if interactive {
+        (
+            format!("{}\n{}", PAGE_STYLE, INTERACTIVE_STYLE),
+            format!("<script>\n{}\n</script>\n", PAGE_SCRIPT),
+        )
+    } else {
+        (PAGE_STYLE.to_string(), String::new())
+    };
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+        <style>{}</style>\n</head>\n<body>\n{}\n{}</body>\n</html>\n",
+        escape_html(title),
+        style,
+        svg.trim(),
+        script
+    )
+}
@@ -1,35 +1,121 @@
+#[cfg(feature = "cli")]
+mod batch;
+mod builder;
+#[cfg(feature = "capi")]
+mod capi;
+mod color;
+#[cfg(feature = "cli")]
+mod config;
+mod dates;
+mod diagnostic;
+#[cfg(feature = "cli")]
+mod eps;
+mod error;
+mod expr;
+#[cfg(feature = "cli")]
+mod gallery;
+#[cfg(feature = "cli")]
+mod html;
 mod log_macros;
+#[cfg(feature = "cli")]
+mod minify;
+mod pattern;
+#[cfg(feature = "png")]
+mod png;
+#[cfg(feature = "cli")]
+mod stream;
+mod template;
+#[cfg(feature = "cli")]
+mod term;
+mod text;
+mod theme;
+#[cfg(feature = "cli")]
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "cli")]
+mod watch;
 
-use clap::Parser;
+use base64::prelude::*;
+#[cfg(feature = "cli")]
+use clap::{
+    parser::ValueSource, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
 use core::fmt::Arguments;
-use easy_error::{self, bail, ResultExt};
+#[cfg(feature = "cli")]
+use easy_error::ResultExt;
 use rand::prelude::*;
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
     error::Error,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "cli")]
+use std::{
     fs::File,
     io::{self, Read, Write},
-    path::PathBuf,
 };
+#[cfg(feature = "cli")]
+use stream::InputFormat;
 use svg::{
     node::{element::path, *},
     Document,
 };
 
-const GOLDEN_RATIO_CONJUGATE: f32 = 0.618033988749895;
+pub use builder::ChartOptionsBuilder;
+pub use diagnostic::{Diagnostic, ErrorFormat, Location, Severity};
+pub use error::StackedBarChartError;
+pub use expr::Transform;
+pub use theme::{Theme, THEMES};
+
+const PRECISION_LOSS_TOLERANCE: f64 = 1e-9;
+const MAX_Y_AXIS_RANGE: f64 = 1e307;
+const X_LABEL_OFFSET: f64 = 15.0;
+#[cfg(feature = "cli")]
+const DETERMINISTIC_PRECISION: usize = 3;
+const METADATA_ELEMENT: &str = "chart-data";
+const METADATA_NAMESPACE: &str = "https://github.com/jlyonsmith/stacked_bar_chart";
+const FILE_OPTIONS: [&str; 4] = ["css_file", "embed_font", "logo", "template"];
+const DEFAULT_TOOLTIP: &str = "{item}, {category}: {value} {units} ({percent}%)";
 
 pub trait StackedBarChartLog {
     fn output(self: &Self, args: Arguments);
     fn warning(self: &Self, args: Arguments);
     fn error(self: &Self, args: Arguments);
+
+    /// Details of how a chart was laid out, given with --verbose, which are ignored by default
+    fn info(self: &Self, _args: Arguments) {}
+
+    /// Report a warning or error from rendering in `format`. By default only the message is
+    /// passed on to `warning` or `error`, so implement this to write JSON or use the location.
+    fn diagnostic(self: &Self, diagnostic: &Diagnostic, _format: ErrorFormat) {
+        match diagnostic.severity {
+            Severity::Warning => self.warning(format_args!("{}", diagnostic.message)),
+            Severity::Error => self.error(format_args!("{}", diagnostic.message)),
+        }
+    }
 }
 
 pub struct StackedBarChartTool<'a> {
     log: &'a dyn StackedBarChartLog,
+    error_format: ErrorFormat,
+    log_level: LogLevel,
+    /// The input file of the last run, which errors reported from it are located in
+    input_file: Option<String>,
 }
 
-#[derive(Parser)]
-#[clap(version, about, long_about = None)]
+/// Which messages other than errors are logged
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum LogLevel {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Parser, Serialize, Clone)]
+#[clap(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Cli {
     /// Disable colors in output
     #[arg(long = "no-color", short = 'n', env = "NO_CLI_COLOR")]
@@ -42,419 +128,8326 @@ struct Cli {
     /// The output file
     #[arg(value_name = "OUTPUT_FILE")]
     output_file: Option<PathBuf>,
-}
 
-impl Cli {
-    fn get_output(&self) -> Result<Box<dyn Write>, Box<dyn Error>> {
-        match self.output_file {
-            Some(ref path) => File::create(path)
-                .context(format!(
-                    "Unable to create file '{}'",
-                    path.to_string_lossy()
-                ))
-                .map(|f| Box::new(f) as Box<dyn Write>)
-                .map_err(|e| Box::new(e) as Box<dyn Error>),
-            None => Ok(Box::new(io::stdout())),
-        }
-    }
+    /// Render every chart file matching this pattern, such as 'charts/*.json5', into --out-dir
+    #[arg(
+        long = "batch",
+        value_name = "PATTERN",
+        requires = "out_dir",
+        conflicts_with_all = ["input_file", "output_file"]
+    )]
+    batch: Option<String>,
 
-    fn get_input(&self) -> Result<Box<dyn Read>, Box<dyn Error>> {
-        match self.input_file {
-            Some(ref path) => File::open(path)
-                .context(format!("Unable to open file '{}'", path.to_string_lossy()))
-                .map(|f| Box::new(f) as Box<dyn Read>)
-                .map_err(|e| Box::new(e) as Box<dyn Error>),
-            None => Ok(Box::new(io::stdin())),
-        }
-    }
-}
+    /// The directory for the output files of --batch, named after the input files
+    #[arg(long = "out-dir", value_name = "DIR", requires = "batch")]
+    out_dir: Option<PathBuf>,
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct ChartData {
-    pub title: String,
-    pub units: String,
-    pub categories: Vec<String>,
-    pub items: Vec<ItemData>,
-}
+    /// Render the chart again whenever the input file or any merged file changes
+    #[arg(long = "watch", requires_all = ["input_file", "output_file"])]
+    watch: bool,
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct ItemData {
-    pub key: String,
-    pub values: Vec<f64>,
-}
+    /// The input format, which defaults to CSV or NDJSON for files with those extensions and
+    /// otherwise JSON5. CSV and NDJSON are read a line at a time so can be any size.
+    #[arg(long = "input-format", value_enum)]
+    input_format: Option<InputFormat>,
 
-#[derive(Debug)]
-struct Gutter {
-    left: f64,
-    top: f64,
-    right: f64,
-    bottom: f64,
-}
+    /// Sum consecutive items of CSV and NDJSON input so there are at most this many bars
+    #[arg(long = "max-bars", value_name = "BARS", default_value_t = 500)]
+    max_bars: usize,
 
-impl Gutter {
-    pub fn top_bottom(&self) -> f64 {
-        self.bottom + self.top
-    }
+    /// Other chart files to merge into the input, combining items with the same key
+    #[arg(long = "merge", value_name = "FILE", num_args = 1..)]
+    merge: Vec<PathBuf>,
 
-    pub fn left_right(&self) -> f64 {
-        self.right + self.left
-    }
-}
+    /// Whether merged charts sum the values of categories with the same name, or concatenate
+    /// all the categories side by side
+    #[arg(long = "merge-policy", value_enum, default_value = "sum")]
+    merge_policy: MergePolicy,
 
-#[derive(Debug)]
-struct BarData {
-    label: String,
-    values: Vec<f64>,
-}
+    /// Title for the chart in place of the one in the input file
+    #[arg(long = "title", value_name = "TITLE")]
+    title: Option<String>,
 
-#[derive(Debug)]
-struct RenderData {
-    title: String,
-    categories: Vec<String>,
-    gutter: Gutter,
-    y_axis_height: f64,
-    y_axis_range: (f64, f64),
-    y_axis_interval: f64,
-    y_axis_decimal_places: usize,
-    x_axis_item_width: f64,
-    bar_data: Vec<BarData>,
-    styles: Vec<String>,
-    legend_gutter: Gutter,
-    legend_rect_size: f64,
-    legend_rect_corner_radius: f64,
+    /// Units for the chart in place of those in the input file
+    #[arg(long = "units", value_name = "UNITS")]
+    units: Option<String>,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    options: ChartOptions,
+
+    /// A TOML file of default options and theme, in place of
+    /// '~/.config/stacked_bar_chart/config.toml'
+    #[arg(long = "config", value_name = "CONFIG_FILE")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+
+    /// Hide confidential data so the chart can be shared, as a comma separated list
+    #[arg(long = "redact", value_enum, value_delimiter = ',')]
+    redact: Vec<Redact>,
+
+    /// The output format, either plain SVG, a PNG or EPS image, an HTML page with interactive
+    /// highlighting, or text for a terminal. Defaults to the extension of the output file for PNG
+    /// and EPS, otherwise SVG.
+    #[arg(long = "format", value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Output only the contents of the root element, implying --fragment
+    #[arg(long = "inner-only")]
+    inner_only: bool,
+
+    /// Shrink the SVG by rounding numbers and leaving out whitespace
+    #[arg(long = "minify")]
+    minify: bool,
+
+    /// Decimal places kept in numbers when minifying
+    #[arg(
+        long = "precision",
+        value_name = "DIGITS",
+        default_value_t = 2,
+        requires = "minify"
+    )]
+    precision: usize,
+
+    /// Make the output depend only on the input, so that rendering the same data always gives
+    /// identical files, by seeding redaction and rounding all numbers to a fixed precision
+    #[arg(long = "deterministic")]
+    deterministic: bool,
+
+    /// Leave the script out of HTML pages, for sending by email or to sites which block scripts
+    #[arg(long = "no-script")]
+    no_script: bool,
+
+    /// Only log errors
+    #[arg(long = "quiet", short = 'q', conflicts_with = "verbose")]
+    #[serde(skip)]
+    quiet: bool,
+
+    /// Also log how each chart was laid out, such as its y-axis range, size and colors
+    #[arg(long = "verbose", short = 'v')]
+    #[serde(skip)]
+    verbose: bool,
+
+    /// Report warnings and errors as text or as JSON lines
+    #[arg(long = "error-format", value_enum, default_value = "text")]
+    #[serde(skip)]
+    error_format: ErrorFormat,
+
+    /// Check the rendered SVG for malformed markup and invalid geometry before writing it
+    #[arg(long = "check-output")]
+    check_output: bool,
+
+    /// Print the effective options as JSON, merged from the defaults, config file, chart file and
+    /// command line, instead of rendering the chart
+    #[arg(long = "print-config")]
+    #[serde(skip)]
+    print_config: bool,
+
+    /// The options given on the command line, which override those in the chart file
+    #[arg(skip)]
+    #[serde(skip)]
+    explicit: Vec<String>,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
 }
 
-impl<'a> StackedBarChartTool<'a> {
-    pub fn new(log: &'a dyn StackedBarChartLog) -> StackedBarChartTool {
-        StackedBarChartTool { log }
-    }
+/// Options controlling the layout and style of a chart, which are also the command line
+/// options of the tool. Start from `ChartOptions::default()` and set the fields needed, or use
+/// `ChartOptions::builder()`, as new fields may be added in minor versions.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[cfg_attr(feature = "cli", derive(Args))]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ChartOptions {
+    /// Seed for category color generation (defaults to a hash of the chart categories)
+    #[cfg_attr(feature = "cli", arg(long = "seed", value_name = "SEED"))]
+    pub seed: Option<u64>,
 
-    pub fn run(
-        self: &mut Self,
-        args: impl IntoIterator<Item = std::ffi::OsString>,
-    ) -> Result<(), Box<dyn Error>> {
-        let cli = match Cli::try_parse_from(args) {
-            Ok(m) => m,
-            Err(err) => {
-                output!(self.log, "{}", err.to_string());
-                return Ok(());
-            }
-        };
+    /// Width of the separator line drawn between stacked segments
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "segment-separator",
+            value_name = "WIDTH",
+            default_value_t = 0.0
+        )
+    )]
+    pub segment_separator: f64,
 
-        let chart_data = Self::read_chart_file(cli.get_input()?)?;
-        let render_data = self.process_chart_data(&chart_data)?;
-        let document = self.render_chart(&render_data)?;
+    /// Corner radius of each stacked segment
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "segment-radius", value_name = "RADIUS", default_value_t = 0.0)
+    )]
+    pub segment_radius: f64,
 
-        Self::write_svg_file(cli.get_output()?, &document)?;
+    /// Corner radius of the top of each bar, or of the bottom of its negative values with --delta
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "bar-radius", value_name = "RADIUS", default_value_t = 0.0)
+    )]
+    pub bar_radius: f64,
 
-        Ok(())
-    }
+    /// How categories are filled
+    #[cfg_attr(feature = "cli", arg(long = "fill", value_enum, default_value_t = FillStyle::Color))]
+    pub fill: FillStyle,
 
-    fn read_chart_file(mut reader: Box<dyn Read>) -> Result<ChartData, Box<dyn Error>> {
-        let mut content = String::new();
+    /// Shade alternate y-axis intervals behind the bars
+    #[cfg_attr(feature = "cli", arg(long = "zebra"))]
+    pub zebra: bool,
 
-        reader.read_to_string(&mut content)?;
+    /// Draw a faint line across the plot at each y-axis label
+    #[cfg_attr(feature = "cli", arg(long = "gridlines"))]
+    pub gridlines: bool,
 
-        let chart_data: ChartData = json5::from_str(&content)?;
+    /// Fill each category with a subtle vertical gradient
+    #[cfg_attr(feature = "cli", arg(long = "gradient"))]
+    pub gradient: bool,
 
-        Ok(chart_data)
-    }
+    /// Color segments by the sign of their value instead of their category, stacking negative
+    /// values down from zero
+    #[cfg_attr(feature = "cli", arg(long = "delta", conflicts_with_all = ["fill", "gradient"]))]
+    pub delta: bool,
 
-    fn write_svg_file(writer: Box<dyn Write>, document: &Document) -> Result<(), Box<dyn Error>> {
-        svg::write(writer, document)?;
+    /// The color theme, one of 'light' or 'dark'
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "theme", value_name = "THEME", default_value = "light")
+    )]
+    pub theme: String,
 
-        Ok(())
-    }
+    /// Background color, or 'transparent' for none (defaults to the theme background)
+    #[cfg_attr(feature = "cli", arg(long = "background", value_name = "COLOR"))]
+    pub background: Option<String>,
 
-    fn hsv_to_rgb(h: f32, s: f32, v: f32) -> u32 {
-        let h_i = (h * 6.0) as usize;
-        let f = h * 6.0 - h_i as f32;
-        let p = v * (1.0 - s);
-        let q = v * (1.0 - f * s);
-        let t = v * (1.0 - (1.0 - f) * s);
+    /// Bar color for a chart with a single category, as #rgb, #rrggbb or rgb(r,g,b)
+    #[cfg_attr(feature = "cli", arg(long = "color", value_name = "COLOR"))]
+    pub color: Option<String>,
 
-        fn rgb(r: f32, g: f32, b: f32) -> u32 {
-            ((r * 256.0) as u32) << 16 | ((g * 256.0) as u32) << 8 | ((b * 256.0) as u32)
-        }
+    /// Colors for the categories in order, separated by commas, as #rgb or #rrggbb, with
+    /// generated colors for any categories beyond them
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "palette", value_name = "COLORS", value_delimiter = ',')
+    )]
+    pub palette: Vec<String>,
 
-        if h_i == 0 {
-            rgb(v, t, p)
-        } else if h_i == 1 {
-            rgb(q, v, p)
-        } else if h_i == 2 {
-            rgb(p, v, t)
-        } else if h_i == 3 {
-            rgb(p, q, v)
-        } else if h_i == 4 {
-            rgb(t, p, v)
-        } else {
-            rgb(v, p, q)
-        }
-    }
+    /// Hover text template for each segment, using {item}, {category}, {value}, {units},
+    /// {percent} and {total}, or empty for no hover text
+    #[cfg_attr(feature = "cli", arg(long = "tooltip", value_name = "TEMPLATE", default_value = DEFAULT_TOOLTIP))]
+    pub tooltip: String,
 
-    fn process_chart_data(self: &Self, cd: &ChartData) -> Result<RenderData, Box<dyn Error>> {
-        // Generate random resource colors based on https://martin.ankerl.com/2009/12/09/how-to-create-random-colors-programmatically/
-        let mut rng = rand::thread_rng();
-        let mut h: f32 = rng.gen();
+    /// Count the values of hidden categories in bar totals and the y-axis range
+    #[cfg_attr(feature = "cli", arg(long = "hidden-in-totals"))]
+    pub hidden_in_totals: bool,
 
-        let mut styles = vec![
-            ".labels{fill:rgb(0,0,0);font-size:10;font-family:Arial}".to_string(),
-            ".title{font-family:Arial;font-size:12;text-anchor:middle;}".to_string(),
-            ".legend{font-family:Arial;font-size:12pt;text-anchor:left;}".to_string(),
-            ".axis{fill:none;stroke:rgb(0,0,0);stroke-width:1;}".to_owned(),
-            ".y-labels{text-anchor:end;}".to_owned(),
-        ];
+    /// How to chart values which are null, which never count towards totals
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "missing", value_enum, default_value = "zero")
+    )]
+    pub missing: MissingValues,
 
-        let mut bar_data = vec![];
-        let mut y_axis_range: (f64, f64) = (0.0, f64::MIN);
+    /// A CSS file whose rules are appended to the built-in styles
+    #[cfg_attr(feature = "cli", arg(long = "css", value_name = "CSS_FILE"))]
+    pub css_file: Option<PathBuf>,
 
-        for tuple in cd.items.iter().enumerate() {
-            let (index, item) = tuple;
+    /// Replace the built-in text and axis styles with the CSS file instead of appending to them
+    #[cfg_attr(feature = "cli", arg(long = "replace-css", requires = "css_file"))]
+    pub replace_css: bool,
 
-            if item.values.len() < cd.categories.len() {
-                bail!(
-                    "Item {} needs {} values and has {}",
-                    index,
-                    cd.categories.len(),
-                    item.values.len()
-                );
-            }
+    /// Font family for all text
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "font-family", value_name = "FAMILY", default_value = "Arial")
+    )]
+    pub font_family: String,
 
-            let sum = item.values.iter().sum();
+    /// Font size of the axis labels, with titles and legends scaled from it
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "font-size", value_name = "SIZE", default_value_t = 10.0)
+    )]
+    pub font_size: f64,
 
-            if sum > y_axis_range.1 {
-                y_axis_range.1 = sum;
-            }
+    /// A WOFF, WOFF2, TTF or OTF font file to embed as the font family
+    #[cfg_attr(feature = "cli", arg(long = "embed-font", value_name = "FONT_FILE"))]
+    pub embed_font: Option<PathBuf>,
 
-            let rgb = Self::hsv_to_rgb(h, 0.5, 0.5);
+    /// Include the chart data and the version of this tool in the SVG, so that it can be
+    /// audited or given as the input file to render the chart again
+    #[cfg_attr(feature = "cli", arg(long = "embed-data"))]
+    pub embed_data: bool,
 
-            styles.push(format!(
-                ".category-{}{{fill:#{1:06x};stroke-width:0}}",
-                index, rgb,
-            ));
+    /// A PNG, JPEG, GIF, WebP or SVG image, such as a company mark, to embed in a corner of the
+    /// chart
+    #[cfg_attr(feature = "cli", arg(long = "logo", value_name = "IMAGE_FILE"))]
+    pub logo: Option<PathBuf>,
 
-            bar_data.push(BarData {
-                label: item.key.to_string(),
-                values: item.values.clone(),
-            });
+    /// Corner of the chart to put the logo in
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "logo-position",
+            value_name = "POSITION",
+            default_value = "top-right"
+        )
+    )]
+    pub logo_position: LogoPosition,
 
-            h = (h + GOLDEN_RATIO_CONJUGATE) % 1.0;
-        }
+    /// Opacity of the logo, from 0 for invisible to 1 for solid
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "logo-opacity", value_name = "OPACITY", default_value_t = 1.0)
+    )]
+    pub logo_opacity: f64,
 
-        let y_axis_max_intervals = 20.0;
-        let y_axis_interval = (10.0_f64).powf(((y_axis_range.1 - y_axis_range.0).log10()).ceil())
-            / y_axis_max_intervals;
-        let decimal_places = y_axis_interval.log10();
-        let y_axis_decimal_places = if decimal_places < 0.0 {
-            decimal_places.abs().ceil() as usize
-        } else {
-            0
-        };
+    /// Height of the logo in pixels, which is scaled to fit this height and four times as wide
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "logo-size", value_name = "PIXELS", default_value_t = 32.0)
+    )]
+    pub logo_size: f64,
 
-        y_axis_range = (
-            f64::floor(y_axis_range.0 / y_axis_interval) * y_axis_interval,
-            f64::ceil(y_axis_range.1 / y_axis_interval) * y_axis_interval,
-        );
+    /// An SVG file to put the chart in, with {{chart}} where it goes and optionally {{title}},
+    /// {{legend}}, {{width}} and {{height}}. The title and legend are left out of the chart when
+    /// the template places them.
+    #[cfg_attr(feature = "cli", arg(long = "template", value_name = "TEMPLATE_FILE"))]
+    pub template: Option<PathBuf>,
 
-        let gutter = Gutter {
-            top: 40.0,
-            bottom: 40.0,
-            left: 40.0,
-            right: 40.0,
-        };
-        let x_axis_item_width = 30.0;
-        let legend_rect_size = 20.0;
-        let legend_gutter = Gutter {
-            top: 10.0,
-            bottom: 80.0,
-            left: 40.0,
-            right: 10.0,
-        };
+    /// Show item keys that are ISO dates such as '2024-03' or '2024-03-15' in this format,
+    /// using %Y, %y, %m, %d, %e, %b, %B, %a and %A as in strftime
+    #[cfg_attr(feature = "cli", arg(long = "date-format", value_name = "FORMAT"))]
+    pub date_format: Option<String>,
 
-        Ok(RenderData {
-            title: cd.title.to_string(),
-            categories: cd.categories.clone(),
-            gutter,
-            x_axis_item_width,
-            y_axis_height: 300.0,
-            y_axis_interval,
-            y_axis_range,
-            y_axis_decimal_places,
-            bar_data,
-            legend_gutter,
-            legend_rect_size,
-            legend_rect_corner_radius: 3.0,
-            styles,
-        })
-    }
+    /// Parse item keys as dates in this format, using %Y, %y, %m, %d, %e, %b, %B and %% as in
+    /// strftime, and chart the items in date order
+    #[cfg_attr(feature = "cli", arg(long = "key-format", value_name = "FORMAT"))]
+    pub key_format: Option<String>,
 
-    fn render_chart(self: &Self, rd: &RenderData) -> Result<Document, Box<dyn Error>> {
-        let width =
-            rd.gutter.left + ((rd.bar_data.len() as f64) * rd.x_axis_item_width) + rd.gutter.right;
-        let height = rd.gutter.top_bottom()
-            + rd.y_axis_height
-            + rd.legend_gutter.top_bottom()
-            + rd.legend_rect_size;
-        let num_y_labels =
-            ((rd.y_axis_range.1 - rd.y_axis_range.0) / rd.y_axis_interval) as usize + 1;
-        let scale =
-            |n: &f64| -> f64 { n * (rd.y_axis_height / (rd.y_axis_range.1 - rd.y_axis_range.0)) };
-        let mut document = Document::new()
-            .set("xmlns", "http://www.w3.org/2000/svg")
-            .set("width", width)
-            .set("height", height)
-            .set("viewBox", format!("0 0 {} {}", width, height))
-            .set("style", "background-color: white;");
-        let style = element::Style::new(rd.styles.join("\n"));
-        let axis = element::Polyline::new().set("class", "axis").set(
-            "points",
-            vec![
-                (rd.gutter.left, rd.gutter.top),
-                (rd.gutter.left, rd.gutter.top + rd.y_axis_height),
-                (width - rd.gutter.right, rd.gutter.top + rd.y_axis_height),
-            ],
-        );
-        let mut x_axis_labels = element::Group::new().set("class", "labels");
+    /// Sum items with date keys into one bar for each day, week, month, quarter or year
+    #[cfg_attr(feature = "cli", arg(long = "bucket", value_enum))]
+    pub bucket: Option<Bucket>,
 
-        for i in 0..rd.bar_data.len() {
-            x_axis_labels.append(element::Text::new(format!("{}", rd.bar_data[i].label)).set(
-                "transform",
-                format!(
-                    "translate({},{}) rotate(45)",
-                    rd.gutter.left + (i as f64 * rd.x_axis_item_width) + rd.x_axis_item_width / 2.0,
-                    rd.gutter.top + rd.y_axis_height + 15.0
-                ),
-            ));
-        }
+    /// Language for month and weekday names in --date-format
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "locale", value_name = "LOCALE", default_value = "en")
+    )]
+    pub locale: String,
 
-        let mut y_axis_labels = element::Group::new().set("class", "labels y-labels");
+    /// Truncate item keys and category names longer than this with an ellipsis
+    #[cfg_attr(feature = "cli", arg(long = "max-label-chars", value_name = "CHARS"))]
+    pub max_label_chars: Option<usize>,
 
-        for i in 0..num_y_labels {
-            let n = i as f64 * rd.y_axis_interval;
+    /// Append a bar summarizing all the items
+    #[cfg_attr(feature = "cli", arg(long = "summary-bar", value_enum))]
+    pub summary_bar: Option<SummaryBar>,
 
-            y_axis_labels.append(
-                element::Text::new(format!(
-                    "{0:.1$}",
-                    n + rd.y_axis_range.0,
-                    rd.y_axis_decimal_places
-                ))
-                .set(
-                    "transform",
-                    format!(
-                        "translate({},{})",
-                        rd.gutter.left - 10.0,
-                        rd.gutter.top + rd.y_axis_height - f64::floor(scale(&n)) + 5.0
-                    ),
-                ),
-            );
-        }
+    /// Only chart categories with names matching one of these patterns, which are globs using
+    /// '*' and '?' or regular expressions between slashes such as '/^(web|db)-/'
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "include-category", value_name = "PATTERN")
+    )]
+    pub include_category: Vec<String>,
 
-        let mut bars = element::Group::new();
-        let bar_width = rd.x_axis_item_width / 2.0;
+    /// Leave out categories with names matching any of these patterns
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "exclude-category", value_name = "PATTERN")
+    )]
+    pub exclude_category: Vec<String>,
 
-        for i in 0..rd.bar_data.len() {
-            let bar_datum = &rd.bar_data[i];
-            let heights = bar_datum.values.iter().map(scale).collect::<Vec<f64>>();
-            let mut bar = element::Group::new();
-            let mut y = rd.gutter.top + rd.y_axis_height;
-
-            for j in 0..heights.len() {
-                bar.append(
-                    element::Path::new()
-                        .set("class", format!("category-{}", j))
-                        .set(
-                            "d",
-                            path::Data::new()
-                                .move_to((
-                                    rd.gutter.left
-                                        + (i as f64 * rd.x_axis_item_width)
-                                        + bar_width / 2.0,
-                                    y,
-                                ))
-                                .line_by((bar_width, 0.0))
-                                .line_by((0.0, -heights[j]))
-                                .line_by((-bar_width, 0.0))
-                                .close(),
-                        ),
-                );
+    /// Only chart items with keys matching this regular expression, such as 'web-.*'
+    #[cfg_attr(feature = "cli", arg(long = "filter-key", value_name = "REGEX"))]
+    pub filter_key: Option<String>,
 
-                y -= heights[j];
-            }
+    /// Order the bars by 'total', 'key', or the value of one category as 'category:NAME',
+    /// rather than in the order of the input
+    #[cfg_attr(feature = "cli", arg(long = "sort", value_name = "ORDER"))]
+    pub sort: Option<SortOrder>,
 
-            bars.append(bar);
-        }
+    /// Sort the bars from largest to smallest
+    #[cfg_attr(feature = "cli", arg(long = "descending", requires = "sort"))]
+    pub descending: bool,
 
-        let mut legend = element::Group::new();
-        let text_width = (width - rd.legend_gutter.left_right()) / (rd.bar_data.len() as f64);
+    /// Order of the segments from the bottom of each bar up, which is 'input', 'reverse',
+    /// 'largest' for the categories with the largest totals at the bottom, or a comma separated
+    /// list of category names with any not listed stacked above them in input order
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "stack-order", value_name = "ORDER", default_value = "input")
+    )]
+    pub stack_order: StackOrder,
 
-        for i in 0..rd.categories.len() {
-            let y = rd.gutter.top_bottom() + rd.y_axis_height + rd.legend_gutter.top;
-            let block = element::Rectangle::new()
-                .set("class", format!("category-{}", i))
-                .set("x", rd.legend_gutter.left + (i as f64) * text_width)
-                .set("y", y)
-                .set("rx", rd.legend_rect_corner_radius)
-                .set("ry", rd.legend_rect_corner_radius)
-                .set("width", rd.legend_rect_size)
-                .set("height", rd.legend_rect_size);
+    /// Keep only this many items with the largest totals, collapsing the rest into an 'Other' item
+    #[cfg_attr(feature = "cli", arg(long = "top", value_name = "N"))]
+    pub top: Option<usize>,
 
-            legend.append(block);
+    /// Keep only this many categories with the largest totals, collapsing the rest into an
+    /// 'Other' category
+    #[cfg_attr(feature = "cli", arg(long = "top-categories", value_name = "N"))]
+    pub top_categories: Option<usize>,
 
-            let text = element::Text::new(format!("{}", &rd.categories[i]))
-                .set("class", "legend")
-                .set(
-                    "transform",
-                    format!(
-                        "translate({},{}) rotate(45)",
-                        rd.legend_gutter.left + (i as f64) * text_width,
-                        y + rd.legend_rect_size * 1.5
-                    ),
-                );
+    /// Multiply every value by this before charting, such as 1e-9 to chart bytes as gigabytes,
+    /// on top of any scale given for its category. Use --units to match.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "scale", value_name = "FACTOR", default_value_t = 1.0)
+    )]
+    pub scale: f64,
 
-            legend.append(text);
-        }
+    /// Arithmetic in 'v' applied to every value before charting, such as 'v / 1024 / 1024',
+    /// after any transform given for its category and before scaling
+    #[cfg_attr(feature = "cli", arg(long = "transform", value_name = "EXPR"))]
+    pub transform: Option<Transform>,
 
-        let title = element::Text::new(format!("{}", &rd.title))
-            .set("class", "title")
-            .set("x", width / 2.0)
-            .set("y", rd.gutter.top / 2.0);
+    /// Outline the bars with these item keys and dim the rest, to draw attention to them
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "highlight", value_name = "KEY", value_delimiter = ',')
+    )]
+    pub highlight: Vec<String>,
 
-        document.append(style);
-        document.append(bars);
-        document.append(axis);
-        document.append(x_axis_labels);
-        document.append(y_axis_labels);
-        document.append(title);
-        document.append(legend);
+    /// Label each bar with the percentage change in its total from the bar before
+    #[cfg_attr(feature = "cli", arg(long = "change-labels"))]
+    pub change_labels: bool,
 
-        Ok(document)
+    /// Where to put the legend
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "legend", value_enum, default_value = "bottom")
+    )]
+    pub legend: LegendPosition,
+
+    /// How to write the numbers on the y-axis, value labels, legend totals and hover text,
+    /// instead of plain y-axis labels, grouped totals and full precision hover text
+    #[cfg_attr(feature = "cli", arg(long = "number-format", value_enum))]
+    pub number_format: Option<NumberFormat>,
+
+    /// Append each category's total to its legend entry
+    #[cfg_attr(feature = "cli", arg(long = "legend-totals"))]
+    pub legend_totals: bool,
+
+    /// Append each category's percentage of the grand total to its legend entry
+    #[cfg_attr(feature = "cli", arg(long = "legend-percent"))]
+    pub legend_percent: bool,
+
+    /// Width of the output in pixels, scaling the whole chart proportionally
+    #[cfg_attr(feature = "cli", arg(long = "width", value_name = "PIXELS"))]
+    pub width: Option<f64>,
+
+    /// Height of the output in pixels, scaling the whole chart proportionally
+    #[cfg_attr(feature = "cli", arg(long = "height", value_name = "PIXELS"))]
+    pub height: Option<f64>,
+
+    /// Margins around the plot area as TOP,RIGHT,BOTTOM,LEFT, with fewer values repeated as in CSS
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "margin", value_name = "MARGINS", value_delimiter = ',')
+    )]
+    pub margin: Vec<f64>,
+
+    /// Margin above the plot area, overriding --margin
+    #[cfg_attr(feature = "cli", arg(long = "margin-top", value_name = "PIXELS"))]
+    pub margin_top: Option<f64>,
+
+    /// Margin right of the plot area, overriding --margin
+    #[cfg_attr(feature = "cli", arg(long = "margin-right", value_name = "PIXELS"))]
+    pub margin_right: Option<f64>,
+
+    /// Margin below the plot area, overriding --margin
+    #[cfg_attr(feature = "cli", arg(long = "margin-bottom", value_name = "PIXELS"))]
+    pub margin_bottom: Option<f64>,
+
+    /// Margin left of the plot area, overriding --margin
+    #[cfg_attr(feature = "cli", arg(long = "margin-left", value_name = "PIXELS"))]
+    pub margin_left: Option<f64>,
+
+    /// Grow each bar up from the axis when the chart is first shown
+    #[cfg_attr(feature = "cli", arg(long = "animate"))]
+    pub animate: bool,
+
+    /// How long each bar takes to grow with --animate
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "animation-duration",
+            value_name = "SECONDS",
+            default_value_t = 0.8,
+            requires = "animate"
+        )
+    )]
+    pub animation_duration: f64,
+
+    /// Delay between each bar starting to grow with --animate
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "animation-stagger",
+            value_name = "SECONDS",
+            default_value_t = 0.1,
+            requires = "animate"
+        )
+    )]
+    pub animation_stagger: f64,
+
+    /// Omit the namespace and size from the root element so it can be inlined into HTML
+    #[cfg_attr(feature = "cli", arg(long = "fragment"))]
+    pub fragment: bool,
+
+    /// Omit the fixed size so that the chart scales to fit its container, keeping its aspect ratio
+    #[cfg_attr(feature = "cli", arg(long = "responsive", conflicts_with_all = ["width", "height"]))]
+    pub responsive: bool,
+
+    /// Lay the chart out right to left, for Hebrew and Arabic labels, with the first item on the
+    /// right and the y-axis on the right hand side
+    #[cfg_attr(feature = "cli", arg(long = "rtl"))]
+    pub rtl: bool,
+
+    /// Which way the bars run, with horizontal bars reading values along the bottom
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "orientation", value_enum, default_value = "vertical")
+    )]
+    pub orientation: Orientation,
+
+    /// Height of the plot area in pixels
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "plot-height", value_name = "PIXELS", default_value_t = 300.0)
+    )]
+    pub plot_height: f64,
+
+    /// Width of the slot each bar and the gap after it take up, in pixels
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "slot-width", value_name = "PIXELS", default_value_t = 30.0)
+    )]
+    pub slot_width: f64,
+
+    /// Width of each bar in pixels, or as a percentage of the slot such as '40%'
+    #[cfg_attr(feature = "cli", arg(long = "bar-width", value_name = "WIDTH"))]
+    pub bar_width: Option<Length>,
+
+    /// Gap between bars in pixels, or as a percentage of the slot such as '60%'
+    #[cfg_attr(feature = "cli", arg(long = "bar-gap", value_name = "GAP"))]
+    pub bar_gap: Option<Length>,
+
+    /// Size of the colored square beside each legend entry
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "legend-swatch", value_name = "PIXELS", default_value_t = 20.0)
+    )]
+    pub legend_swatch: f64,
+
+    /// Warn when the chart is wider than this many pixels
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "max-width", value_name = "PIXELS", default_value_t = 4000.0)
+    )]
+    pub max_width: f64,
+
+    /// Warn when the chart is taller than this many pixels
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "max-height", value_name = "PIXELS", default_value_t = 4000.0)
+    )]
+    pub max_height: f64,
+
+    /// Fail instead of warning when the title, labels, legend entries or caption would be cut
+    /// off at the edge of the chart
+    #[cfg_attr(feature = "cli", arg(long = "strict"))]
+    pub strict: bool,
+
+    /// Pixel density of PNG output, in dots per inch such as '300' or as a multiple of the 96
+    /// DPI of the SVG such as '2x', with the same layout at any density
+    #[cfg_attr(feature = "cli", arg(long = "dpi", value_name = "DENSITY"))]
+    pub dpi: Option<PixelDensity>,
+
+    /// The options named in the options block these were read from, as those set to their
+    /// defaults can't otherwise be told from those left out
+    #[cfg_attr(feature = "cli", arg(skip))]
+    #[serde(skip)]
+    given: Option<Vec<String>>,
+}
+
+impl Default for ChartOptions {
+    /// The defaults are those in the argument attributes above
+    fn default() -> Self {
+        ChartOptions::builtin_defaults()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Everything needed to draw a chart: what to show, how to lay it out, and the colors to use.
+///
+/// This is the stable surface for using the crate as a library. Fields will only be added to
+/// `ChartSpec` and `ChartOptions` in minor versions, and the layout computed from a spec is
+/// internal so that it can change without breaking callers.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ChartSpec {
+    pub data: ChartData,
+    pub options: ChartOptions,
+    pub theme: Theme,
+}
 
-    #[test]
-    fn basic_test() {
-        struct TestLogger;
+impl ChartSpec {
+    /// A spec for `data` with the given options and the theme they name, or an error if there
+    /// is no theme by that name
+    pub fn new(data: ChartData, options: ChartOptions) -> Result<ChartSpec, StackedBarChartError> {
+        let theme = options
+            .get_theme()
+            .map_err(|e| StackedBarChartError::classify(e, StackedBarChartError::validation))?
+            .clone();
 
-        impl TestLogger {
-            fn new() -> TestLogger {
-                TestLogger {}
-            }
-        }
+        Ok(ChartSpec {
+            data,
+            options,
+            theme,
+        })
+    }
+}
 
-        impl StackedBarChartLog for TestLogger {
-            fn output(self: &Self, _args: Arguments) {}
-            fn warning(self: &Self, _args: Arguments) {}
-            fn error(self: &Self, _args: Arguments) {}
-        }
+#[cfg(feature = "cli")]
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Render a directory of chart files with every theme into an HTML gallery page
+    Examples {
+        /// The directory of chart files
+        #[arg(value_name = "FIXTURE_DIR", default_value = "example/gallery")]
+        fixture_dir: PathBuf,
 
-        let logger = TestLogger::new();
-        let mut tool = StackedBarChartTool::new(&logger);
-        let args: Vec<std::ffi::OsString> = vec!["".into(), "--help".into()];
+        /// The HTML output file
+        #[arg(value_name = "OUTPUT_FILE")]
+        output_file: Option<PathBuf>,
+    },
+}
 
-        tool.run(args).unwrap();
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum FillStyle {
+    /// A solid color per category
+    Color,
+    /// A black and white hatch pattern per category
+    Pattern,
+    /// A hatch pattern over the category color
+    Both,
+}
+
+#[cfg(feature = "cli")]
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    /// An SVG image
+    Svg,
+    /// A standalone HTML page where hovering highlights a category and clicking the legend
+    /// shows or hides it
+    Html,
+    /// A PNG image rasterized from the SVG
+    Png,
+    /// Encapsulated PostScript, for LaTeX and other print workflows
+    Eps,
+    /// Rows of colored blocks for a quick look at the data in a terminal
+    Term,
+}
+
+#[cfg(feature = "cli")]
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MergePolicy {
+    /// Sum the values of categories with the same name
+    Sum,
+    /// Keep the categories of each chart separate, one after the other
+    Concat,
+}
+
+#[cfg(feature = "cli")]
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Redact {
+    /// Replace the title, units, item keys and category names with placeholders
+    Labels,
+    /// Scale all the values by the same random factor
+    Values,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum LegendPosition {
+    /// Below the plot, wrapping onto more rows as needed
+    Bottom,
+    /// Between the title and the plot, wrapping onto more rows as needed
+    Top,
+    /// In a column to the right of the plot
+    Right,
+    /// Leave the legend out
+    None,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// Bars rise from an x-axis along the bottom
+    Vertical,
+    /// Bars run to the right from a y-axis down the left, with one row per item
+    Horizontal,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    /// Digits only, as in 1234567.5
+    Plain,
+    /// Commas between each group of three digits, as in 1,234,567.5
+    Grouped,
+    /// Scaled down with a k, M, B or T suffix, as in 1.2M
+    Compact,
+}
+
+impl NumberFormat {
+    /// Format `value` in this style with `decimal_places` after the point
+    pub fn format(self: &Self, value: f64, decimal_places: usize) -> String {
+        match self {
+            NumberFormat::Plain => format!("{0:.1$}", value, decimal_places),
+            NumberFormat::Grouped => text::format_number(value, decimal_places),
+            NumberFormat::Compact => text::format_compact(value, decimal_places),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum LogoPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryBar {
+    /// The average of each category across all items
+    Avg,
+    /// The total of each category across all items
+    Total,
+}
+
+/// A period of time that items with date keys are summed over
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Bucket {
+    /// How to label the bars when there is no --date-format
+    fn date_format(&self) -> &'static str {
+        match self {
+            Bucket::Day | Bucket::Week => "%e %b %Y",
+            Bucket::Month => "%b %Y",
+            Bucket::Quarter => "Q%q %Y",
+            Bucket::Year => "%Y",
+        }
+    }
+}
+
+/// How to chart item values which are `null` because the measurement is missing
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum MissingValues {
+    /// Chart missing values as zero
+    Zero,
+    /// Leave out the segment for a missing value
+    Skip,
+    /// Draw a small hatched segment to show there is no data
+    Placeholder,
+}
+
+/// A size given either in pixels or as a percentage of some other size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Pixels(f64),
+    Percent(f64),
+}
+
+impl Length {
+    fn resolve(&self, whole: f64) -> f64 {
+        match self {
+            Length::Pixels(pixels) => *pixels,
+            Length::Percent(percent) => whole * percent / 100.0,
+        }
+    }
+}
+
+impl std::str::FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, length): (&str, fn(f64) -> Length) = match s.strip_suffix('%') {
+            Some(number) => (number, Length::Percent),
+            None => (s, Length::Pixels),
+        };
+
+        match number.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 => Ok(length(n)),
+            _ => Err(format!(
+                "'{}' is not a positive number of pixels or percentage",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{}", pixels),
+            Length::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl Serialize for Length {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Length {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FromStrVisitor(std::marker::PhantomData))
+    }
+}
+
+/// The pixel density of a raster image, given either in dots per inch or as a multiple of the
+/// 96 DPI that SVG pixels are defined as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelDensity {
+    Dpi(f64),
+    Ratio(f64),
+}
+
+impl PixelDensity {
+    /// Image pixels per SVG pixel
+    pub fn ratio(&self) -> f64 {
+        match self {
+            PixelDensity::Dpi(dpi) => dpi / 96.0,
+            PixelDensity::Ratio(ratio) => *ratio,
+        }
+    }
+
+    pub fn dpi(&self) -> f64 {
+        self.ratio() * 96.0
+    }
+}
+
+impl std::str::FromStr for PixelDensity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, density): (&str, fn(f64) -> PixelDensity) = match s.strip_suffix('x') {
+            Some(number) => (number, PixelDensity::Ratio),
+            None => (s, PixelDensity::Dpi),
+        };
+
+        match number.trim().parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(density(n)),
+            _ => Err(format!(
+                "'{}' is not a positive number of dots per inch or multiple such as '2x'",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for PixelDensity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PixelDensity::Dpi(dpi) => write!(f, "{}", dpi),
+            PixelDensity::Ratio(ratio) => write!(f, "{}x", ratio),
+        }
+    }
+}
+
+impl Serialize for PixelDensity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PixelDensity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FromStrVisitor(std::marker::PhantomData))
+    }
+}
+
+/// What to sort the bars by
+#[derive(Clone, Debug, PartialEq)]
+pub enum SortOrder {
+    Total,
+    Key,
+    Category(String),
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total" => Ok(SortOrder::Total),
+            "key" => Ok(SortOrder::Key),
+            _ => match s.strip_prefix("category:") {
+                Some(name) => Ok(SortOrder::Category(name.to_string())),
+                None => Err(format!(
+                    "'{}' is not 'total', 'key' or 'category:' followed by a category name",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SortOrder::Total => write!(f, "total"),
+            SortOrder::Key => write!(f, "key"),
+            SortOrder::Category(name) => write!(f, "category:{}", name),
+        }
+    }
+}
+
+impl Serialize for SortOrder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SortOrder {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FromStrVisitor(std::marker::PhantomData))
+    }
+}
+
+/// The order that categories are stacked in each bar, which can differ from the legend
+#[derive(Clone, Debug, PartialEq)]
+pub enum StackOrder {
+    Input,
+    Reverse,
+    Largest,
+    Categories(Vec<String>),
+}
+
+impl std::str::FromStr for StackOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "input" => Ok(StackOrder::Input),
+            "reverse" => Ok(StackOrder::Reverse),
+            "largest" => Ok(StackOrder::Largest),
+            _ => Ok(StackOrder::Categories(
+                s.split(',').map(|name| name.trim().to_string()).collect(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for StackOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StackOrder::Input => write!(f, "input"),
+            StackOrder::Reverse => write!(f, "reverse"),
+            StackOrder::Largest => write!(f, "largest"),
+            StackOrder::Categories(names) => write!(f, "{}", names.join(",")),
+        }
+    }
+}
+
+impl Serialize for StackOrder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StackOrder {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FromStrVisitor(std::marker::PhantomData))
+    }
+}
+
+/// Deserializes options from the same text as on the command line, also taking numbers so that
+/// a length can be given as `40` rather than `"40"`
+struct FromStrVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: std::str::FromStr<Err = String>> serde::de::Visitor<'de> for FromStrVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a string or number")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<T, E> {
+        s.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, n: u64) -> Result<T, E> {
+        self.visit_str(&n.to_string())
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, n: i64) -> Result<T, E> {
+        self.visit_str(&n.to_string())
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, n: f64) -> Result<T, E> {
+        self.visit_str(&n.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Hatch {
+    Diagonal,
+    Dots,
+    Cross,
+    ReverseDiagonal,
+    Horizontal,
+    Vertical,
+}
+
+const HATCHES: [Hatch; 6] = [
+    Hatch::Diagonal,
+    Hatch::Dots,
+    Hatch::Cross,
+    Hatch::ReverseDiagonal,
+    Hatch::Horizontal,
+    Hatch::Vertical,
+];
+
+#[cfg(feature = "cli")]
+impl Cli {
+    /// Parse the command line, remembering which options were given on it rather than left
+    /// as their defaults
+    fn parse_args(args: impl IntoIterator<Item = std::ffi::OsString>) -> Result<Cli, clap::Error> {
+        let matches = Cli::command().try_get_matches_from(args)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+
+        cli.explicit = matches
+            .ids()
+            .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+            .map(|id| id.to_string())
+            .collect();
+
+        Ok(cli)
+    }
+
+    /// The options with those named in `keys` taken from `layer`, unless they were given on the
+    /// command line
+    fn overlay_options(
+        &self,
+        layer: &ChartOptions,
+        keys: &[String],
+    ) -> Result<ChartOptions, Box<dyn Error>> {
+        let mut options = serde_json::to_value(&self.options)?;
+        let layer = serde_json::to_value(layer)?;
+
+        for key in keys.iter().filter(|key| !self.explicit.contains(key)) {
+            if let Some(value) = layer.get(key) {
+                options[key] = value.clone();
+            }
+        }
+
+        Ok(serde_json::from_value(options)?)
+    }
+
+    /// The options with those in the chart file in place of the config file and defaults. For
+    /// options built in code rather than read from the file, only those changed from their
+    /// defaults are known to have been given.
+    fn merge_options(&self, file_options: &ChartOptions) -> Result<ChartOptions, Box<dyn Error>> {
+        let keys: Vec<String> = match &file_options.given {
+            Some(given) => given.clone(),
+            None => {
+                let defaults = serde_json::to_value(ChartOptions::default())?;
+
+                match serde_json::to_value(file_options)? {
+                    serde_json::Value::Object(map) => map
+                        .into_iter()
+                        .filter(|(key, value)| defaults.get(key) != Some(value))
+                        .map(|(key, _)| key)
+                        .collect(),
+                    _ => vec![],
+                }
+            }
+        };
+
+        self.overlay_options(file_options, &keys)
+    }
+
+    /// Combine chart data with the title, units and options given on the command line
+    fn get_spec(&self, mut data: ChartData) -> Result<ChartSpec, Box<dyn Error>> {
+        if let Some(title) = &self.title {
+            data.title = title.clone();
+        }
+
+        if let Some(units) = &self.units {
+            data.units = units.clone();
+        }
+
+        let mut options = match data.options.take() {
+            Some(file_options) => self.merge_options(&file_options)?,
+            None => self.options.clone(),
+        };
+
+        options.fragment |= self.inner_only;
+
+        let theme = options.get_theme()?;
+
+        Ok(ChartSpec {
+            data,
+            options,
+            theme: theme.clone(),
+        })
+    }
+
+    /// The options merged from the defaults, config file, chart file and command line as they
+    /// are for rendering the first chart, for --print-config
+    fn get_effective_config(&self) -> Result<serde_json::Value, Box<dyn Error>> {
+        let charts = match &self.input_file {
+            Some(path) => StackedBarChartTool::read_input(self, Some(path))?,
+            None => vec![],
+        };
+        let options = match charts.into_iter().next() {
+            Some(chart_data) => self.get_spec(chart_data)?.options,
+            None => self.options.clone(),
+        };
+        Ok(serde_json::to_value(options)?)
+    }
+
+    fn get_format(&self) -> OutputFormat {
+        let extension = self.output_file.as_deref().and_then(Path::extension);
+
+        match (self.format, extension) {
+            (Some(format), _) => format,
+            (None, Some(extension)) if extension.eq_ignore_ascii_case("png") => OutputFormat::Png,
+            (None, Some(extension)) if extension.eq_ignore_ascii_case("eps") => OutputFormat::Eps,
+            (None, _) => OutputFormat::Svg,
+        }
+    }
+
+    fn get_input_format(&self, path: Option<&Path>) -> InputFormat {
+        let extension = path.and_then(Path::extension);
+
+        match (self.input_format, extension) {
+            (Some(format), _) => format,
+            (None, Some(extension)) if extension.eq_ignore_ascii_case("csv") => InputFormat::Csv,
+            (None, Some(extension))
+                if extension.eq_ignore_ascii_case("ndjson")
+                    || extension.eq_ignore_ascii_case("jsonl") =>
+            {
+                InputFormat::Ndjson
+            }
+            (None, _) => InputFormat::Json5,
+        }
+    }
+
+    /// Create `path` for writing, or use standard output when there isn't one
+    fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, Box<dyn Error>> {
+        match path {
+            Some(path) => File::create(path)
+                .context(format!(
+                    "Unable to create file '{}'",
+                    path.to_string_lossy()
+                ))
+                .map(|f| Box::new(f) as Box<dyn Write>)
+                .map_err(|e| Box::new(e) as Box<dyn Error>),
+            None => Ok(Box::new(io::stdout())),
+        }
+    }
+
+    /// Open `path` for reading, or use standard input when there isn't one
+    fn open_input(path: Option<&Path>) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        match path {
+            Some(path) => File::open(path)
+                .context(format!("Unable to open file '{}'", path.to_string_lossy()))
+                .map(|f| Box::new(f) as Box<dyn Read>)
+                .map_err(|e| Box::new(e) as Box<dyn Error>),
+            None => Ok(Box::new(io::stdin())),
+        }
+    }
+}
+
+impl ChartOptions {
+    /// Read the options block of a chart file, remembering which options it names
+    fn deserialize_block<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<ChartOptions>, D::Error> {
+        let block: Option<serde_json::Map<String, serde_json::Value>> =
+            Option::deserialize(deserializer)?;
+
+        block
+            .map(|block| {
+                // Rendering someone else's chart file mustn't read other files on the machine
+                if let Some(key) = block
+                    .keys()
+                    .find(|key| FILE_OPTIONS.contains(&key.as_str()))
+                {
+                    return Err(serde::de::Error::custom(format!(
+                        "'{}' names a file so it can only be given on the command line or in the \
+                        config file",
+                        key
+                    )));
+                }
+
+                let given = block.keys().cloned().collect();
+                let mut options: ChartOptions =
+                    serde_json::from_value(serde_json::Value::Object(block))
+                        .map_err(serde::de::Error::custom)?;
+
+                options.given = Some(given);
+
+                Ok(options)
+            })
+            .transpose()
+    }
+
+    /// The defaults in the argument attributes, without parsing a command line. A test checks
+    /// that they match.
+    fn builtin_defaults() -> ChartOptions {
+        ChartOptions {
+            seed: None,
+            segment_separator: 0.0,
+            segment_radius: 0.0,
+            bar_radius: 0.0,
+            fill: FillStyle::Color,
+            zebra: false,
+            gridlines: false,
+            gradient: false,
+            delta: false,
+            theme: "light".to_string(),
+            background: None,
+            color: None,
+            palette: vec![],
+            tooltip: DEFAULT_TOOLTIP.to_string(),
+            hidden_in_totals: false,
+            missing: MissingValues::Zero,
+            css_file: None,
+            replace_css: false,
+            font_family: "Arial".to_string(),
+            font_size: 10.0,
+            embed_font: None,
+            embed_data: false,
+            logo: None,
+            logo_position: LogoPosition::TopRight,
+            logo_opacity: 1.0,
+            logo_size: 32.0,
+            template: None,
+            date_format: None,
+            key_format: None,
+            bucket: None,
+            locale: "en".to_string(),
+            max_label_chars: None,
+            summary_bar: None,
+            include_category: vec![],
+            exclude_category: vec![],
+            filter_key: None,
+            sort: None,
+            descending: false,
+            stack_order: StackOrder::Input,
+            top: None,
+            top_categories: None,
+            scale: 1.0,
+            transform: None,
+            highlight: vec![],
+            change_labels: false,
+            legend: LegendPosition::Bottom,
+            number_format: None,
+            legend_totals: false,
+            legend_percent: false,
+            width: None,
+            height: None,
+            margin: vec![],
+            margin_top: None,
+            margin_right: None,
+            margin_bottom: None,
+            margin_left: None,
+            animate: false,
+            animation_duration: 0.8,
+            animation_stagger: 0.1,
+            fragment: false,
+            responsive: false,
+            rtl: false,
+            orientation: Orientation::Vertical,
+            plot_height: 300.0,
+            slot_width: 30.0,
+            bar_width: None,
+            bar_gap: None,
+            legend_swatch: 20.0,
+            max_width: 4000.0,
+            max_height: 4000.0,
+            strict: false,
+            dpi: None,
+            given: None,
+        }
+    }
+
+    /// The theme named by --theme
+    fn get_theme(&self) -> Result<&'static Theme, Box<dyn Error>> {
+        match Theme::find(&self.theme) {
+            Some(theme) => Ok(theme),
+            None => Err(StackedBarChartError::Usage(format!(
+                "Unknown theme '{}', expected one of {}",
+                self.theme,
+                Theme::names().join(", ")
+            ))
+            .into()),
+        }
+    }
+
+    /// Margins from the command line, falling back to those in the chart file
+    fn get_margins(&self, chart_margins: &MarginData) -> Result<MarginData, Box<dyn Error>> {
+        let (top, right, bottom, left) = match self.margin[..] {
+            [] => (None, None, None, None),
+            [all] => (Some(all), Some(all), Some(all), Some(all)),
+            [vertical, horizontal] => (
+                Some(vertical),
+                Some(horizontal),
+                Some(vertical),
+                Some(horizontal),
+            ),
+            [top, horizontal, bottom] => {
+                (Some(top), Some(horizontal), Some(bottom), Some(horizontal))
+            }
+            [top, right, bottom, left] => (Some(top), Some(right), Some(bottom), Some(left)),
+            _ => {
+                return Err(StackedBarChartError::Usage(
+                    "--margin takes between one and four values".to_string(),
+                )
+                .into())
+            }
+        };
+        let margins = MarginData {
+            top: self.margin_top.or(top).or(chart_margins.top),
+            right: self.margin_right.or(right).or(chart_margins.right),
+            bottom: self.margin_bottom.or(bottom).or(chart_margins.bottom),
+            left: self.margin_left.or(left).or(chart_margins.left),
+        };
+
+        for margin in [margins.top, margins.right, margins.bottom, margins.left] {
+            if margin.is_some_and(|margin| margin.is_nan() || margin < 0.0) {
+                return Err(
+                    StackedBarChartError::Usage("Margins cannot be negative".to_string()).into(),
+                );
+            }
+        }
+
+        Ok(margins)
+    }
+
+    fn check_sizes(&self) -> Result<(), Box<dyn Error>> {
+        for (name, size) in [
+            ("Font size", self.font_size),
+            ("Plot height", self.plot_height),
+            ("Slot width", self.slot_width),
+            ("Legend swatch size", self.legend_swatch),
+            ("Logo size", self.logo_size),
+        ] {
+            if size.is_nan() || size <= 0.0 {
+                return Err(StackedBarChartError::Usage(format!(
+                    "{} must be greater than zero",
+                    name
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The width of each bar and the gap after it. Percentages are of the slot, and when only
+    /// one of the two is given the other takes up the rest of the slot.
+    fn get_bar_size(&self) -> Result<(f64, f64), Box<dyn Error>> {
+        let slot_width = self.slot_width;
+        let (bar_width, bar_gap) = match (self.bar_width, self.bar_gap) {
+            (None, None) => (slot_width / 2.0, slot_width / 2.0),
+            (Some(width), None) => {
+                let width = width.resolve(slot_width);
+
+                (width, slot_width - width)
+            }
+            (None, Some(gap)) => {
+                let gap = gap.resolve(slot_width);
+
+                (slot_width - gap, gap)
+            }
+            (Some(width), Some(gap)) => (width.resolve(slot_width), gap.resolve(slot_width)),
+        };
+
+        if bar_width <= 0.0 || bar_gap < 0.0 {
+            return Err(StackedBarChartError::Usage(format!(
+                "Bar width of {}px and gap of {}px do not fit in a {}px slot",
+                bar_width, bar_gap, slot_width
+            ))
+            .into());
+        }
+
+        Ok((bar_width, bar_gap))
+    }
+
+    /// The compiled --include-category and --exclude-category patterns
+    fn get_category_filters(&self) -> Result<(Vec<Regex>, Vec<Regex>), Box<dyn Error>> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| pattern::compile(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok((
+            compile(&self.include_category)?,
+            compile(&self.exclude_category)?,
+        ))
+    }
+
+    fn get_key_filter(&self) -> Result<Option<Regex>, Box<dyn Error>> {
+        match self.filter_key {
+            Some(ref expression) => match Regex::new(expression) {
+                Ok(regex) => Ok(Some(regex)),
+                Err(error) => Err(StackedBarChartError::Usage(format!(
+                    "--filter-key '{}' is not valid: {}",
+                    expression, error
+                ))
+                .into()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Read a CSS, font, logo or template file named in the options
+    #[cfg(feature = "cli")]
+    pub(crate) fn read_file(path: &Path, kind: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(std::fs::read(path).context(format!(
+            "Unable to read {} file '{}'",
+            kind,
+            path.to_string_lossy()
+        ))?)
+    }
+
+    /// Without the command line tool there is no file access, as when running in a browser
+    #[cfg(not(feature = "cli"))]
+    pub(crate) fn read_file(path: &Path, kind: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        Err(StackedBarChartError::Usage(format!(
+            "Unable to read {} file '{}' when built without the 'cli' feature",
+            kind,
+            path.to_string_lossy()
+        ))
+        .into())
+    }
+
+    fn get_css(&self) -> Result<Option<String>, Box<dyn Error>> {
+        match self.css_file {
+            Some(ref path) => Ok(Some(String::from_utf8(Self::read_file(path, "CSS")?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_font_face(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let path = match self.embed_font {
+            Some(ref path) => path,
+            None => return Ok(None),
+        };
+        let (mime, format) = match path.extension().and_then(|e| e.to_str()) {
+            Some("woff") => ("woff", "woff"),
+            Some("woff2") => ("woff2", "woff2"),
+            Some("ttf") => ("ttf", "truetype"),
+            Some("otf") => ("otf", "opentype"),
+            _ => {
+                return Err(StackedBarChartError::Usage(format!(
+                    "Font file '{}' must be a .woff, .woff2, .ttf or .otf file",
+                    path.to_string_lossy()
+                ))
+                .into())
+            }
+        };
+        let bytes = Self::read_file(path, "font")?;
+
+        Ok(Some(format!(
+            "@font-face{{font-family:\"{}\";src:url(data:font/{};base64,{}) format(\"{}\");}}",
+            self.font_family,
+            mime,
+            BASE64_STANDARD.encode(bytes),
+            format
+        )))
+    }
+
+    /// The logo image as a data URL
+    fn get_logo(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let path = match self.logo {
+            Some(ref path) => path,
+            None => return Ok(None),
+        };
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let mime = match extension.as_deref() {
+            Some("png") => "png",
+            Some("jpg") | Some("jpeg") => "jpeg",
+            Some("gif") => "gif",
+            Some("webp") => "webp",
+            Some("svg") => "svg+xml",
+            _ => {
+                return Err(StackedBarChartError::Usage(format!(
+                    "Logo file '{}' must be a .png, .jpg, .gif, .webp or .svg file",
+                    path.to_string_lossy()
+                ))
+                .into())
+            }
+        };
+
+        if !(0.0..=1.0).contains(&self.logo_opacity) {
+            return Err(StackedBarChartError::Usage(
+                "Logo opacity must be from 0 to 1".to_string(),
+            )
+            .into());
+        }
+
+        let bytes = Self::read_file(path, "logo")?;
+
+        Ok(Some(format!(
+            "data:image/{};base64,{}",
+            mime,
+            BASE64_STANDARD.encode(bytes)
+        )))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ChartData {
+    pub title: String,
+    pub units: String,
+    pub categories: Vec<CategoryData>,
+    pub items: Vec<ItemData>,
+    #[serde(default)]
+    pub margins: MarginData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Options for rendering the chart with the tool, which those given on the command line
+    /// override
+    #[serde(
+        default,
+        deserialize_with = "ChartOptions::deserialize_block",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub options: Option<ChartOptions>,
+}
+
+/// Margins around the plot area, with any not given sized automatically
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MarginData {
+    pub top: Option<f64>,
+    pub right: Option<f64>,
+    pub bottom: Option<f64>,
+    pub left: Option<f64>,
+}
+
+/// A category, given in the input either as just its name or as an object
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(from = "CategoryEntry", into = "CategoryEntry")]
+pub struct CategoryData {
+    pub name: String,
+    pub hidden: bool,
+    /// Categories with the same stack are stacked together, side by side with other stacks
+    pub stack: Option<String>,
+    /// Factor the category's values are multiplied by before charting
+    pub scale: Option<f64>,
+    /// Arithmetic in 'v' applied to the category's values before charting
+    pub transform: Option<Transform>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum CategoryEntry {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        hidden: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stack: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scale: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transform: Option<Transform>,
+    },
+}
+
+impl From<CategoryEntry> for CategoryData {
+    fn from(entry: CategoryEntry) -> Self {
+        match entry {
+            CategoryEntry::Name(name) => CategoryData {
+                name,
+                hidden: false,
+                stack: None,
+                scale: None,
+                transform: None,
+            },
+            CategoryEntry::Full {
+                name,
+                hidden,
+                stack,
+                scale,
+                transform,
+            } => CategoryData {
+                name,
+                hidden,
+                stack,
+                scale,
+                transform,
+            },
+        }
+    }
+}
+
+impl From<CategoryData> for CategoryEntry {
+    fn from(category: CategoryData) -> Self {
+        match category {
+            CategoryData {
+                name,
+                hidden: false,
+                stack: None,
+                scale: None,
+                transform: None,
+            } => CategoryEntry::Name(name),
+            CategoryData {
+                name,
+                hidden,
+                stack,
+                scale,
+                transform,
+            } => CategoryEntry::Full {
+                name,
+                hidden,
+                stack,
+                scale,
+                transform,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ItemData {
+    pub key: String,
+    /// Values for each category, which are `null` when missing
+    pub values: Vec<Option<f64>>,
+    /// Width of this bar relative to the others
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    /// A short note drawn as a callout above the bar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl ChartData {
+    /// A stable FNV-1a hash of the chart categories, used to seed color generation so that
+    /// the same categories always get the same colors regardless of the values charted.
+    pub fn category_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        for category in self.categories.iter() {
+            for byte in category.name.bytes().chain(std::iter::once(0)) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+
+        hash
+    }
+
+    /// A copy of the chart with control characters removed from every label, so that hostile
+    /// data cannot produce invalid output
+    fn sanitized(&self) -> ChartData {
+        let mut chart_data = self.clone();
+
+        chart_data.title = text::sanitize(&self.title);
+        chart_data.units = text::sanitize(&self.units);
+        chart_data.caption = self.caption.as_deref().map(text::sanitize);
+
+        for category in chart_data.categories.iter_mut() {
+            category.name = text::sanitize(&category.name);
+        }
+
+        for item in chart_data.items.iter_mut() {
+            item.key = text::sanitize(&item.key);
+            item.note = item.note.as_deref().map(text::sanitize);
+        }
+
+        chart_data
+    }
+
+    /// Apply the transform of each value's category then `transform`, and multiply by `scale`
+    /// and the scale of the category. The category transforms and scales are then dropped so
+    /// that the values aren't changed again if the chart is embedded in the output.
+    fn transform_values(
+        &mut self,
+        transform: Option<&Transform>,
+        scale: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut scales = vec![];
+        let mut transforms = vec![];
+
+        for category in self.categories.iter_mut() {
+            let category_scale = category.scale.take().unwrap_or(1.0);
+
+            if !category_scale.is_finite() || category_scale == 0.0 {
+                return Err(StackedBarChartError::validation(format!(
+                    "Scale for category '{}' must be a number other than zero",
+                    category.name
+                ))
+                .into());
+            }
+
+            scales.push(scale * category_scale);
+            transforms.push(category.transform.take());
+        }
+
+        // Values that aren't numbers to start with are reported when the chart is checked
+        for item in self.items.iter_mut() {
+            for (j, value) in item.values.iter_mut().enumerate().take(scales.len()) {
+                let n = match value {
+                    Some(n) if n.is_finite() => *n,
+                    _ => continue,
+                };
+                let transformed = [transforms[j].as_ref(), transform]
+                    .into_iter()
+                    .flatten()
+                    .fold(n, |n, transform| transform.apply(n));
+
+                if !transformed.is_finite() {
+                    return Err(StackedBarChartError::Validation {
+                        item: Some(item.key.clone()),
+                        reason: format!(
+                            "Transforming {} in '{}' for category '{}' does not give a number",
+                            n, item.key, self.categories[j].name
+                        ),
+                    }
+                    .into());
+                }
+
+                *value = Some(transformed * scales[j]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the categories with names that don't match any `include` pattern, unless there
+    /// are none, or that match an `exclude` pattern, along with their values
+    fn filter_categories(&mut self, include: &[Regex], exclude: &[Regex]) {
+        if !self.is_complete() {
+            return;
+        }
+
+        let kept: Vec<bool> = self
+            .categories
+            .iter()
+            .map(|category| {
+                (include.is_empty() || include.iter().any(|p| p.is_match(&category.name)))
+                    && !exclude.iter().any(|p| p.is_match(&category.name))
+            })
+            .collect();
+
+        for item in self.items.iter_mut() {
+            item.values = item
+                .values
+                .iter()
+                .zip(&kept)
+                .filter(|(_, kept)| **kept)
+                .map(|(value, _)| *value)
+                .collect();
+        }
+
+        self.categories = std::mem::take(&mut self.categories)
+            .into_iter()
+            .zip(&kept)
+            .filter(|(_, kept)| **kept)
+            .map(|(category, _)| category)
+            .collect();
+    }
+
+    /// Parse the item keys as dates, in `key_format` or else ISO 8601, sort the items by date
+    /// and sum those in the same `bucket`. The keys are rewritten in ISO 8601 form.
+    fn date_items(
+        &mut self,
+        key_format: Option<&str>,
+        bucket: Option<Bucket>,
+        locale: &dates::Locale,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut dated = vec![];
+
+        for (index, item) in std::mem::take(&mut self.items).into_iter().enumerate() {
+            let date = match key_format {
+                Some(format) => dates::Date::parse_format(&item.key, format, locale),
+                None => dates::Date::parse(&item.key),
+            };
+
+            match (date, bucket) {
+                (Some(date), Some(bucket)) => dated.push((date.bucket(bucket), item)),
+                (Some(date), None) => dated.push((date, item)),
+                (None, _) => {
+                    return Err(StackedBarChartError::validation(format!(
+                        "Item {} key '{}' is not a date in the format '{}'",
+                        index,
+                        item.key,
+                        key_format.unwrap_or("%Y-%m-%d")
+                    ))
+                    .into())
+                }
+            }
+        }
+
+        dated.sort_by_key(|(date, _)| *date);
+
+        for (date, mut item) in dated {
+            item.key = date.iso();
+
+            match self.items.last_mut() {
+                Some(last) if bucket.is_some() && last.key == item.key => {
+                    if last.values.len() < item.values.len() {
+                        last.values.resize(item.values.len(), None);
+                    }
+
+                    for (sum, value) in last.values.iter_mut().zip(item.values) {
+                        Self::add_value(sum, value);
+                    }
+
+                    last.width = None;
+                }
+                _ => self.items.push(item),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    /// Merge the items of `other` into this chart, adding its categories after these ones
+    /// unless `policy` sums them into categories with the same name. Items with keys not in
+    /// this chart are added after its items.
+    fn merge(&mut self, other: ChartData, policy: MergePolicy) -> Result<(), Box<dyn Error>> {
+        if !self.is_complete() || !other.is_complete() {
+            return Err(StackedBarChartError::validation(
+                "Charts can only be merged when every item has a value for every category"
+                    .to_string(),
+            )
+            .into());
+        }
+
+        if self.units != other.units {
+            return Err(StackedBarChartError::validation(format!(
+                "Charts in '{}' cannot be merged with charts in '{}'",
+                other.units, self.units
+            ))
+            .into());
+        }
+
+        let num_categories = self.categories.len();
+        // The category of this chart that each category of the other chart goes into
+        let columns: Vec<usize> = other
+            .categories
+            .into_iter()
+            .map(|category| {
+                match (
+                    policy,
+                    self.categories.iter().position(|c| c.name == category.name),
+                ) {
+                    (MergePolicy::Sum, Some(j)) => j,
+                    _ => {
+                        self.categories.push(category);
+                        self.categories.len() - 1
+                    }
+                }
+            })
+            .collect();
+
+        for item in self.items.iter_mut() {
+            item.values.truncate(num_categories);
+            item.values.resize(self.categories.len(), None);
+        }
+
+        for other_item in other.items {
+            let index = match self.items.iter().position(|i| i.key == other_item.key) {
+                Some(index) => index,
+                None => {
+                    self.items.push(ItemData {
+                        key: other_item.key.clone(),
+                        values: vec![None; self.categories.len()],
+                        width: other_item.width,
+                        note: other_item.note.clone(),
+                    });
+                    self.items.len() - 1
+                }
+            };
+
+            for (value, column) in other_item.values.into_iter().zip(&columns) {
+                Self::add_value(&mut self.items[index].values[*column], value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The total of the visible values of each item, with missing values counting as zero
+    fn item_totals(&self) -> Vec<f64> {
+        self.items
+            .iter()
+            .map(|item| {
+                item.values
+                    .iter()
+                    .zip(&self.categories)
+                    .filter(|(_, category)| !category.hidden)
+                    .filter_map(|(value, _)| *value)
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Sort the items, keeping items which compare equal in their original order
+    fn sort_items(&mut self, sort: &SortOrder, descending: bool) -> Result<(), Box<dyn Error>> {
+        if !self.is_complete() {
+            return Ok(());
+        }
+
+        let mut keyed: Vec<(f64, ItemData)> = match sort {
+            SortOrder::Total => self.item_totals(),
+            SortOrder::Key => vec![0.0; self.items.len()],
+            SortOrder::Category(name) => {
+                let j = match self.categories.iter().position(|c| c.name == *name) {
+                    Some(j) => j,
+                    None => {
+                        return Err(StackedBarChartError::Usage(format!(
+                            "There is no category '{}' to sort by",
+                            name
+                        ))
+                        .into())
+                    }
+                };
+
+                self.items
+                    .iter()
+                    .map(|item| item.values[j].unwrap_or(0.0))
+                    .collect()
+            }
+        }
+        .into_iter()
+        .zip(std::mem::take(&mut self.items))
+        .collect();
+
+        keyed.sort_by(|(a, a_item), (b, b_item)| {
+            let ordering = match sort {
+                SortOrder::Key => a_item.key.cmp(&b_item.key),
+                _ => a.total_cmp(b),
+            };
+
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.items = keyed.into_iter().map(|(_, item)| item).collect();
+
+        Ok(())
+    }
+
+    /// Keep the `n` items with the largest totals, in their original order, and collapse the
+    /// rest into a single 'Other' item on the end
+    fn collapse_items(&mut self, n: usize) {
+        if self.items.len() <= n || !self.is_complete() {
+            return;
+        }
+
+        let totals = self.item_totals();
+        let mut order: Vec<usize> = (0..self.items.len()).collect();
+
+        order.sort_by(|a, b| totals[*b].total_cmp(&totals[*a]));
+        order.truncate(n);
+
+        let mut other = ItemData {
+            key: "Other".to_string(),
+            values: vec![None; self.categories.len()],
+            width: None,
+            note: None,
+        };
+        let mut items = vec![];
+
+        for (i, item) in std::mem::take(&mut self.items).into_iter().enumerate() {
+            if order.contains(&i) {
+                items.push(item);
+            } else {
+                for (sum, value) in other.values.iter_mut().zip(item.values) {
+                    Self::add_value(sum, value);
+                }
+            }
+        }
+
+        items.push(other);
+        self.items = items;
+    }
+
+    /// Keep the `n` visible categories with the largest totals and collapse the rest into a
+    /// single 'Other' category on the end. Hidden categories are left alone.
+    fn collapse_categories(&mut self, n: usize) {
+        let visible: Vec<usize> = (0..self.categories.len())
+            .filter(|j| !self.categories[*j].hidden)
+            .collect();
+
+        if visible.len() <= n || !self.is_complete() {
+            return;
+        }
+
+        let totals: Vec<f64> = (0..self.categories.len())
+            .map(|j| self.items.iter().filter_map(|item| item.values[j]).sum())
+            .collect();
+        let mut order = visible.clone();
+
+        order.sort_by(|a, b| totals[*b].total_cmp(&totals[*a]));
+        order.truncate(n);
+
+        let collapsed: Vec<usize> = visible.into_iter().filter(|j| !order.contains(j)).collect();
+
+        for item in self.items.iter_mut() {
+            let mut other = None;
+
+            for j in collapsed.iter() {
+                Self::add_value(&mut other, item.values[*j]);
+            }
+
+            item.values = (0..self.categories.len())
+                .filter(|j| !collapsed.contains(j))
+                .map(|j| item.values[j])
+                .chain(std::iter::once(other))
+                .collect();
+        }
+
+        self.categories = std::mem::take(&mut self.categories)
+            .into_iter()
+            .enumerate()
+            .filter(|(j, _)| !collapsed.contains(j))
+            .map(|(_, category)| category)
+            .chain(std::iter::once(CategoryData {
+                name: "Other".to_string(),
+                hidden: false,
+                stack: None,
+                scale: None,
+                transform: None,
+            }))
+            .collect();
+    }
+
+    /// Whether every item has a value for every category, which is checked when the chart is
+    /// processed so that the problems can be reported together
+    fn is_complete(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| item.values.len() >= self.categories.len())
+    }
+
+    /// Add a value to a sum which stays missing until a value that isn't is added
+    fn add_value(sum: &mut Option<f64>, value: Option<f64>) {
+        if let Some(value) = value {
+            *sum = Some(sum.unwrap_or(0.0) + value);
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    /// Replace the labels and/or values with stand-ins that keep the shape of the chart
+    fn redact(&mut self, redact: &[Redact], rng: &mut impl Rng) {
+        if redact.contains(&Redact::Labels) {
+            self.title = "Chart".to_string();
+            self.units = "units".to_string();
+
+            for (i, category) in self.categories.iter_mut().enumerate() {
+                category.name = format!("Category {}", i + 1);
+            }
+
+            for (i, item) in self.items.iter_mut().enumerate() {
+                item.key = format!("Item {}", i + 1);
+            }
+
+            if self.caption.is_some() {
+                self.caption = Some("Caption".to_string());
+            }
+        }
+
+        if redact.contains(&Redact::Values) {
+            let factor: f64 = rng.gen_range(0.5..2.0);
+
+            for item in self.items.iter_mut() {
+                for value in item.values.iter_mut().flatten() {
+                    *value *= factor;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Gutter {
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+impl Gutter {
+    pub fn top_bottom(&self) -> f64 {
+        self.bottom + self.top
+    }
+
+    pub fn left_right(&self) -> f64 {
+        self.right + self.left
+    }
+}
+
+#[derive(Debug)]
+struct BarData {
+    label: String,
+    values: Vec<f64>,
+    /// Whether each value was missing, in which case it is zero in `values`
+    missing: Vec<bool>,
+    total: f64,
+    x: f64,
+    width: f64,
+    summary: bool,
+    highlighted: bool,
+    note: Option<String>,
+}
+
+/// A note at `(x, y)` with a leader line down to its bar at `(bar_x, bar_y)`, which is above any
+/// change label, relative to the top left of the plot
+#[derive(Debug)]
+struct Note {
+    text: String,
+    x: f64,
+    y: f64,
+    bar_x: f64,
+    bar_y: f64,
+}
+
+/// A bar segment with its bottom left corner at `(x, y)`
+#[derive(Debug)]
+struct Segment {
+    category: usize,
+    negative: bool,
+    missing: bool,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    top_radius: f64,
+    bottom_radius: f64,
+}
+
+#[derive(Debug)]
+struct RenderData {
+    title: String,
+    title_lines: Vec<String>,
+    title_line_height: f64,
+    categories: Vec<String>,
+    gutter: Gutter,
+    y_axis_height: f64,
+    y_axis_range: (f64, f64),
+    y_axis_interval: f64,
+    y_axis_decimal_places: usize,
+    number_format: Option<NumberFormat>,
+    x_axis_width: f64,
+    bar_fraction: f64,
+    category_stacks: Vec<usize>,
+    num_stacks: usize,
+    /// Indices of the categories in the order they are stacked from the bottom up
+    stack_order: Vec<usize>,
+    bar_data: Vec<BarData>,
+    styles: Vec<String>,
+    legend_gutter: Gutter,
+    legend_rect_size: f64,
+    legend_labels: Vec<String>,
+    legend_classes: Vec<String>,
+    legend_entries: Vec<(f64, f64)>,
+    legend_suffixes: Vec<String>,
+    legend_height: f64,
+    legend_position: LegendPosition,
+    /// The width of a legend to the right of the plot, including the space around it
+    legend_width: f64,
+    /// The room above the plot for value, change and note labels
+    notes_height: f64,
+    caption: Option<String>,
+    caption_height: f64,
+    metadata: Option<String>,
+    canvas: (Option<f64>, Option<f64>),
+    fragment: bool,
+    responsive: bool,
+    rtl: bool,
+    /// Whether right to left text is anchored by its left or right hand end rather than by where
+    /// it starts, for renderers that ignore the direction
+    physical_anchors: bool,
+    /// Whether the plot is drawn turned a quarter turn clockwise, with bars running to the right
+    horizontal: bool,
+    animation_stagger: Option<f64>,
+    /// The logo image as a data URL
+    logo: Option<String>,
+    logo_position: LogoPosition,
+    logo_opacity: f64,
+    logo_size: f64,
+    /// The template to compose the chart into
+    template: Option<String>,
+    /// Percentage change of each bar's total from the bar before, when labelled
+    changes: Vec<Option<f64>>,
+    /// Whether each bar's total is shown above it, as it is when there is a single category
+    value_labels: bool,
+    notes: Vec<Note>,
+    legend_rect_corner_radius: f64,
+    segment_separator_width: f64,
+    segment_corner_radius: f64,
+    bar_corner_radius: f64,
+    fill: FillStyle,
+    category_colors: Vec<u32>,
+    zebra: bool,
+    /// The color of the lines across the plot at each y-axis label, when they are drawn
+    gridlines: Option<u32>,
+    gradient: bool,
+    delta: bool,
+    theme: Theme,
+    background: Option<String>,
+    units: String,
+    tooltip: Option<String>,
+    max_label_chars: Option<usize>,
+    /// The value charted for missing values, or none when their segments are left out
+    missing_value: Option<f64>,
+    font_size: f64,
+    /// Only read for EPS output, as SVG output has the font in its styles
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    font_family: String,
+}
+
+impl RenderData {
+    fn width(&self) -> f64 {
+        self.gutter.left + self.plot_size().0 + self.gutter.right + self.legend_width
+    }
+
+    fn height(&self) -> f64 {
+        let legend = match self.legend_position {
+            LegendPosition::Bottom => self.legend_gutter.top_bottom() + self.legend_height,
+            _ => 0.0,
+        };
+
+        self.gutter.top_bottom() + self.plot_size().1 + legend + self.caption_height
+    }
+
+    /// The width and height of the plot on the chart, which are swapped for horizontal bars
+    fn plot_size(&self) -> (f64, f64) {
+        if self.horizontal {
+            (self.y_axis_height, self.x_axis_width)
+        } else {
+            (self.x_axis_width, self.y_axis_height)
+        }
+    }
+
+    /// Where a point of the plot laid out with upright bars goes on the chart. For horizontal
+    /// bars the plot turns a quarter turn clockwise so the bottom left corner stays put, which
+    /// puts the first item at the top.
+    fn place(&self, x: f64, y: f64) -> (f64, f64) {
+        if self.horizontal {
+            (
+                self.gutter.left + self.gutter.top + self.y_axis_height - y,
+                self.gutter.top + x - self.gutter.left,
+            )
+        } else {
+            (x, y)
+        }
+    }
+
+    /// The transform that does what [RenderData::place] does to the parts of the plot
+    fn plot_transform(&self) -> String {
+        format!(
+            "matrix(0,1,-1,0,{},{})",
+            self.gutter.left + self.gutter.top + self.y_axis_height,
+            self.gutter.top - self.gutter.left
+        )
+    }
+
+    /// The text of a y-axis label for `n`
+    fn y_label(&self, n: f64) -> String {
+        self.number_format
+            .unwrap_or(NumberFormat::Plain)
+            .format(n, self.y_axis_decimal_places)
+    }
+
+    /// The text of a bar or legend total
+    fn format_total(&self, total: f64) -> String {
+        self.number_format
+            .unwrap_or(NumberFormat::Grouped)
+            .format(total, self.y_axis_decimal_places)
+    }
+
+    /// The X coordinate of the right hand end of the plot, as laid out with upright bars
+    fn plot_right(&self) -> f64 {
+        self.gutter.left + self.x_axis_width
+    }
+
+    /// The top left corner of the legend, which its entries are placed from
+    fn legend_origin(&self) -> (f64, f64) {
+        match self.legend_position {
+            LegendPosition::Top => (
+                self.legend_gutter.left,
+                self.gutter.top
+                    - self.notes_height
+                    - self.legend_height
+                    - self.legend_gutter.bottom,
+            ),
+            LegendPosition::Right => (
+                self.gutter.left + self.plot_size().0 + self.gutter.right + self.legend_gutter.left,
+                self.gutter.top,
+            ),
+            _ => (
+                self.legend_gutter.left,
+                self.gutter.top_bottom() + self.plot_size().1 + self.legend_gutter.top,
+            ),
+        }
+    }
+
+    /// The height of the space above the plot that the title is centered in, which is above
+    /// any legend at the top
+    fn title_area(&self) -> f64 {
+        match self.legend_position {
+            LegendPosition::Top if self.legend_height > 0.0 => {
+                self.gutter.top
+                    - self.notes_height
+                    - self.legend_height
+                    - self.legend_gutter.top_bottom()
+            }
+            _ => self.gutter.top,
+        }
+    }
+
+    /// Convert a value to a height on the Y axis
+    fn scale(&self, n: f64) -> f64 {
+        n * (self.y_axis_height / (self.y_axis_range.1 - self.y_axis_range.0))
+    }
+
+    /// The Y coordinate of zero, which is the bottom of the plot unless there are negative values
+    fn baseline(&self) -> f64 {
+        self.gutter.top + self.y_axis_height - self.scale(-self.y_axis_range.0)
+    }
+
+    /// The Y coordinate of the top of a bar, or of zero if the bar is below it
+    fn bar_top(&self, bar: &BarData) -> f64 {
+        let (segments, _) = self.bar_segments(bar);
+
+        segments
+            .iter()
+            .map(|segment| segment.y - segment.height)
+            .fold(self.baseline(), f64::min)
+    }
+
+    /// The height of a line of value, change or note labels
+    fn label_line_height(&self) -> f64 {
+        (self.font_size * 1.2).ceil()
+    }
+
+    /// The top of a bar's value and change labels relative to the top of the plot, measured
+    /// from the bar top clamped to the plot so that bars drawn outside it have no effect
+    fn label_top(&self, i: usize, bar: &BarData) -> f64 {
+        let labels =
+            self.value_labels as usize + matches!(self.changes.get(i), Some(Some(_))) as usize;
+
+        (self.bar_top(bar) - self.gutter.top).clamp(0.0, self.y_axis_height)
+            - labels as f64 * self.label_line_height()
+    }
+
+    /// Place each bar's note above it, clear of any value or change label, moving it up a line
+    /// at a time until it overlaps no note placed before it, then make room above the plot for
+    /// the highest note or label
+    fn place_notes(&mut self) {
+        let line_height = self.label_line_height();
+        let labels_top = self
+            .bar_data
+            .iter()
+            .enumerate()
+            .map(|(i, bar)| self.label_top(i, bar))
+            .fold(0.0, f64::min);
+        let mut notes = vec![];
+
+        for (i, bar) in self.bar_data.iter().enumerate() {
+            let text = match &bar.note {
+                Some(text) => text,
+                None => continue,
+            };
+            let bar_y = self.label_top(i, bar);
+            let width = text::text_width(text, self.font_size);
+            let bar_x = bar.x + bar.width / 2.0;
+            // Keep the note on the canvas, spilling into the gutters if need be
+            let (low, high) = (
+                width / 2.0 - self.gutter.left,
+                self.x_axis_width + self.gutter.right - width / 2.0,
+            );
+            let x = if low <= high {
+                bar_x.clamp(low, high)
+            } else {
+                self.x_axis_width / 2.0
+            };
+            let overlaps = |y: f64, note: &Note| {
+                let note_width = text::text_width(&note.text, self.font_size);
+
+                (x - note.x).abs() < (width + note_width) / 2.0 && (y - note.y).abs() < line_height
+            };
+            let mut y = bar_y - 15.0;
+
+            while notes.iter().any(|note| overlaps(y, note)) {
+                y -= line_height;
+            }
+
+            notes.push(Note {
+                text: text.clone(),
+                x,
+                y,
+                bar_x,
+                bar_y,
+            });
+        }
+
+        let highest = notes
+            .iter()
+            .map(|note| note.y - self.font_size)
+            .fold(labels_top, f64::min);
+
+        self.gutter.top -= highest;
+        self.notes_height = -highest;
+        self.notes = notes;
+    }
+
+    /// The X coordinate of the y-axis, which is on the right for right to left charts
+    fn y_axis_x(&self) -> f64 {
+        if self.rtl {
+            self.plot_right()
+        } else {
+            self.gutter.left
+        }
+    }
+
+    /// The X coordinate of the text of a legend entry with its swatch at `x`
+    fn legend_text_x(&self, x: f64) -> f64 {
+        if self.rtl {
+            x - 5.0
+        } else {
+            x + self.legend_rect_size + 5.0
+        }
+    }
+
+    /// The X coordinate of the end of the caption, which is on the left for right to left charts
+    fn caption_x(&self) -> f64 {
+        if self.rtl {
+            self.legend_gutter.left
+        } else {
+            self.width() - self.legend_gutter.right
+        }
+    }
+
+    /// Text that would extend past the edges of the chart, with the key of the item whose label
+    /// it is. Widths are estimates, so this catches text well outside the chart rather than by a
+    /// pixel or two.
+    fn clipped_content(&self) -> Vec<(Option<String>, String)> {
+        let (width, height) = (self.width(), self.height());
+        let outside = |left: f64, right: f64| left < 0.0 || right > width;
+        let mut clipped = vec![];
+
+        for line in self.title_lines.iter() {
+            let line_width = text::text_width(line, self.font_size * 1.2);
+
+            if outside((width - line_width) / 2.0, (width + line_width) / 2.0) {
+                clipped.push((None, format!("Title '{}' is wider than the chart", line)));
+            }
+        }
+
+        let num_y_labels =
+            ((self.y_axis_range.1 - self.y_axis_range.0) / self.y_axis_interval) as usize + 1;
+        let y_label_width = (0..num_y_labels)
+            .map(|i| {
+                let n = i as f64 * self.y_axis_interval + self.y_axis_range.0;
+
+                text::text_width(&self.y_label(n), self.font_size)
+            })
+            .fold(0.0, f64::max);
+        // Under horizontal bars the labels are centered on their marks along the bottom
+        let y_labels_outside = if self.horizontal {
+            outside(
+                self.gutter.left - y_label_width / 2.0,
+                self.gutter.left + self.y_axis_height + y_label_width / 2.0,
+            )
+        } else if self.rtl {
+            outside(0.0, self.y_axis_x() + 10.0 + y_label_width)
+        } else {
+            outside(self.y_axis_x() - 10.0 - y_label_width, 0.0)
+        };
+
+        if y_labels_outside {
+            clipped.push((
+                None,
+                "Y-axis labels run off the side of the chart".to_string(),
+            ));
+        }
+
+        for bar in self.bar_data.iter() {
+            let label = text::truncate(&bar.label, self.max_label_chars);
+            let label_width =
+                text::text_width(label.as_ref().unwrap_or(&bar.label), self.font_size);
+            let reach = label_width * std::f64::consts::FRAC_1_SQRT_2;
+            let x = self.gutter.left + bar.x + bar.width / 2.0;
+            let (left, right) = if self.rtl {
+                (x - reach, x)
+            } else {
+                (x, x + reach)
+            };
+            let clipped_label = if self.horizontal {
+                outside(self.gutter.left - 10.0 - label_width, 0.0)
+            } else {
+                outside(left, right)
+                    || self.gutter.top + self.y_axis_height + X_LABEL_OFFSET + reach > height
+            };
+
+            if clipped_label {
+                clipped.push((
+                    (!bar.summary).then(|| bar.label.clone()),
+                    format!("Label '{}' runs off the edge of the chart", bar.label),
+                ));
+            }
+        }
+
+        let legend_font_size = self.font_size * 1.2 * 4.0 / 3.0;
+
+        for (i, (x, _)) in self.legend_entries.iter().enumerate() {
+            let label = text::truncate(&self.legend_labels[i], self.max_label_chars);
+            let text_width = text::text_width(
+                label.as_ref().unwrap_or(&self.legend_labels[i]),
+                legend_font_size,
+            ) + text::text_width(&self.legend_suffixes[i], legend_font_size);
+            let x = self.legend_origin().0 + x;
+            let (left, right) = if self.rtl {
+                (x - 5.0 - text_width, x + self.legend_rect_size)
+            } else {
+                (x, x + self.legend_rect_size + 5.0 + text_width)
+            };
+
+            if outside(left, right) {
+                clipped.push((
+                    None,
+                    format!(
+                        "Legend entry '{}' runs off the side of the chart",
+                        self.legend_labels[i]
+                    ),
+                ));
+            }
+        }
+
+        if let Some(caption) = &self.caption {
+            let caption_width = text::text_width(caption, self.font_size * 0.9);
+            let x = self.caption_x();
+            let (left, right) = if self.rtl {
+                (x, x + caption_width)
+            } else {
+                (x - caption_width, x)
+            };
+
+            if outside(left, right) {
+                clipped.push((None, "Caption is wider than the chart".to_string()));
+            }
+        }
+
+        clipped
+    }
+
+    /// Whether any bar has a placeholder segment for a missing value
+    fn has_placeholders(&self) -> bool {
+        self.missing_value.is_some() && self.bar_data.iter().any(|b| b.missing.contains(&true))
+    }
+
+    /// The segments of a bar, and the left end and width of the separators between them
+    fn bar_segments(&self, bar: &BarData) -> (Vec<Segment>, Vec<(f64, f64, f64)>) {
+        let baseline = self.baseline();
+        let bar_width = bar.width * self.bar_fraction;
+        let stack_width = bar_width / self.num_stacks as f64;
+        let bar_x = self.gutter.left + bar.x + (bar.width - bar_width) / 2.0;
+        let heights: Vec<f64> = bar
+            .values
+            .iter()
+            .zip(&bar.missing)
+            .map(|(n, missing)| match (missing, self.missing_value) {
+                (true, Some(n)) => self.scale(n),
+                _ => self.scale(*n),
+            })
+            .collect();
+        // Segments stack up from zero, except negative ones in delta mode which stack down,
+        // with each stack of the bar side by side
+        let (mut above, mut below) = (
+            vec![baseline; self.num_stacks],
+            vec![baseline; self.num_stacks],
+        );
+        let (mut num_above, mut num_below) = (vec![0; self.num_stacks], vec![0; self.num_stacks]);
+        let mut segments = vec![];
+        let mut separators = vec![];
+        // The outermost visible segments at either end of each stack get rounded corners
+        let outermost = |negative: bool, stack: usize| {
+            self.stack_order.iter().copied().rev().find(|j| {
+                heights[*j] != 0.0
+                    && self.category_stacks[*j] == stack
+                    && (self.delta && bar.values[*j] < 0.0) == negative
+            })
+        };
+
+        for j in self.stack_order.iter().copied() {
+            if bar.missing[j] && self.missing_value.is_none() {
+                continue;
+            }
+
+            let stack = self.category_stacks[j];
+            let x = bar_x + stack as f64 * stack_width;
+            let negative = self.delta && bar.values[j] < 0.0;
+            let (edge, count) = if negative {
+                (&mut below[stack], &mut num_below[stack])
+            } else {
+                (&mut above[stack], &mut num_above[stack])
+            };
+
+            if *count > 0 {
+                separators.push((x, *edge, stack_width));
+            }
+
+            *count += 1;
+            *edge -= heights[j];
+
+            let (mut top_radius, mut bottom_radius) =
+                (self.segment_corner_radius, self.segment_corner_radius);
+
+            if outermost(false, stack) == Some(j) {
+                top_radius = top_radius.max(self.bar_corner_radius);
+            } else if outermost(true, stack) == Some(j) {
+                bottom_radius = bottom_radius.max(self.bar_corner_radius);
+            }
+
+            segments.push(Segment {
+                category: j,
+                negative,
+                missing: bar.missing[j],
+                x,
+                y: if negative { *edge } else { *edge + heights[j] },
+                width: stack_width,
+                height: heights[j].abs(),
+                top_radius,
+                bottom_radius,
+            });
+        }
+
+        (segments, separators)
+    }
+
+    /// The size of the output, which differs from the layout size when the chart is scaled
+    fn canvas_size(&self) -> (f64, f64) {
+        let (width, height) = (self.width(), self.height());
+
+        match self.canvas {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, height * w / width),
+            (None, Some(h)) => (width * h / height, h),
+            (None, None) => (width, height),
+        }
+    }
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    pub fn new(log: &'a dyn StackedBarChartLog) -> StackedBarChartTool<'a> {
+        StackedBarChartTool {
+            log,
+            error_format: ErrorFormat::Text,
+            log_level: LogLevel::Normal,
+            input_file: None,
+        }
+    }
+
+    /// Render `spec` to an SVG document, logging a warning if it is too large to display well
+    pub fn render_spec(self: &Self, spec: &ChartSpec) -> Result<String, StackedBarChartError> {
+        let render_data = self
+            .process_chart_data(spec)
+            .map_err(|e| StackedBarChartError::classify(e, StackedBarChartError::validation))?;
+
+        self.check_dimensions(&render_data, &spec.options);
+        self.check_overflow(&render_data, &spec.options)
+            .map_err(|e| StackedBarChartError::classify(e, StackedBarChartError::Render))?;
+        self.log_layout(&render_data);
+
+        match self.render_svg(&render_data) {
+            Ok(document) => Ok(document),
+            Err(e) => Err(StackedBarChartError::classify(
+                e,
+                StackedBarChartError::Render,
+            )),
+        }
+    }
+
+    /// Render `chart` to an SVG document with the theme named in the options, for using the
+    /// crate as a library without going through the command line
+    pub fn render(
+        self: &Self,
+        chart: &ChartData,
+        options: &ChartOptions,
+    ) -> Result<String, StackedBarChartError> {
+        self.render_spec(&ChartSpec::new(chart.clone(), options.clone())?)
+    }
+
+    #[cfg(feature = "cli")]
+    /// Run the tool with command line arguments, the first of which is the program name
+    pub fn run(
+        self: &mut Self,
+        args: impl IntoIterator<Item = std::ffi::OsString>,
+    ) -> Result<(), StackedBarChartError> {
+        self.run_args(args)
+            .map_err(|e| StackedBarChartError::classify(e, StackedBarChartError::validation))
+    }
+
+    #[cfg(feature = "cli")]
+    fn run_args(
+        self: &mut Self,
+        args: impl IntoIterator<Item = std::ffi::OsString>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<std::ffi::OsString> = args.into_iter().collect();
+
+        self.error_format = Self::raw_error_format(&args);
+
+        let mut cli = match Cli::parse_args(args) {
+            Ok(m) => m,
+            Err(err) => match err.kind() {
+                clap::error::ErrorKind::DisplayHelp
+                | clap::error::ErrorKind::DisplayVersion
+                | clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
+                    output!(self.log, "{}", err.to_string());
+                    return Ok(());
+                }
+                _ => {
+                    return Err(StackedBarChartError::Usage(
+                        err.to_string()
+                            .trim_start_matches("error: ")
+                            .trim_end()
+                            .to_string(),
+                    )
+                    .into())
+                }
+            },
+        };
+
+        self.error_format = cli.error_format;
+        self.input_file = cli
+            .input_file
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string());
+        self.log_level = match (cli.quiet, cli.verbose) {
+            (true, _) => LogLevel::Quiet,
+            (_, true) => LogLevel::Verbose,
+            _ => LogLevel::Normal,
+        };
+        cli.apply_config()?;
+
+        if cli.print_config {
+            output!(
+                self.log,
+                "{}",
+                serde_json::to_string_pretty(&cli.get_effective_config()?)?
+            );
+            return Ok(());
+        }
+
+        if let Some(Command::Examples {
+            fixture_dir,
+            output_file,
+        }) = &cli.command
+        {
+            let html = self.render_gallery(fixture_dir)?;
+
+            Cli::open_output(output_file.as_deref())?.write_all(html.as_bytes())?;
+            return Ok(());
+        }
+
+        let format = cli.get_format();
+
+        if format == OutputFormat::Png && (cli.options.fragment || cli.inner_only) {
+            return Err(StackedBarChartError::Usage(
+                "PNG output needs a complete SVG document so cannot be a fragment".to_string(),
+            )
+            .into());
+        }
+
+        if let Some(pattern) = &cli.batch {
+            return self.render_batch(&cli, format, pattern);
+        }
+
+        if cli.watch {
+            return self.watch(&cli, format);
+        }
+
+        self.render_input(
+            &cli,
+            format,
+            cli.input_file.as_deref(),
+            cli.output_file.as_deref(),
+        )
+    }
+
+    #[cfg(feature = "cli")]
+    /// Render every chart in `input`, numbering the output files when there are several
+    fn render_input(
+        self: &Self,
+        cli: &Cli,
+        format: OutputFormat,
+        input_file: Option<&Path>,
+        output_file: Option<&Path>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut charts = Self::read_input(cli, input_file)?;
+        let num_charts = charts.len();
+
+        for path in cli.merge.iter() {
+            let mut others = Self::read_input(cli, Some(path))?;
+
+            if num_charts > 1 || others.len() > 1 {
+                return Err(StackedBarChartError::validation(
+                    "Only files with a single chart can be merged".to_string(),
+                )
+                .into());
+            }
+
+            charts[0].merge(others.remove(0), cli.merge_policy)?;
+        }
+
+        if num_charts > 1 && output_file.is_none() {
+            return Err(StackedBarChartError::Usage(format!(
+                "The input has {} charts so an output file is needed to number them",
+                num_charts
+            ))
+            .into());
+        }
+
+        for (index, chart_data) in charts.into_iter().enumerate() {
+            let mut spec = cli.get_spec(chart_data)?;
+
+            if !cli.redact.is_empty() {
+                // Keep the colors of the original categories
+                spec.options.seed = spec
+                    .options
+                    .seed
+                    .or_else(|| Some(spec.data.category_hash()));
+                if cli.deterministic {
+                    let seed = spec.options.seed.unwrap_or_default();
+
+                    spec.data
+                        .redact(&cli.redact, &mut StdRng::seed_from_u64(seed));
+                } else {
+                    spec.data.redact(&cli.redact, &mut thread_rng());
+                }
+            }
+
+            let mut render_data = self.process_chart_data(&spec)?;
+
+            // The rasterizer lays out right to left text as if it were left to right
+            render_data.physical_anchors = format == OutputFormat::Png;
+            self.check_dimensions(&render_data, &spec.options);
+            self.check_overflow(&render_data, &spec.options)?;
+            self.log_layout(&render_data);
+
+            let document = self.render_svg(&render_data)?;
+            let document = if cli.minify {
+                minify::minify(&document, cli.precision)
+            } else if cli.deterministic {
+                minify::round(&document, DETERMINISTIC_PRECISION)
+            } else {
+                document
+            };
+
+            if cli.check_output {
+                let problems = validate::check_svg(&document);
+
+                if !problems.is_empty() {
+                    return Err(StackedBarChartError::Render(format!(
+                        "Rendered SVG failed {} check(s):\n  {}",
+                        problems.len(),
+                        problems.join("\n  ")
+                    ))
+                    .into());
+                }
+            }
+
+            let writer = match output_file {
+                Some(path) if num_charts > 1 => {
+                    Cli::open_output(Some(&Self::numbered_path(path, index + 1)))?
+                }
+                _ => Cli::open_output(output_file)?,
+            };
+
+            match format {
+                OutputFormat::Svg => Self::write_svg_file(writer, &document, cli.inner_only)?,
+                OutputFormat::Html => {
+                    Self::write_html_file(writer, &render_data.title, &document, !cli.no_script)?
+                }
+                #[cfg(feature = "png")]
+                OutputFormat::Png => Self::write_png_file(writer, &document, &spec.options)?,
+                #[cfg(not(feature = "png"))]
+                OutputFormat::Png => {
+                    return Err(StackedBarChartError::Usage(
+                        "PNG output needs the tool built with the 'png' feature".to_string(),
+                    )
+                    .into())
+                }
+                OutputFormat::Eps => Self::write_eps_file(writer, &render_data)?,
+                OutputFormat::Term => Self::write_term_file(writer, &render_data, !cli.no_color)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    /// Read the chart data from an SVG rendered with `--embed-data`
+    fn read_embedded_data(content: &str) -> Result<ChartData, Box<dyn Error>> {
+        let mut in_metadata = false;
+
+        for event in svg::parser::Parser::new(content) {
+            match event {
+                svg::parser::Event::Tag(METADATA_ELEMENT, tag_type, _) => {
+                    in_metadata = tag_type == element::tag::Type::Start;
+                }
+                svg::parser::Event::Text(text) if in_metadata => {
+                    let json = text
+                        .replace("&lt;", "<")
+                        .replace("&gt;", ">")
+                        .replace("&amp;", "&");
+
+                    return Ok(json5::from_str(&json)?);
+                }
+                _ => (),
+            }
+        }
+
+        Err(StackedBarChartError::validation(
+            "The SVG has no chart data in it; render it with --embed-data to include it"
+                .to_string(),
+        )
+        .into())
+    }
+
+    #[cfg(feature = "cli")]
+    /// Read one chart, or an array of charts, from JSON5, or a chart from an SVG with its data
+    /// embedded
+    /// Read the charts in `path`, or standard input when there isn't one, streaming CSV and
+    /// NDJSON which have the file name as the title
+    fn read_input(cli: &Cli, path: Option<&Path>) -> Result<Vec<ChartData>, Box<dyn Error>> {
+        let input = Cli::open_input(path)?;
+
+        match cli.get_input_format(path) {
+            InputFormat::Json5 => Self::read_chart_file(input),
+            format => {
+                let title = path
+                    .and_then(Path::file_stem)
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                Ok(vec![stream::read_stream(
+                    io::BufReader::new(input),
+                    format,
+                    cli.max_bars,
+                    &title,
+                )?])
+            }
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    fn read_chart_file(mut reader: Box<dyn Read>) -> Result<Vec<ChartData>, Box<dyn Error>> {
+        let mut content = String::new();
+
+        reader.read_to_string(&mut content)?;
+
+        // Look past any leading comments to tell an array from a single chart, as trying one
+        // and then the other would lose the line numbers in parse errors
+        let mut rest = content.trim_start();
+
+        while let Some(comment) = rest.strip_prefix("//").or_else(|| rest.strip_prefix("/*")) {
+            let end = if rest.starts_with("//") {
+                comment.find('\n').map_or(comment.len(), |i| i + 1)
+            } else {
+                comment.find("*/").map_or(comment.len(), |i| i + 2)
+            };
+
+            rest = comment[end..].trim_start();
+        }
+
+        if rest.starts_with('<') {
+            return Ok(vec![Self::read_embedded_data(rest)?]);
+        }
+
+        if !rest.starts_with('[') {
+            return Ok(vec![json5::from_str(&content)?]);
+        }
+
+        let charts: Vec<ChartData> = json5::from_str(&content)?;
+
+        if charts.is_empty() {
+            return Err(StackedBarChartError::validation(
+                "The input file contains no charts".to_string(),
+            )
+            .into());
+        }
+
+        Ok(charts)
+    }
+
+    #[cfg(feature = "cli")]
+    /// Number an output file for one of several charts, so 'sales.svg' becomes 'sales-2.svg'
+    fn numbered_path(path: &Path, number: usize) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        match path.extension() {
+            Some(ext) => {
+                path.with_file_name(format!("{}-{}.{}", stem, number, ext.to_string_lossy()))
+            }
+            None => path.with_file_name(format!("{}-{}", stem, number)),
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    fn write_svg_file(
+        mut writer: Box<dyn Write>,
+        content: &str,
+        inner_only: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if !inner_only {
+            writer.write_all(content.as_bytes())?;
+            return Ok(());
+        }
+
+        let start = content.find('>').map_or(0, |i| i + 1);
+        let end = content.rfind("</svg>").unwrap_or(content.len());
+
+        writeln!(writer, "{}", content[start..end].trim())?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    fn write_eps_file(mut writer: Box<dyn Write>, rd: &RenderData) -> Result<(), Box<dyn Error>> {
+        writer.write_all(Self::render_eps(rd)?.as_bytes())?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    fn write_term_file(
+        mut writer: Box<dyn Write>,
+        rd: &RenderData,
+        color: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        writer.write_all(Self::render_term(rd, color, Self::term_columns()).as_bytes())?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    fn write_html_file(
+        mut writer: Box<dyn Write>,
+        title: &str,
+        content: &str,
+        interactive: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        writer.write_all(html::page(title, content, interactive).as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Log the values derived from the chart data which decide how it looks
+    fn log_layout(self: &Self, rd: &RenderData) {
+        if self.log_level < LogLevel::Verbose {
+            return;
+        }
+
+        let (width, height) = rd.canvas_size();
+
+        info!(
+            self.log,
+            "'{}' is {}x{}px with {} bars",
+            rd.title,
+            width,
+            height,
+            rd.bar_data.len()
+        );
+        info!(
+            self.log,
+            "Y axis runs from {} to {} in steps of {}",
+            rd.y_axis_range.0,
+            rd.y_axis_range.1,
+            rd.y_axis_interval
+        );
+
+        for (category, rgb) in rd.categories.iter().zip(rd.category_colors.iter()) {
+            info!(self.log, "Category '{}' is #{:06x}", category, rgb);
+        }
+    }
+
+    /// Warn when the chart is larger than some viewers can reliably display
+    fn check_dimensions(self: &Self, rd: &RenderData, options: &ChartOptions) {
+        let (width, height) = rd.canvas_size();
+
+        if width > options.max_width {
+            self.report_warning(
+                "chart-too-wide",
+                None,
+                format_args!(
+                    "Chart width of {}px exceeds {}px; consider charting fewer items or splitting the chart",
+                    width,
+                    options.max_width
+                ),
+            );
+        }
+
+        if height > options.max_height {
+            self.report_warning(
+                "chart-too-tall",
+                None,
+                format_args!(
+                    "Chart height of {}px exceeds {}px; consider fewer categories or a smaller --font-size",
+                    height,
+                    options.max_height
+                ),
+            );
+        }
+    }
+
+    /// Warn about text that would be cut off at the edges of the chart, or fail with --strict
+    fn check_overflow(
+        self: &Self,
+        rd: &RenderData,
+        options: &ChartOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let clipped = rd.clipped_content();
+
+        if options.strict && !clipped.is_empty() {
+            let messages: Vec<&str> = clipped
+                .iter()
+                .map(|(_, message)| message.as_str())
+                .collect();
+
+            return Err(StackedBarChartError::Render(format!(
+                "Found {} problem(s) with text cut off at the edge of the chart:\n  {}",
+                messages.len(),
+                messages.join("\n  ")
+            ))
+            .into());
+        }
+
+        for (item, message) in clipped.iter() {
+            self.report_warning(
+                "content-clipped",
+                item.as_deref(),
+                format_args!(
+                    "{}; consider a larger --margin or a smaller --font-size",
+                    message
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The y-axis label interval for a range of values, being a twentieth of the next power of
+    /// ten, together with the decimal places needed to show it. The power is found by repeated
+    /// multiplication rather than `log10` and `powf`, whose results vary between platform math
+    /// libraries and would otherwise make the output differ between machines.
+    fn y_axis_interval(range: f64) -> (f64, usize) {
+        let range = if range > 0.0 && range.is_finite() {
+            range
+        } else {
+            1.0
+        };
+        let mut power = 1.0;
+        let mut exponent: i32 = 0;
+
+        while power < range && power <= f64::MAX / 10.0 {
+            power *= 10.0;
+            exponent += 1;
+        }
+
+        while power / 10.0 >= range {
+            power /= 10.0;
+            exponent -= 1;
+        }
+
+        (power / 20.0, (2 - exponent).max(0) as usize)
+    }
+
+    /// Neumaier's variant of Kahan summation, which tracks the low order bits lost in each
+    /// addition so that many values of differing magnitude sum without visible drift.
+    fn compensated_sum(values: &[f64]) -> f64 {
+        let mut sum = 0.0;
+        let mut compensation = 0.0;
+
+        for value in values.iter() {
+            let t = sum + value;
+
+            if f64::abs(sum) >= f64::abs(*value) {
+                compensation += (sum - t) + value;
+            } else {
+                compensation += (value - t) + sum;
+            }
+
+            sum = t;
+        }
+
+        sum + compensation
+    }
+
+    /// The sums of the negative and positive values, which are the extents of a bar that
+    /// stacks them in opposite directions from zero
+    fn signed_sums(values: &[f64]) -> (f64, f64) {
+        let (negative, positive): (Vec<f64>, Vec<f64>) =
+            values.iter().partition(|value| **value < 0.0);
+
+        (
+            Self::compensated_sum(&negative),
+            Self::compensated_sum(&positive),
+        )
+    }
+
+    /// The lowest and highest points reached by any of the stacks in a bar, with negative
+    /// values stacked down from zero in delta mode
+    fn stack_extent(
+        values: &[f64],
+        category_stacks: &[usize],
+        num_stacks: usize,
+        delta: bool,
+    ) -> (f64, f64) {
+        (0..num_stacks)
+            .map(|stack| {
+                let stacked: Vec<f64> = values
+                    .iter()
+                    .zip(category_stacks)
+                    .filter(|(_, category_stack)| **category_stack == stack)
+                    .map(|(value, _)| *value)
+                    .collect();
+
+                if delta {
+                    Self::signed_sums(&stacked)
+                } else {
+                    (0.0, Self::compensated_sum(&stacked))
+                }
+            })
+            .fold((0.0, f64::MIN), |(low, high), (l, h)| {
+                (f64::min(low, l), f64::max(high, h))
+            })
+    }
+
+    fn process_chart_data(self: &Self, spec: &ChartSpec) -> Result<RenderData, Box<dyn Error>> {
+        let options = &spec.options;
+        let mut chart_data = spec.data.sanitized();
+
+        if !options.scale.is_finite() || options.scale == 0.0 {
+            return Err(StackedBarChartError::validation(
+                "Scale must be a number other than zero".to_string(),
+            )
+            .into());
+        }
+
+        chart_data.transform_values(options.transform.as_ref(), options.scale)?;
+        let locale = match dates::Locale::find(&options.locale) {
+            Some(locale) => locale,
+            None => {
+                return Err(StackedBarChartError::Usage(format!(
+                    "Unknown locale '{}', expected one of {}",
+                    options.locale,
+                    dates::Locale::names().join(", ")
+                ))
+                .into())
+            }
+        };
+        // Labels are formatted from the ISO 8601 keys, which for dates parsed in some other
+        // format reproduces the original keys
+        let date_format =
+            options
+                .date_format
+                .clone()
+                .or_else(|| match (options.bucket, &options.key_format) {
+                    (Some(bucket), _) => Some(bucket.date_format().to_string()),
+                    (None, Some(key_format)) => Some(key_format.clone()),
+                    (None, None) => None,
+                });
+
+        if options.key_format.is_some() || options.bucket.is_some() {
+            chart_data.date_items(options.key_format.as_deref(), options.bucket, locale)?;
+        }
+
+        let (include, exclude) = options.get_category_filters()?;
+
+        if !include.is_empty() || !exclude.is_empty() {
+            chart_data.filter_categories(&include, &exclude);
+
+            if chart_data.categories.is_empty() {
+                return Err(StackedBarChartError::Usage(
+                    "No categories are left after filtering".to_string(),
+                )
+                .into());
+            }
+        }
+
+        if let Some(filter) = options.get_key_filter()? {
+            chart_data.items.retain(|item| filter.is_match(&item.key));
+
+            if chart_data.items.is_empty() {
+                return Err(StackedBarChartError::Usage(
+                    "No items have keys matching --filter-key".to_string(),
+                )
+                .into());
+            }
+        }
+
+        if let Some(sort) = &options.sort {
+            chart_data.sort_items(sort, options.descending)?;
+        }
+
+        if let Some(n) = options.top_categories {
+            chart_data.collapse_categories(n);
+        }
+
+        if let Some(n) = options.top {
+            chart_data.collapse_items(n);
+        }
+
+        let cd = &chart_data;
+        let theme = &spec.theme;
+        // The seed picks the hue of the first category, with the rest spaced evenly from it
+        let seed = options.seed.unwrap_or_else(|| cd.category_hash());
+        let start_hue = StdRng::seed_from_u64(seed).gen::<f64>() * 360.0;
+        let background = match options.background.as_deref().map(str::trim) {
+            Some("transparent") | Some("none") => None,
+            Some("") => {
+                return Err(StackedBarChartError::Usage(
+                    "Background color cannot be empty".to_string(),
+                )
+                .into())
+            }
+            Some(color) => Some(color.to_string()),
+            None => Some(theme.background.to_string()),
+        };
+
+        for (name, size) in [("width", options.width), ("height", options.height)] {
+            if size.is_some_and(|size| size.is_nan() || size <= 0.0) {
+                return Err(StackedBarChartError::Usage(format!(
+                    "Chart {} must be greater than zero",
+                    name
+                ))
+                .into());
+            }
+        }
+
+        let font_size = options.font_size;
+        let font_family = &options.font_family;
+        let mut styles = vec![
+            format!(
+                ".labels{{fill:{};font-size:{};font-family:{}}}",
+                theme.foreground, font_size, font_family
+            ),
+            format!(
+                ".title{{fill:{};font-family:{};font-size:{};text-anchor:middle;}}",
+                theme.foreground,
+                font_family,
+                font_size * 1.2
+            ),
+            format!(
+                ".legend{{fill:{};font-family:{};font-size:{}pt;text-anchor:left;}}",
+                theme.foreground,
+                font_family,
+                font_size * 1.2
+            ),
+            format!(
+                ".axis{{fill:none;stroke:{};stroke-width:1;}}",
+                theme.foreground
+            ),
+            ".y-labels{text-anchor:end;}".to_owned(),
+            format!(
+                ".caption{{fill:{};font-family:{};font-size:{};text-anchor:end;}}",
+                theme.foreground,
+                font_family,
+                font_size * 0.9
+            ),
+            format!(".zebra{{fill:{};stroke-width:0}}", theme.zebra),
+        ];
+
+        // Anchors follow the text direction, so right to left the start is the right hand end.
+        // Item labels end at their bars and legend text at its swatch, and y-axis labels start
+        // at the axis.
+        if options.rtl {
+            styles.push(".labels{text-anchor:start;}".to_owned());
+            styles.push(".y-labels{text-anchor:end;}".to_owned());
+            styles.push(".legend{text-anchor:start;}".to_owned());
+        }
+
+        // Item labels end beside horizontal bars, and y-axis labels are centered on their marks
+        if options.orientation == Orientation::Horizontal {
+            styles.push(".labels{text-anchor:end;}".to_owned());
+            styles.push(".y-labels{text-anchor:middle;}".to_owned());
+        }
+
+        if options.replace_css {
+            styles.clear();
+        }
+
+        if let Some(font_face) = options.get_font_face()? {
+            styles.insert(0, font_face);
+        }
+
+        let mut bar_data: Vec<BarData> = vec![];
+        let mut y_axis_range: (f64, f64) = (0.0, f64::MIN);
+        options.check_sizes()?;
+
+        let (bar_width, bar_gap) = options.get_bar_size()?;
+
+        let x_axis_item_width = bar_width + bar_gap;
+
+        // Adjacent 45 degree labels are separated by the slot width times sin(45°)
+        if x_axis_item_width * std::f64::consts::FRAC_1_SQRT_2 < font_size {
+            self.report_warning(
+                "labels-overlap",
+                None,
+                format_args!(
+                    "Bars are {}px apart which is too close for {}px labels to not overlap",
+                    x_axis_item_width, font_size
+                ),
+            );
+        }
+        let mut x_axis_width = 0.0;
+        let visible: Vec<usize> = (0..cd.categories.len())
+            .filter(|i| !cd.categories[*i].hidden)
+            .collect();
+        // The stack of each visible category, numbered in the order they first appear
+        let mut stack_names: Vec<Option<&String>> = vec![];
+        let category_stacks: Vec<usize> = visible
+            .iter()
+            .map(|i| {
+                let name = cd.categories[*i].stack.as_ref();
+
+                stack_names
+                    .iter()
+                    .position(|stack_name| *stack_name == name)
+                    .unwrap_or_else(|| {
+                        stack_names.push(name);
+                        stack_names.len() - 1
+                    })
+            })
+            .collect();
+        let num_stacks = stack_names.len().max(1);
+
+        // Collect the problems with every item so they can all be fixed in one go
+        let mut errors: Vec<(&str, String)> = vec![];
+
+        for tuple in cd.items.iter().enumerate() {
+            let (index, item) = tuple;
+            let num_errors = errors.len();
+
+            if item.values.len() < cd.categories.len() {
+                errors.push((
+                    &item.key,
+                    format!(
+                        "Item {} needs {} values and has {}",
+                        index,
+                        cd.categories.len(),
+                        item.values.len()
+                    ),
+                ));
+                continue;
+            }
+
+            for (j, value) in item.values.iter().enumerate() {
+                if value.is_some_and(|value| !value.is_finite()) {
+                    errors.push((
+                        &item.key,
+                        format!("Item {} value {} is not a finite number", index, j),
+                    ));
+                }
+            }
+
+            let width = x_axis_item_width * item.width.unwrap_or(1.0);
+
+            if width.is_nan() || width <= 0.0 {
+                errors.push((
+                    &item.key,
+                    format!("Item {} width must be greater than zero", index),
+                ));
+            }
+
+            if errors.len() > num_errors {
+                continue;
+            }
+
+            let values: Vec<f64> = visible
+                .iter()
+                .map(|i| item.values[*i].unwrap_or(0.0))
+                .collect();
+            let all_values: Vec<f64> = item.values[..cd.categories.len()]
+                .iter()
+                .map(|value| value.unwrap_or(0.0))
+                .collect();
+            let counted = if options.hidden_in_totals {
+                &all_values[..]
+            } else {
+                &values[..]
+            };
+            let naive_sum: f64 = counted.iter().sum();
+            let sum = Self::compensated_sum(counted);
+
+            if !sum.is_finite() {
+                errors.push((
+                    &item.key,
+                    format!("Item {} values overflow when summed", index),
+                ));
+                continue;
+            }
+
+            if (naive_sum - sum).abs() > sum.abs() * PRECISION_LOSS_TOLERANCE {
+                self.report_warning(
+                    "precision-loss",
+                    Some(&item.key),
+                    format_args!(
+                        "Item {} lost precision summing values ({} vs {}), using compensated sum",
+                        index, naive_sum, sum
+                    ),
+                );
+            }
+
+            if options.delta || num_stacks > 1 {
+                let (low, high) =
+                    Self::stack_extent(&values, &category_stacks, num_stacks, options.delta);
+
+                y_axis_range = (y_axis_range.0.min(low), y_axis_range.1.max(high));
+            } else if sum > y_axis_range.1 {
+                y_axis_range.1 = sum;
+            }
+
+            bar_data.push(BarData {
+                label: match (&date_format, dates::Date::parse(&item.key)) {
+                    (Some(format), Some(date)) => date.format(format, locale),
+                    _ => item.key.to_string(),
+                },
+                values,
+                missing: visible
+                    .iter()
+                    .map(|i| item.values[*i].is_none() && options.missing != MissingValues::Zero)
+                    .collect(),
+                total: sum,
+                x: x_axis_width,
+                width,
+                summary: false,
+                highlighted: options.highlight.contains(&item.key),
+                note: item.note.clone(),
+            });
+            x_axis_width += width;
+        }
+
+        match &errors[..] {
+            [] => (),
+            [(key, reason)] => {
+                return Err(StackedBarChartError::Validation {
+                    item: Some(key.to_string()),
+                    reason: reason.clone(),
+                }
+                .into())
+            }
+            _ => {
+                return Err(StackedBarChartError::Validation {
+                    item: None,
+                    reason: format!(
+                        "Found {} problems in the chart data:\n  {}",
+                        errors.len(),
+                        errors
+                            .iter()
+                            .map(|(_, reason)| reason.as_str())
+                            .collect::<Vec<_>>()
+                            .join("\n  ")
+                    ),
+                }
+                .into())
+            }
+        }
+
+        if let (Some(summary_bar), false) = (options.summary_bar, bar_data.is_empty()) {
+            let divisor = match summary_bar {
+                SummaryBar::Avg => bar_data.len() as f64,
+                SummaryBar::Total => 1.0,
+            };
+            let values: Vec<f64> = (0..visible.len())
+                .map(|j| {
+                    Self::compensated_sum(&bar_data.iter().map(|b| b.values[j]).collect::<Vec<_>>())
+                        / divisor
+                })
+                .collect();
+            let total =
+                Self::compensated_sum(&bar_data.iter().map(|b| b.total).collect::<Vec<_>>())
+                    / divisor;
+
+            if !total.is_finite() {
+                return Err(StackedBarChartError::validation(
+                    "Summary bar values overflow when summed".to_string(),
+                )
+                .into());
+            }
+
+            if options.delta || num_stacks > 1 {
+                let (low, high) =
+                    Self::stack_extent(&values, &category_stacks, num_stacks, options.delta);
+
+                y_axis_range = (y_axis_range.0.min(low), y_axis_range.1.max(high));
+            } else if total > y_axis_range.1 {
+                y_axis_range.1 = total;
+            }
+
+            // Leave a gap to set the summary bar apart from the items
+            x_axis_width += x_axis_item_width / 2.0;
+            bar_data.push(BarData {
+                label: match summary_bar {
+                    SummaryBar::Avg => "Average".to_string(),
+                    SummaryBar::Total => "Total".to_string(),
+                },
+                missing: vec![false; values.len()],
+                values,
+                total,
+                x: x_axis_width,
+                width: x_axis_item_width,
+                summary: true,
+                highlighted: false,
+                note: None,
+            });
+            x_axis_width += x_axis_item_width;
+        }
+
+        if options.rtl {
+            for bar in bar_data.iter_mut() {
+                bar.x = x_axis_width - bar.x - bar.width;
+            }
+        }
+
+        // Placeholders are a small fraction of the range, which is then widened to fit any
+        // bars that they make taller
+        let missing_value = match options.missing {
+            MissingValues::Placeholder => {
+                let range = y_axis_range.1.max(0.0) - y_axis_range.0;
+
+                Some(if range > 0.0 { range * 0.05 } else { 1.0 })
+            }
+            _ => None,
+        };
+
+        let has_placeholders =
+            missing_value.is_some() && bar_data.iter().any(|b| b.missing.contains(&true));
+        let background_rgb = background
+            .as_deref()
+            .and_then(theme::parse_color)
+            .or_else(|| theme::parse_color(theme.background))
+            .unwrap_or(0xffffff);
+
+        if let Some(missing_value) = missing_value {
+            for bar in bar_data.iter().filter(|b| b.missing.contains(&true)) {
+                let values: Vec<f64> = bar
+                    .values
+                    .iter()
+                    .zip(&bar.missing)
+                    .map(|(n, missing)| if *missing { missing_value } else { *n })
+                    .collect();
+                let (low, high) =
+                    Self::stack_extent(&values, &category_stacks, num_stacks, options.delta);
+
+                y_axis_range = (y_axis_range.0.min(low), y_axis_range.1.max(high));
+            }
+        }
+
+        // A fifth of the way from the background to the foreground, so the lines stay faint
+        let gridlines = if options.gridlines {
+            let rgb = theme::mix(
+                background_rgb,
+                theme::parse_color(theme.foreground).unwrap_or(0),
+                0.2,
+            );
+
+            styles.push(format!(".grid{{stroke:#{:06x};stroke-width:1}}", rgb));
+            Some(rgb)
+        } else {
+            None
+        };
+
+        if options.segment_separator > 0.0 {
+            styles.push(format!(
+                ".separator{{stroke:{};stroke-width:{}}}",
+                theme.separator, options.segment_separator
+            ));
+        }
+
+        // A single category is drawn as a plain bar chart, with no legend and values on the bars
+        let single_series = visible.len() == 1 && !options.delta;
+        let single_color = match options.color.as_deref() {
+            Some(_) if !single_series => {
+                return Err(StackedBarChartError::Usage(
+                    "--color can only be used with a single category".to_string(),
+                )
+                .into())
+            }
+            Some(color) => match theme::parse_color(color) {
+                Some(rgb) => Some(rgb),
+                None => {
+                    return Err(StackedBarChartError::Usage(format!(
+                        "Color '{}' must be #rgb, #rrggbb or rgb(r,g,b)",
+                        color
+                    ))
+                    .into())
+                }
+            },
+            None => None,
+        };
+        let mut chosen_colors = vec![];
+
+        for color in options.palette.iter() {
+            match theme::parse_color(color) {
+                Some(rgb) => chosen_colors.push(rgb),
+                None => {
+                    return Err(StackedBarChartError::Usage(format!(
+                        "Palette color '{}' must be #rgb, #rrggbb or rgb(r,g,b)",
+                        color
+                    ))
+                    .into())
+                }
+            }
+        }
+
+        let palette = color::palette(visible.len(), start_hue, theme, background_rgb);
+        let mut category_colors = vec![];
+
+        for (index, rgb) in palette.into_iter().enumerate() {
+            let rgb = single_color
+                .or_else(|| chosen_colors.get(index).copied())
+                .unwrap_or(rgb);
+
+            if options.fill == FillStyle::Color && options.gradient {
+                styles.push(format!(
+                    ".category-{0}{{fill:url(#gradient-{0});stroke-width:0}}",
+                    index
+                ));
+            } else if options.fill == FillStyle::Color {
+                styles.push(format!(
+                    ".category-{}{{fill:#{1:06x};stroke-width:0}}",
+                    index, rgb,
+                ));
+            } else {
+                styles.push(format!(
+                    ".category-{0}{{fill:url(#pattern-{0});stroke-width:0}}",
+                    index
+                ));
+            }
+
+            category_colors.push(rgb);
+        }
+
+        // Pushed after the category rules so that they win for segments with both classes
+        if options.delta {
+            styles.push(format!(
+                ".positive{{fill:{};stroke-width:0}}",
+                theme.positive
+            ));
+            styles.push(format!(
+                ".negative{{fill:{};stroke-width:0}}",
+                theme.negative
+            ));
+        }
+
+        if has_placeholders {
+            styles.push(".missing{fill:url(#missing);stroke-width:0}".to_owned());
+        }
+
+        for key in options.highlight.iter() {
+            if !cd.items.iter().any(|item| item.key == *key) {
+                return Err(StackedBarChartError::validation(format!(
+                    "There is no item '{}' to highlight",
+                    key
+                ))
+                .into());
+            }
+        }
+
+        // Pushed after the category rules so that the outline wins over their zero stroke width
+        if !options.highlight.is_empty() {
+            styles.push(format!(
+                ".highlighted [class^=category-]{{stroke:{};stroke-width:1.5}}",
+                theme.foreground
+            ));
+            styles.push(".dimmed{opacity:0.35}".to_owned());
+        }
+
+        // Each bar is compared with the one before, except for the summary bar
+        let changes: Vec<Option<f64>> = if options.change_labels {
+            styles.push(format!(
+                ".change{{fill:{};font-family:{};font-size:{};text-anchor:middle}}",
+                theme.foreground, font_family, font_size
+            ));
+            styles.push(format!(".increase{{fill:{}}}", theme.positive));
+            styles.push(format!(".decrease{{fill:{}}}", theme.negative));
+
+            bar_data
+                .iter()
+                .enumerate()
+                .map(|(i, bar)| {
+                    let previous = bar_data[..i].last()?;
+
+                    if bar.summary || previous.total == 0.0 {
+                        None
+                    } else {
+                        Some((bar.total - previous.total) / previous.total.abs() * 100.0)
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        if options.animate {
+            for (name, seconds) in [
+                ("duration", options.animation_duration),
+                ("stagger", options.animation_stagger),
+            ] {
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return Err(StackedBarChartError::Usage(format!(
+                        "Animation {} cannot be negative",
+                        name
+                    ))
+                    .into());
+                }
+            }
+
+            styles.push(
+                "@keyframes grow{from{transform:scaleY(0)}to{transform:scaleY(1)}}".to_owned(),
+            );
+            styles.push(format!(
+                ".animated{{animation:grow {}s ease-out both}}",
+                options.animation_duration
+            ));
+            styles.push(
+                "@media (prefers-reduced-motion:reduce){.animated{animation:none}}".to_owned(),
+            );
+        }
+
+        if let Some(css) = options.get_css()? {
+            styles.push(css.trim().to_string());
+        }
+
+        // Rounding the axis out to whole intervals goes past the largest f64 above this
+        if y_axis_range.1 - y_axis_range.0 > MAX_Y_AXIS_RANGE {
+            return Err(StackedBarChartError::validation(format!(
+                "Values span more than {:e} so cannot be drawn on an axis",
+                MAX_Y_AXIS_RANGE
+            ))
+            .into());
+        }
+
+        let (y_axis_interval, y_axis_decimal_places) =
+            Self::y_axis_interval(y_axis_range.1 - y_axis_range.0);
+
+        y_axis_range = (
+            f64::floor(y_axis_range.0 / y_axis_interval) * y_axis_interval,
+            f64::ceil(y_axis_range.1 / y_axis_interval) * y_axis_interval,
+        );
+
+        // Horizontal bars have their labels level with them, with no room for the labels and
+        // notes that go above upright bars
+        let horizontal = options.orientation == Orientation::Horizontal;
+
+        if horizontal {
+            if options.rtl {
+                return Err(StackedBarChartError::Usage(
+                    "--rtl can only be used with vertical bars".to_string(),
+                )
+                .into());
+            }
+
+            if options.change_labels {
+                return Err(StackedBarChartError::Usage(
+                    "--change-labels can only be used with vertical bars".to_string(),
+                )
+                .into());
+            }
+
+            if let Some(bar) = bar_data.iter().find(|bar| bar.note.is_some()) {
+                return Err(StackedBarChartError::validation(format!(
+                    "Item '{}' has a note, which can only be shown on vertical bars",
+                    bar.label
+                ))
+                .into());
+            }
+        }
+
+        let num_y_labels = ((y_axis_range.1 - y_axis_range.0) / y_axis_interval) as usize + 1;
+        let y_label_width = (0..num_y_labels)
+            .map(|i| {
+                text::text_width(
+                    &options.number_format.unwrap_or(NumberFormat::Plain).format(
+                        i as f64 * y_axis_interval + y_axis_range.0,
+                        y_axis_decimal_places,
+                    ),
+                    font_size,
+                )
+            })
+            .fold(0.0, f64::max);
+        // X labels are rotated 45 degrees down from the middle of each bar, so each one
+        // extends its width times sin(45°) below the axis and cos(45°) to the right, or to the
+        // left for right to left charts, and the last few may run past the end of the x axis
+        let x_label_widths: Vec<f64> = bar_data
+            .iter()
+            .map(|bar| {
+                let label = text::truncate(&bar.label, options.max_label_chars);
+
+                text::text_width(label.as_ref().unwrap_or(&bar.label), font_size)
+            })
+            .collect();
+        let x_label_overhang = bar_data
+            .iter()
+            .zip(x_label_widths.iter())
+            .map(|(bar, width)| {
+                let room = if options.rtl {
+                    bar.x + bar.width / 2.0
+                } else {
+                    x_axis_width - bar.x - bar.width / 2.0
+                };
+
+                width * std::f64::consts::FRAC_1_SQRT_2 - room
+            })
+            .fold(0.0, f64::max);
+        let x_label_depth = x_label_widths.iter().cloned().fold(0.0, f64::max)
+            * std::f64::consts::FRAC_1_SQRT_2
+            + font_size * std::f64::consts::FRAC_1_SQRT_2
+            + X_LABEL_OFFSET;
+        let margins = options.get_margins(&cd.margins)?;
+        // Right to left, the y-axis labels are on the right and the item labels hang to the left
+        let y_label_side = f64::max(40.0, (y_label_width + 15.0).ceil());
+        let overhang_side = f64::max(40.0, (x_label_overhang + 5.0).ceil());
+        // Horizontal bars have the item labels on the left, the y-axis labels centered under
+        // the plot, and any value labels just past the end of the longest bar
+        let (left, right, bottom) = if horizontal {
+            let value_label_width = if single_series {
+                bar_data
+                    .iter()
+                    .map(|bar| {
+                        text::text_width(
+                            &options
+                                .number_format
+                                .unwrap_or(NumberFormat::Grouped)
+                                .format(bar.total, y_axis_decimal_places),
+                            font_size,
+                        )
+                    })
+                    .fold(0.0, f64::max)
+            } else {
+                0.0
+            };
+
+            (
+                f64::max(
+                    40.0,
+                    (x_label_widths.iter().cloned().fold(0.0, f64::max) + 15.0).ceil(),
+                ),
+                f64::max(y_label_side / 2.0, (value_label_width + 10.0).ceil()).max(40.0),
+                f64::max(40.0, (font_size + 15.0).ceil()),
+            )
+        } else if options.rtl {
+            (overhang_side, y_label_side, x_label_depth)
+        } else {
+            (y_label_side, overhang_side, x_label_depth)
+        };
+        let left = margins.left.unwrap_or(left);
+        let right = margins.right.unwrap_or(right);
+        let (plot_width, plot_height) = if horizontal {
+            (options.plot_height, x_axis_width)
+        } else {
+            (x_axis_width, options.plot_height)
+        };
+        // Long titles wrap to the width of the chart, pushing the plot area down a line at a time
+        let title_lines = text::wrap(&cd.title, font_size * 1.2, left + plot_width + right - 20.0);
+        let title_line_height = (font_size * 1.2 * 1.2).ceil();
+        let mut gutter = Gutter {
+            top: margins
+                .top
+                .unwrap_or(40.0 + (title_lines.len() - 1) as f64 * title_line_height),
+            bottom: margins.bottom.unwrap_or_else(|| {
+                if horizontal {
+                    bottom
+                } else {
+                    f64::max(40.0, (bottom + 5.0).ceil())
+                }
+            }),
+            left,
+            right,
+        };
+        let legend_rect_size = options.legend_swatch;
+        let side_legend = options.legend == LegendPosition::Right;
+        let mut legend_gutter = Gutter {
+            top: 10.0,
+            bottom: 10.0,
+            left: if options.rtl || side_legend {
+                10.0
+            } else {
+                40.0
+            },
+            right: if options.rtl && !side_legend {
+                40.0
+            } else {
+                10.0
+            },
+        };
+        let categories: Vec<String> = visible
+            .iter()
+            .map(|i| cd.categories[*i].name.clone())
+            .collect();
+        let category_totals: Vec<f64> = (0..visible.len())
+            .map(|j| {
+                Self::compensated_sum(
+                    &bar_data
+                        .iter()
+                        .filter(|b| !b.summary)
+                        .map(|b| b.values[j])
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let mut stack_order: Vec<usize> = (0..visible.len()).collect();
+
+        match &options.stack_order {
+            StackOrder::Input => (),
+            StackOrder::Reverse => stack_order.reverse(),
+            StackOrder::Largest => {
+                stack_order.sort_by(|a, b| category_totals[*b].total_cmp(&category_totals[*a]))
+            }
+            StackOrder::Categories(names) => {
+                let mut positions = vec![];
+
+                for name in names {
+                    match categories.iter().position(|category| category == name) {
+                        Some(j) if positions.contains(&j) => {
+                            return Err(StackedBarChartError::Usage(format!(
+                                "Category '{}' is given more than once in --stack-order",
+                                name
+                            ))
+                            .into())
+                        }
+                        Some(j) => positions.push(j),
+                        None => {
+                            return Err(StackedBarChartError::Usage(format!(
+                                "There is no category '{}' in --stack-order",
+                                name
+                            ))
+                            .into())
+                        }
+                    }
+                }
+
+                stack_order.retain(|j| !positions.contains(j));
+                positions.append(&mut stack_order);
+                stack_order = positions;
+            }
+        }
+
+        // In delta mode the legend has an entry for each sign rather than each category, with
+        // percentages of the total movement in either direction
+        let (legend_labels, legend_classes, legend_totals) = if options.legend
+            == LegendPosition::None
+            || single_series && !options.legend_totals && !options.legend_percent
+        {
+            (vec![], vec![], vec![])
+        } else if options.delta {
+            let signed_totals = bar_data
+                .iter()
+                .filter(|b| !b.summary)
+                .map(|b| Self::signed_sums(&b.values))
+                .fold(
+                    (vec![], vec![]),
+                    |(mut negatives, mut positives), (n, p)| {
+                        negatives.push(n);
+                        positives.push(p);
+                        (negatives, positives)
+                    },
+                );
+
+            (
+                vec!["Positive".to_string(), "Negative".to_string()],
+                vec!["positive".to_string(), "negative".to_string()],
+                vec![
+                    Self::compensated_sum(&signed_totals.1),
+                    Self::compensated_sum(&signed_totals.0),
+                ],
+            )
+        } else {
+            (
+                categories.clone(),
+                (0..categories.len())
+                    .map(|i| format!("category-{}", i))
+                    .collect(),
+                category_totals,
+            )
+        };
+        let grand_total = Self::compensated_sum(
+            &legend_totals
+                .iter()
+                .map(|total| if options.delta { total.abs() } else { *total })
+                .collect::<Vec<_>>(),
+        );
+        let legend_suffixes: Vec<String> = legend_totals
+            .iter()
+            .map(|total| {
+                let total_text = options
+                    .number_format
+                    .unwrap_or(NumberFormat::Grouped)
+                    .format(*total, y_axis_decimal_places);
+                let percent_text = format!(
+                    "{:.0}%",
+                    if grand_total != 0.0 {
+                        total / grand_total * 100.0
+                    } else {
+                        0.0
+                    }
+                );
+
+                match (options.legend_totals, options.legend_percent) {
+                    (true, true) => format!(" ({} — {})", total_text, percent_text),
+                    (true, false) => format!(" ({})", total_text),
+                    (false, true) => format!(" ({})", percent_text),
+                    (false, false) => String::new(),
+                }
+            })
+            .collect();
+
+        let legend_font_size = font_size * 1.2 * 4.0 / 3.0;
+        let entry_widths: Vec<f64> = legend_labels
+            .iter()
+            .zip(legend_suffixes.iter())
+            .map(|(category, suffix)| {
+                let label = text::truncate(category, options.max_label_chars);
+
+                legend_rect_size
+                    + 5.0
+                    + text::text_width(label.as_ref().unwrap_or(category), legend_font_size)
+                    + text::text_width(suffix, legend_font_size)
+            })
+            .collect();
+        // Legend entries flow left to right, wrapping onto a new row when the next entry
+        // would run past the right hand side of the chart, except that a legend on the right
+        // is a column as wide as its widest entry
+        let legend_width = if side_legend {
+            entry_widths.iter().cloned().fold(0.0, f64::max)
+        } else {
+            gutter.left + plot_width + gutter.right - legend_gutter.left_right()
+        };
+        let mut legend_entries = vec![];
+        let (mut x, mut y) = (0.0, 0.0);
+
+        for entry_width in entry_widths {
+            if x > 0.0 && x + entry_width > legend_width {
+                x = 0.0;
+                y += legend_rect_size + 5.0;
+            }
+
+            // Right to left, entries flow from the right with the swatch on the right of the text
+            if options.rtl {
+                legend_entries.push((legend_width - x - legend_rect_size, y));
+            } else {
+                legend_entries.push((x, y));
+            }
+
+            x += entry_width + 15.0;
+        }
+
+        // A legend placed by the template takes no room in the chart
+        let template = options.get_template()?;
+        let legend_height = if legend_labels.is_empty()
+            || template
+                .as_ref()
+                .is_some_and(|template| template::uses(template, "legend"))
+        {
+            legend_gutter.top = 0.0;
+            0.0
+        } else {
+            y + legend_rect_size
+        };
+
+        // A legend at the top pushes the plot down, and one on the right starts level with the
+        // top of the plot and may need more room below it
+        match options.legend {
+            LegendPosition::Top if legend_height > 0.0 => {
+                gutter.top += legend_gutter.top_bottom() + legend_height
+            }
+            LegendPosition::Right => {
+                gutter.bottom = gutter
+                    .bottom
+                    .max(legend_height - plot_height + legend_gutter.bottom)
+            }
+            _ => (),
+        }
+
+        // Leave the same space below the caption as between the legend and the caption
+        let caption_height = if cd.caption.is_some() {
+            font_size * 0.9 + legend_gutter.bottom
+        } else {
+            0.0
+        };
+
+        if single_series {
+            styles.push(format!(
+                ".value{{fill:{};font-family:{};font-size:{};text-anchor:{}}}",
+                theme.foreground,
+                font_family,
+                font_size,
+                if horizontal { "start" } else { "middle" }
+            ));
+        }
+
+        if bar_data.iter().any(|bar| bar.note.is_some()) {
+            styles.push(format!(
+                ".note{{fill:{};font-family:{};font-size:{};text-anchor:middle}}",
+                theme.foreground, font_family, font_size
+            ));
+            styles.push(format!(
+                ".leader{{stroke:{};stroke-width:1}}",
+                theme.foreground
+            ));
+        }
+
+        let side_width = if side_legend && legend_height > 0.0 {
+            legend_gutter.left_right() + legend_width
+        } else {
+            0.0
+        };
+        let mut render_data = RenderData {
+            title: cd.title.to_string(),
+            title_lines,
+            title_line_height,
+            categories,
+            gutter,
+            x_axis_width,
+            bar_fraction: bar_width / x_axis_item_width,
+            category_stacks,
+            stack_order,
+            num_stacks,
+            y_axis_height: options.plot_height,
+            y_axis_interval,
+            y_axis_range,
+            y_axis_decimal_places,
+            number_format: options.number_format,
+            bar_data,
+            legend_gutter,
+            legend_rect_size,
+            legend_labels,
+            legend_classes,
+            legend_entries,
+            legend_suffixes,
+            legend_height,
+            legend_position: options.legend,
+            legend_width: side_width,
+            notes_height: 0.0,
+            caption: cd.caption.clone(),
+            caption_height,
+            metadata: if options.embed_data {
+                Some(serde_json::to_string(cd)?)
+            } else {
+                None
+            },
+            canvas: (options.width, options.height),
+            fragment: options.fragment,
+            responsive: options.responsive,
+            rtl: options.rtl,
+            physical_anchors: false,
+            horizontal,
+            animation_stagger: options.animate.then_some(options.animation_stagger),
+            logo: options.get_logo()?,
+            logo_position: options.logo_position,
+            logo_opacity: options.logo_opacity,
+            logo_size: options.logo_size,
+            template,
+            changes,
+            value_labels: single_series,
+            notes: vec![],
+            legend_rect_corner_radius: 3.0,
+            segment_separator_width: options.segment_separator,
+            segment_corner_radius: options.segment_radius,
+            bar_corner_radius: options.bar_radius,
+            fill: options.fill,
+            category_colors,
+            zebra: options.zebra,
+            gridlines,
+            gradient: options.gradient && options.fill == FillStyle::Color,
+            delta: options.delta,
+            theme: theme.clone(),
+            background,
+            units: cd.units.to_string(),
+            tooltip: (!options.tooltip.is_empty()).then(|| options.tooltip.clone()),
+            max_label_chars: options.max_label_chars,
+            missing_value,
+            font_size,
+            font_family: font_family.clone(),
+            styles,
+        };
+
+        if !horizontal {
+            render_data.place_notes();
+        }
+
+        Ok(render_data)
+    }
+
+    /// Path for a bar segment whose bottom left corner is at `(x, y)`, with its top and bottom
+    /// corners rounded by `top_radius` and `bottom_radius` clamped to fit the segment.
+    fn segment_path(
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        top_radius: f64,
+        bottom_radius: f64,
+    ) -> path::Data {
+        let limit = if top_radius > 0.0 && bottom_radius > 0.0 {
+            height / 2.0
+        } else {
+            height
+        };
+        let rt = top_radius.min(width / 2.0).min(limit).max(0.0);
+        let rb = bottom_radius.min(width / 2.0).min(limit).max(0.0);
+
+        if rt <= 0.0 && rb <= 0.0 {
+            return path::Data::new()
+                .move_to((x, y))
+                .line_by((width, 0.0))
+                .line_by((0.0, -height))
+                .line_by((-width, 0.0))
+                .close();
+        }
+
+        let mut data = path::Data::new()
+            .move_to((x + rb, y))
+            .line_by((width - 2.0 * rb, 0.0));
+
+        if rb > 0.0 {
+            data = data.quadratic_curve_by((rb, 0.0, rb, -rb));
+        }
+
+        data = data.line_by((0.0, -(height - rt - rb)));
+
+        if rt > 0.0 {
+            data = data.quadratic_curve_by((0.0, -rt, -rt, -rt));
+        }
+
+        data = data.line_by((-(width - 2.0 * rt), 0.0));
+
+        if rt > 0.0 {
+            data = data.quadratic_curve_by((-rt, 0.0, -rt, rt));
+        }
+
+        data = data.line_by((0.0, height - rt - rb));
+
+        if rb > 0.0 {
+            data = data.quadratic_curve_by((0.0, rb, rb, rb));
+        }
+
+        data.close()
+    }
+
+    /// Replace the placeholders in a tooltip template with the values for one segment
+    fn expand_tooltip(
+        template: &str,
+        item: &str,
+        category: &str,
+        value: f64,
+        units: &str,
+        total: f64,
+        format_value: impl Fn(f64) -> String,
+    ) -> String {
+        let percent = if total != 0.0 {
+            value / total * 100.0
+        } else {
+            0.0
+        };
+
+        template
+            .replace("{item}", item)
+            .replace("{category}", category)
+            .replace("{value}", &format_value(value))
+            .replace("{units}", units)
+            .replace("{percent}", &format!("{:.0}", percent))
+            .replace("{total}", &format_value(total))
+    }
+
+    /// Text for a label, truncated to `max_chars` with the full label in a hover title, and
+    /// followed by `suffix`
+    fn label_text(label: &str, max_chars: Option<usize>, suffix: &str) -> element::Text {
+        match text::truncate(label, max_chars) {
+            Some(truncated) => element::Text::new(truncated + suffix)
+                .add(element::Title::new(label.to_string() + suffix)),
+            None => element::Text::new(label.to_string() + suffix),
+        }
+    }
+
+    /// Blend a color towards white by `amount` between 0 and 1
+    fn lighten(rgb: u32, amount: f32) -> u32 {
+        let blend = |c: u32| -> u32 { c + ((255 - c) as f32 * amount) as u32 };
+
+        blend(rgb >> 16 & 0xff) << 16 | blend(rgb >> 8 & 0xff) << 8 | blend(rgb & 0xff)
+    }
+
+    /// A vertical gradient for category `index`, lighter at the top of each segment
+    fn category_gradient(index: usize, rgb: u32) -> element::LinearGradient {
+        element::LinearGradient::new()
+            .set("id", format!("gradient-{}", index))
+            .set("x1", 0)
+            .set("y1", 0)
+            .set("x2", 0)
+            .set("y2", 1)
+            .add(
+                element::Stop::new()
+                    .set("offset", "0%")
+                    .set("stop-color", format!("#{:06x}", Self::lighten(rgb, 0.3))),
+            )
+            .add(
+                element::Stop::new()
+                    .set("offset", "100%")
+                    .set("stop-color", format!("#{:06x}", rgb)),
+            )
+    }
+
+    /// A small tile repeating the hatch for category `index`, drawn in the theme foreground
+    /// color or, when combined with color fills, in the theme background over the category color.
+    /// Each time the hatches run out they start again with a larger, sparser tile.
+    fn hatch_pattern(index: usize, fill: FillStyle, rgb: u32, theme: &Theme) -> element::Pattern {
+        let size = 6.0 + 4.0 * (index / HATCHES.len()) as f64;
+        let (background, ink) = match fill {
+            FillStyle::Both => (format!("#{:06x}", rgb), theme.background.to_string()),
+            _ => (theme.background.to_string(), theme.foreground.to_string()),
+        };
+        let lines = |data: path::Data| {
+            element::Path::new()
+                .set("d", data)
+                .set("stroke", ink.as_str())
+                .set("stroke-width", 1)
+        };
+        let mut pattern = element::Pattern::new()
+            .set("id", format!("pattern-{}", index))
+            .set("patternUnits", "userSpaceOnUse")
+            .set("width", size)
+            .set("height", size)
+            .add(
+                element::Rectangle::new()
+                    .set("width", size)
+                    .set("height", size)
+                    .set("fill", background.as_str()),
+            );
+        let diagonal = path::Data::new()
+            .move_to((0.0, size))
+            .line_to((size, 0.0))
+            .move_to((-1.0, 1.0))
+            .line_to((1.0, -1.0))
+            .move_to((size - 1.0, size + 1.0))
+            .line_to((size + 1.0, size - 1.0));
+        let reverse_diagonal = path::Data::new()
+            .move_to((0.0, 0.0))
+            .line_to((size, size))
+            .move_to((-1.0, size - 1.0))
+            .line_to((1.0, size + 1.0))
+            .move_to((size - 1.0, -1.0))
+            .line_to((size + 1.0, 1.0));
+
+        match HATCHES[index % HATCHES.len()] {
+            Hatch::Diagonal => pattern.append(lines(diagonal)),
+            Hatch::ReverseDiagonal => pattern.append(lines(reverse_diagonal)),
+            Hatch::Cross => {
+                pattern.append(lines(diagonal));
+                pattern.append(lines(reverse_diagonal));
+            }
+            Hatch::Dots => pattern.append(
+                element::Circle::new()
+                    .set("cx", size / 2.0)
+                    .set("cy", size / 2.0)
+                    .set("r", 1.2)
+                    .set("fill", ink.as_str()),
+            ),
+            Hatch::Horizontal => pattern.append(lines(
+                path::Data::new()
+                    .move_to((0.0, size / 2.0))
+                    .horizontal_line_by(size),
+            )),
+            Hatch::Vertical => pattern.append(lines(
+                path::Data::new()
+                    .move_to((size / 2.0, 0.0))
+                    .vertical_line_by(size),
+            )),
+        }
+
+        pattern
+    }
+
+    /// A label above each bar with an arrow and the percentage change in its total, colored
+    /// by whether it went up or down
+    fn change_labels(rd: &RenderData) -> element::Group {
+        let mut group = element::Group::new()
+            .set("role", "group")
+            .set("aria-label", "Changes")
+            .add(element::Title::new("Changes"));
+
+        for (bar, change) in rd.bar_data.iter().zip(&rd.changes) {
+            let change = match change {
+                Some(change) => *change,
+                None => continue,
+            };
+            let (text, class) = if change > 0.0 {
+                (
+                    format!("▲ {}%", text::format_number(change, 0)),
+                    "change increase",
+                )
+            } else if change < 0.0 {
+                (
+                    format!("▼ {}%", text::format_number(-change, 0)),
+                    "change decrease",
+                )
+            } else {
+                ("0%".to_string(), "change")
+            };
+            // Above the value label when there is one
+            let top = if rd.value_labels {
+                rd.bar_top(bar) - rd.label_line_height()
+            } else {
+                rd.bar_top(bar)
+            };
+
+            group.append(
+                element::Text::new(text)
+                    .set("class", class)
+                    .set("x", rd.gutter.left + bar.x + bar.width / 2.0)
+                    .set("y", top - 4.0),
+            );
+        }
+
+        group
+    }
+
+    fn value_labels(rd: &RenderData) -> element::Group {
+        let mut group = element::Group::new()
+            .set("role", "group")
+            .set("aria-label", "Values")
+            .add(element::Title::new("Values"));
+
+        for bar in rd.bar_data.iter() {
+            let middle = rd.gutter.left + bar.x + bar.width / 2.0;
+            // Past the end of a horizontal bar, level with the middle of the text
+            let (x, y) = if rd.horizontal {
+                let (x, y) = rd.place(middle, rd.bar_top(bar));
+
+                (x + 4.0, y + rd.font_size / 3.0)
+            } else {
+                (middle, rd.bar_top(bar) - 4.0)
+            };
+
+            group.append(
+                element::Text::new(rd.format_total(bar.total))
+                    .set("class", "value")
+                    .set("x", x)
+                    .set("y", y),
+            );
+        }
+
+        group
+    }
+
+    /// The logo inset from its corner, scaled to fit a box four times as wide as it is high and
+    /// pushed into the corner of the box
+    fn logo_image(rd: &RenderData, logo: &str, width: f64, height: f64) -> element::Image {
+        let (box_width, box_height) = (rd.logo_size * 4.0, rd.logo_size);
+        let inset = 5.0;
+        let (x, y, align) = match rd.logo_position {
+            LogoPosition::TopLeft => (inset, inset, "xMinYMin"),
+            LogoPosition::TopRight => (width - box_width - inset, inset, "xMaxYMin"),
+            LogoPosition::BottomLeft => (inset, height - box_height - inset, "xMinYMax"),
+            LogoPosition::BottomRight => (
+                width - box_width - inset,
+                height - box_height - inset,
+                "xMaxYMax",
+            ),
+        };
+
+        element::Image::new()
+            .set("class", "logo")
+            .set("aria-hidden", "true")
+            .set("href", logo)
+            .set("x", x)
+            .set("y", y)
+            .set("width", box_width)
+            .set("height", box_height)
+            .set("preserveAspectRatio", format!("{} meet", align))
+            .set("opacity", rd.logo_opacity)
+    }
+
+    fn legend(rd: &RenderData) -> element::Group {
+        let mut legend = element::Group::new()
+            .set("role", "group")
+            .set("aria-label", "Legend")
+            .add(element::Title::new("Legend"));
+
+        let (left, top) = rd.legend_origin();
+
+        for i in 0..rd.legend_labels.len() {
+            let x = left + rd.legend_entries[i].0;
+            let y = top + rd.legend_entries[i].1;
+            let block = element::Rectangle::new()
+                .set("class", rd.legend_classes[i].as_str())
+                .set("x", x)
+                .set("y", y)
+                .set("rx", rd.legend_rect_corner_radius)
+                .set("ry", rd.legend_rect_corner_radius)
+                .set("width", rd.legend_rect_size)
+                .set("height", rd.legend_rect_size);
+
+            legend.append(block);
+
+            let text = Self::label_text(
+                &rd.legend_labels[i],
+                rd.max_label_chars,
+                &rd.legend_suffixes[i],
+            )
+            .set("class", "legend")
+            .set("x", rd.legend_text_x(x))
+            .set("y", y + rd.legend_rect_size * 0.75);
+
+            legend.append(text);
+        }
+
+        legend
+    }
+
+    fn note_callouts(rd: &RenderData) -> element::Group {
+        let mut group = element::Group::new()
+            .set("role", "group")
+            .set("aria-label", "Notes")
+            .add(element::Title::new("Notes"));
+
+        for note in rd.notes.iter() {
+            group.append(
+                element::Line::new()
+                    .set("class", "leader")
+                    .set("x1", rd.gutter.left + note.x)
+                    .set("y1", rd.gutter.top + note.y + 3.0)
+                    .set("x2", rd.gutter.left + note.bar_x)
+                    .set("y2", rd.gutter.top + note.bar_y - 2.0),
+            );
+            group.append(
+                element::Text::new(note.text.clone())
+                    .set("class", "note")
+                    .set("x", rd.gutter.left + note.x)
+                    .set("y", rd.gutter.top + note.y),
+            );
+        }
+
+        group
+    }
+
+    /// A sparse diagonal hatch in the theme foreground color for segments with no data, so that
+    /// they don't look like any category
+    fn missing_pattern(theme: &Theme) -> element::Pattern {
+        let size = 4.0;
+
+        element::Pattern::new()
+            .set("id", "missing")
+            .set("patternUnits", "userSpaceOnUse")
+            .set("width", size)
+            .set("height", size)
+            .add(
+                element::Rectangle::new()
+                    .set("width", size)
+                    .set("height", size)
+                    .set("fill", theme.background),
+            )
+            .add(
+                element::Path::new()
+                    .set(
+                        "d",
+                        path::Data::new()
+                            .move_to((0.0, size))
+                            .line_to((size, 0.0))
+                            .move_to((-1.0, 1.0))
+                            .line_to((1.0, -1.0))
+                            .move_to((size - 1.0, size + 1.0))
+                            .line_to((size + 1.0, size - 1.0)),
+                    )
+                    .set("stroke", theme.foreground)
+                    .set("stroke-width", 0.5),
+            )
+    }
+
+    fn accessible_title(rd: &RenderData) -> String {
+        if rd.title.trim().is_empty() {
+            "Stacked bar chart".to_string()
+        } else {
+            rd.title_lines.join(" ")
+        }
+    }
+
+    /// A plain sentence summary of the chart for screen readers
+    fn describe(rd: &RenderData) -> String {
+        let items: Vec<&BarData> = rd.bar_data.iter().filter(|bar| !bar.summary).collect();
+        let mut description = format!("Stacked bar chart of {} item", items.len());
+
+        if items.len() != 1 {
+            description.push('s');
+        }
+
+        if let (Some(first), Some(last)) = (items.first(), items.last()) {
+            if items.len() > 1 {
+                description += &format!(" from {} to {}", first.label, last.label);
+            } else {
+                description += &format!(", {}", first.label);
+            }
+        }
+
+        description += &match rd.categories.len() {
+            0 => format!(" in {}, with no categories shown.", rd.units),
+            1 => format!(
+                " in {}, split into 1 category: {}.",
+                rd.units, rd.categories[0]
+            ),
+            n => format!(
+                " in {}, split into {} categories: {}.",
+                rd.units,
+                n,
+                rd.categories.join(", ")
+            ),
+        };
+
+        let largest = items.iter().max_by(|a, b| a.total.total_cmp(&b.total));
+
+        if let Some(largest) = largest {
+            description += &format!(
+                " The largest total is {} {} for {}.",
+                rd.format_total(largest.total),
+                rd.units,
+                largest.label
+            );
+        }
+
+        description
+    }
+
+    fn render_chart(self: &Self, rd: &RenderData) -> Result<Document, Box<dyn Error>> {
+        let width = rd.width();
+        let height = rd.height();
+        let (canvas_width, canvas_height) = rd.canvas_size();
+        let num_y_labels =
+            ((rd.y_axis_range.1 - rd.y_axis_range.0) / rd.y_axis_interval) as usize + 1;
+        let scale =
+            |n: &f64| -> f64 { n * (rd.y_axis_height / (rd.y_axis_range.1 - rd.y_axis_range.0)) };
+        let mut document = Document::new()
+            .set("xmlns", "http://www.w3.org/2000/svg")
+            .set("width", canvas_width)
+            .set("height", canvas_height)
+            .set("viewBox", format!("0 0 {} {}", width, height));
+
+        if rd.fragment || rd.responsive {
+            let attributes = element::Element::get_attributes_mut(&mut document);
+
+            for name in ["width", "height"] {
+                attributes.remove(name);
+            }
+
+            if rd.fragment {
+                attributes.remove("xmlns");
+            }
+        }
+
+        if rd.responsive {
+            document.assign("preserveAspectRatio", "xMidYMid meet");
+        }
+
+        // Screen readers announce the chart as a single image using its title and description
+        document.assign("role", "img");
+        document.assign("aria-label", Self::accessible_title(rd));
+
+        // Text anchors are relative to the direction, so that start is the right hand end
+        if rd.rtl {
+            document.assign("direction", "rtl");
+        }
+
+        let mut styles = rd.styles.clone();
+
+        // Each anchor that follows the direction is given again as the end it falls at
+        if rd.rtl && rd.physical_anchors {
+            styles.extend(
+                [
+                    ".labels{text-anchor:end;}",
+                    ".y-labels{text-anchor:start;}",
+                    ".legend{text-anchor:end;}",
+                    ".caption{text-anchor:start;}",
+                ]
+                .map(str::to_owned),
+            );
+        }
+
+        let style = element::Style::new(styles.join("\n"));
+        // The axes of horizontal bars are drawn where they end up rather than turned with the
+        // plot, so that the y-axis runs along the bottom
+        let (plot_width, plot_height) = rd.plot_size();
+        let axis = element::Polyline::new()
+            .set("class", "axis")
+            .set("aria-hidden", "true")
+            .set(
+                "points",
+                if rd.horizontal {
+                    vec![
+                        (rd.gutter.left, rd.gutter.top),
+                        (rd.gutter.left, rd.gutter.top + plot_height),
+                        (rd.gutter.left + plot_width, rd.gutter.top + plot_height),
+                    ]
+                } else {
+                    vec![
+                        (rd.y_axis_x(), rd.gutter.top),
+                        (rd.y_axis_x(), rd.gutter.top + rd.y_axis_height),
+                        (
+                            rd.gutter.left + rd.plot_right() - rd.y_axis_x(),
+                            rd.gutter.top + rd.y_axis_height,
+                        ),
+                    ]
+                },
+            );
+        let mut x_axis_labels = element::Group::new()
+            .set("class", "labels")
+            .set("role", "group")
+            .set("aria-label", "Items")
+            .add(element::Title::new("Items"));
+
+        for i in 0..rd.bar_data.len() {
+            let middle = rd.bar_data[i].x + rd.bar_data[i].width / 2.0;
+
+            x_axis_labels.append(
+                Self::label_text(&rd.bar_data[i].label, rd.max_label_chars, "").set(
+                    "transform",
+                    if rd.horizontal {
+                        format!(
+                            "translate({},{})",
+                            rd.gutter.left - 10.0,
+                            rd.gutter.top + middle + rd.font_size / 3.0
+                        )
+                    } else {
+                        format!(
+                            "translate({},{}) rotate({})",
+                            rd.gutter.left + middle,
+                            rd.gutter.top + rd.y_axis_height + X_LABEL_OFFSET,
+                            if rd.rtl { -45 } else { 45 }
+                        )
+                    },
+                ),
+            );
+        }
+
+        let mut y_axis_labels = element::Group::new()
+            .set("class", "labels y-labels")
+            .set("role", "group")
+            .set("aria-label", format!("Values in {}", rd.units))
+            .add(element::Title::new(format!("Values in {}", rd.units)));
+
+        for i in 0..num_y_labels {
+            let n = i as f64 * rd.y_axis_interval;
+
+            let (x, y) = if rd.horizontal {
+                (
+                    rd.gutter.left + f64::floor(scale(&n)),
+                    rd.gutter.top + plot_height + 10.0 + rd.font_size,
+                )
+            } else if rd.rtl {
+                (
+                    rd.y_axis_x() + 10.0,
+                    rd.gutter.top + rd.y_axis_height - f64::floor(scale(&n)) + 5.0,
+                )
+            } else {
+                (
+                    rd.y_axis_x() - 10.0,
+                    rd.gutter.top + rd.y_axis_height - f64::floor(scale(&n)) + 5.0,
+                )
+            };
+
+            y_axis_labels.append(
+                element::Text::new(rd.y_label(n + rd.y_axis_range.0))
+                    .set("transform", format!("translate({},{})", x, y)),
+            );
+        }
+
+        let mut zebra = element::Group::new().set("class", "zebra");
+
+        if rd.zebra {
+            for i in (1..num_y_labels - 1).step_by(2) {
+                let n = (i + 1) as f64 * rd.y_axis_interval;
+
+                zebra.append(
+                    element::Rectangle::new()
+                        .set("x", rd.gutter.left)
+                        .set("y", rd.gutter.top + rd.y_axis_height - scale(&n))
+                        .set("width", rd.x_axis_width)
+                        .set("height", scale(&rd.y_axis_interval)),
+                );
+            }
+        }
+
+        let mut grid = element::Group::new()
+            .set("class", "grid")
+            .set("aria-hidden", "true");
+
+        for i in 1..num_y_labels {
+            let y = rd.gutter.top + rd.y_axis_height
+                - f64::floor(scale(&(i as f64 * rd.y_axis_interval)));
+
+            grid.append(
+                element::Line::new()
+                    .set("x1", rd.gutter.left)
+                    .set("y1", y)
+                    .set("x2", rd.plot_right())
+                    .set("y2", y),
+            );
+        }
+
+        let baseline = rd.baseline();
+        let mut bars = element::Group::new()
+            .set("role", "group")
+            .set("aria-label", "Bars")
+            .add(element::Title::new("Bars"));
+
+        let highlighting = rd.bar_data.iter().any(|bar| bar.highlighted);
+
+        for i in 0..rd.bar_data.len() {
+            let bar_datum = &rd.bar_data[i];
+            let mut bar = element::Group::new();
+            let mut classes = vec![];
+
+            if bar_datum.summary {
+                classes.push("summary");
+            }
+
+            if rd.animation_stagger.is_some() {
+                classes.push("animated");
+            }
+
+            if highlighting {
+                classes.push(if bar_datum.highlighted {
+                    "highlighted"
+                } else {
+                    "dimmed"
+                });
+            }
+
+            if !classes.is_empty() {
+                bar.assign("class", classes.join(" "));
+            }
+
+            if let Some(stagger) = rd.animation_stagger {
+                // Scale from the zero line, which is the baseline of the whole chart
+                bar.assign(
+                    "style",
+                    format!(
+                        "transform-origin:0 {}px;animation-delay:{}s",
+                        baseline,
+                        (i as f64 * stagger * 1000.0).round() / 1000.0
+                    ),
+                );
+            }
+
+            bar.assign("role", "group");
+            bar.assign(
+                "aria-label",
+                format!(
+                    "{}: total {} {}",
+                    bar_datum.label,
+                    rd.format_total(bar_datum.total),
+                    rd.units
+                ),
+            );
+
+            let (segments, separators) = rd.bar_segments(bar_datum);
+
+            for segment in segments {
+                let j = segment.category;
+                let class = match (rd.delta, segment.negative) {
+                    _ if segment.missing => format!("category-{} missing", j),
+                    (false, _) => format!("category-{}", j),
+                    (true, false) => format!("category-{} positive", j),
+                    (true, true) => format!("category-{} negative", j),
+                };
+                let mut path = element::Path::new().set("class", class).set(
+                    "d",
+                    Self::segment_path(
+                        segment.x,
+                        segment.y,
+                        segment.width,
+                        segment.height,
+                        segment.top_radius,
+                        segment.bottom_radius,
+                    ),
+                );
+
+                if let Some(template) = &rd.tooltip {
+                    let category = rd.categories.get(j).map_or("", |c| c.as_str());
+
+                    path.append(element::Title::new(if segment.missing {
+                        format!("{}, {}: no data", bar_datum.label, category)
+                    } else {
+                        Self::expand_tooltip(
+                            template,
+                            &bar_datum.label,
+                            category,
+                            bar_datum.values[j],
+                            &rd.units,
+                            bar_datum.total,
+                            |n| match rd.number_format {
+                                Some(format) => format.format(n, rd.y_axis_decimal_places),
+                                None => n.to_string(),
+                            },
+                        )
+                    }));
+                }
+
+                bar.append(path);
+            }
+
+            if rd.segment_separator_width > 0.0 {
+                for (x, y, width) in separators {
+                    bar.append(
+                        element::Line::new()
+                            .set("class", "separator")
+                            .set("x1", x)
+                            .set("y1", y)
+                            .set("x2", x + width)
+                            .set("y2", y),
+                    );
+                }
+            }
+
+            bars.append(bar);
+        }
+
+        // Center the block of title lines where a single line would go
+        let mut title = element::Text::new(if rd.title_lines.len() > 1 {
+            ""
+        } else {
+            &rd.title
+        })
+        .set("class", "title")
+        .set("x", width / 2.0)
+        .set(
+            "y",
+            rd.title_area() / 2.0 - (rd.title_lines.len() - 1) as f64 * rd.title_line_height / 2.0,
+        );
+
+        if rd.title_lines.len() > 1 {
+            for (i, line) in rd.title_lines.iter().enumerate() {
+                title.append(
+                    element::TSpan::new(line.as_str())
+                        .set("x", width / 2.0)
+                        .set("dy", if i == 0 { 0.0 } else { rd.title_line_height }),
+                );
+            }
+        }
+
+        document.append(element::Title::new(Self::accessible_title(rd)));
+        document.append(element::Description::new().add(Text::new(Self::describe(rd))));
+
+        if let Some(metadata) = &rd.metadata {
+            // The svg crate has no types for these elements, so build them by hand
+            let mut chart_data = element::Element::new(METADATA_ELEMENT);
+            let mut container = element::Element::new("metadata");
+
+            chart_data.assign("xmlns", METADATA_NAMESPACE);
+            chart_data.assign("version", env!("CARGO_PKG_VERSION"));
+            chart_data.append(Text::new(metadata.as_str()));
+            container.append(chart_data);
+            document.append(container);
+        }
+        document.append(style);
+
+        // A rectangle rather than a style so that raster converters draw it too
+        if let Some(background) = &rd.background {
+            document.append(
+                element::Rectangle::new()
+                    .set("class", "background")
+                    .set("width", width)
+                    .set("height", height)
+                    .set("fill", background.as_str()),
+            );
+        }
+
+        if rd.fill != FillStyle::Color || rd.gradient || rd.has_placeholders() {
+            let mut defs = element::Definitions::new();
+
+            for (index, rgb) in rd.category_colors.iter().enumerate() {
+                if rd.gradient {
+                    defs.append(Self::category_gradient(index, *rgb));
+                } else if rd.fill != FillStyle::Color {
+                    defs.append(Self::hatch_pattern(index, rd.fill, *rgb, &rd.theme));
+                }
+            }
+
+            if rd.has_placeholders() {
+                defs.append(Self::missing_pattern(&rd.theme));
+            }
+
+            document.append(defs);
+        }
+
+        let mut zero_line = element::Line::new()
+            .set("class", "axis")
+            .set("aria-hidden", "true")
+            .set("x1", rd.gutter.left)
+            .set("y1", baseline)
+            .set("x2", rd.plot_right())
+            .set("y2", baseline);
+
+        if rd.horizontal {
+            for group in [&mut zebra, &mut grid, &mut bars] {
+                group.assign("transform", rd.plot_transform());
+            }
+
+            zero_line.assign("transform", rd.plot_transform());
+        }
+
+        if rd.zebra {
+            document.append(zebra);
+        }
+
+        if rd.gridlines.is_some() {
+            document.append(grid);
+        }
+
+        document.append(bars);
+        document.append(axis);
+
+        if rd.y_axis_range.0 < 0.0 {
+            document.append(zero_line);
+        }
+
+        if rd.value_labels {
+            document.append(Self::value_labels(rd));
+        }
+
+        if !rd.changes.is_empty() {
+            document.append(Self::change_labels(rd));
+        }
+
+        if !rd.notes.is_empty() {
+            document.append(Self::note_callouts(rd));
+        }
+
+        document.append(x_axis_labels);
+        document.append(y_axis_labels);
+        if !rd.template_uses("title") {
+            document.append(title);
+        }
+
+        if !rd.legend_labels.is_empty() && !rd.template_uses("legend") {
+            document.append(Self::legend(rd));
+        }
+
+        if let Some(caption) = &rd.caption {
+            document.append(
+                element::Text::new(caption.as_str())
+                    .set("class", "caption")
+                    .set("x", rd.caption_x())
+                    .set("y", height - rd.legend_gutter.bottom),
+            );
+        }
+
+        if let Some(logo) = &rd.logo {
+            document.append(Self::logo_image(rd, logo, width, height));
+        }
+
+        Ok(document)
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    struct TestLogger;
+
+    impl TestLogger {
+        fn new() -> TestLogger {
+            TestLogger {}
+        }
+    }
+
+    impl StackedBarChartLog for TestLogger {
+        fn output(self: &Self, _args: Arguments) {}
+        fn warning(self: &Self, _args: Arguments) {}
+        fn error(self: &Self, _args: Arguments) {}
+    }
+
+    fn spec(chart_data: &ChartData, cli: &Cli) -> ChartSpec {
+        cli.get_spec(chart_data.clone()).unwrap()
+    }
+
+    fn test_chart_data() -> ChartData {
+        json5::from_str(
+            r#"{
+                title: "Jobs",
+                units: "count",
+                categories: ["Ready", "Active", { name: "Complete", hidden: false }],
+                items: [
+                    { key: "Jan", values: [10, 0, 0] },
+                    { key: "Feb", values: [8, 1, 2] },
+                ],
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn basic_test() {
+        let logger = TestLogger::new();
+        let mut tool = StackedBarChartTool::new(&logger);
+        let args: Vec<std::ffi::OsString> = vec!["".into(), "--help".into()];
+
+        tool.run(args).unwrap();
+    }
+
+    #[test]
+    fn bad_argument_test() {
+        let logger = TestLogger::new();
+        let mut tool = StackedBarChartTool::new(&logger);
+        let mut run = |args: &[&str]| tool.run(args.iter().map(|arg| arg.into()));
+
+        assert!(run(&["", "--version"]).is_ok());
+        assert!(run(&["", "--not-a-flag"]).is_err());
+        assert!(run(&["", "--fill", "plaid"]).is_err());
+    }
+
+    #[test]
+    fn hatch_pattern_test() {
+        let theme = Theme::find("light").unwrap();
+        let tiles: Vec<String> = (0..HATCHES.len() * 3)
+            .map(|index| {
+                StackedBarChartTool::hatch_pattern(index, FillStyle::Pattern, 0, theme)
+                    .to_string()
+                    .replace(&format!("pattern-{}", index), "")
+            })
+            .collect();
+
+        for (index, tile) in tiles.iter().enumerate() {
+            assert!(!tiles[..index].contains(tile), "{}", index);
+        }
+    }
+
+    #[test]
+    fn separator_radius_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+        let svg = render(&[""]);
+
+        assert!(!svg.contains("separator"));
+        assert!(!svg.contains(" q"));
+
+        // A line across the top of every segment but the last in each bar
+        let svg = render(&["", "--segment-separator", "2"]);
+
+        assert!(svg.contains(".separator{stroke:rgb(255,255,255);stroke-width:2}"));
+        assert_eq!(svg.matches("<line class=\"separator\"").count(), 4);
+        assert!(svg
+            .contains("<line class=\"separator\" x1=\"77.5\" x2=\"92.5\" y1=\"180\" y2=\"180\"/>"));
+
+        // Every corner of every segment is rounded
+        let svg = render(&["", "--segment-radius", "3"]);
+
+        assert!(svg.contains(concat!(
+            "d=\"M80.5,180 l9,0 q3,0,3,-3 l0,-14 q0,-3,-3,-3 l-9,0 q-3,0,-3,3 l0,14 ",
+            "q0,3,3,3 z\""
+        )));
+
+        // Only the top of each bar is rounded
+        let svg = render(&["", "--bar-radius", "4"]);
+
+        assert!(svg.contains("d=\"M77.5,340 l15,0 l0,-160 l-15,0 z\""));
+        assert!(svg.contains("d=\"M77.5,160 l15,0 l0,-36 q0,-4,-4,-4 l-7,0 q-4,0,-4,4 l0,36 z\""));
+    }
+
+    #[test]
+    fn zebra_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+
+        assert!(!render(&[""]).contains("<g class=\"zebra\">"));
+
+        let svg = render(&["", "--zebra"]);
+
+        // Bands go behind the bars, across the plot, in every other interval
+        assert!(svg.contains(".zebra{fill:rgb(242,242,242);stroke-width:0}"));
+        assert!(svg.contains(
+            "<g class=\"zebra\">\n<rect height=\"100\" width=\"60\" x=\"40\" y=\"140\"/>\n</g>"
+        ));
+        assert!(svg.find("class=\"zebra\"").unwrap() < svg.find("aria-label=\"Bars\"").unwrap());
+        assert!(render(&["", "--zebra", "--theme", "dark"])
+            .contains(".zebra{fill:rgb(44,44,44);stroke-width:0}"));
+    }
+
+    #[test]
+    fn gradient_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+
+        assert!(!render(&[""]).contains("linearGradient"));
+
+        let svg = render(&["", "--gradient"]);
+
+        // Each category is filled with its own gradient, from a lighter tint down to its color
+        assert_eq!(svg.matches("<linearGradient").count(), 3);
+        assert!(svg.contains(".category-0{fill:url(#gradient-0);stroke-width:0}"));
+        assert!(svg.contains(concat!(
+            "<linearGradient id=\"gradient-0\" x1=\"0\" x2=\"0\" y1=\"0\" y2=\"1\">\n",
+            "<stop offset=\"0%\" stop-color=\"#d09455\"/>\n",
+            "<stop offset=\"100%\" stop-color=\"#bc670d\"/>\n",
+            "</linearGradient>"
+        )));
+        assert!(!render(&["", "--gradient", "--fill", "pattern"]).contains("linearGradient"));
+    }
+
+    #[test]
+    fn dark_theme_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+        let svg = render(&["", "--theme", "dark"]);
+
+        // No black text or white background is left from the light theme
+        assert!(svg.contains("<rect class=\"background\" fill=\"rgb(30,30,30)\""));
+        assert!(!svg.contains("rgb(0,0,0)") && !svg.contains("rgb(255,255,255)"));
+
+        for class in ["labels", "title", "legend", "caption"] {
+            assert!(svg.contains(&format!(".{}{{fill:rgb(230,230,230);", class)));
+        }
+
+        assert!(svg.contains(".axis{fill:none;stroke:rgb(230,230,230);"));
+        assert!(svg.contains(".category-0{fill:#e79a5d;stroke-width:0}"));
+        assert!(
+            render(&["", "--theme", "light"]).contains(".category-0{fill:#bc670d;stroke-width:0}")
+        );
+
+        let svg = render(&["", "--theme", "dark", "--delta"]);
+
+        assert!(svg.contains(".positive{fill:rgb(92,190,120);stroke-width:0}"));
+        assert!(svg.contains(".negative{fill:rgb(235,100,100);stroke-width:0}"));
+    }
+
+    #[test]
+    fn css_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let css_path = std::env::temp_dir().join("stacked_bar_chart_css_test.css");
+        let render = |args: &[&str]| {
+            let cli = Cli::parse_from([&["", "--css", css_path.to_str().unwrap()], args].concat());
+
+            tool.process_chart_data(&spec(&chart_data, &cli))
+                .map(|render_data| tool.render_chart(&render_data).unwrap().to_string())
+        };
+
+        std::fs::write(&css_path, "\n.labels { fill: teal; }\n\n").unwrap();
+
+        // The rules go after the built-in ones so that they win
+        let svg = render(&[]).unwrap();
+        let style = &svg[svg.find("<style>").unwrap()..svg.find("</style>").unwrap()];
+
+        assert!(style.contains(".labels{fill:rgb(0,0,0);"));
+        assert!(
+            style.ends_with(".category-2{fill:#8b6bc8;stroke-width:0}\n.labels { fill: teal; }\n")
+        );
+
+        std::fs::remove_file(&css_path).unwrap();
+
+        assert!(render(&[]).is_err());
+    }
+
+    #[test]
+    fn replace_css_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let css_path = std::env::temp_dir().join("stacked_bar_chart_replace_css_test.css");
+
+        std::fs::write(&css_path, ".labels { fill: teal; }").unwrap();
+
+        let cli = Cli::parse_from(["", "--css", css_path.to_str().unwrap(), "--replace-css"]);
+        let render_data = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        // Only the category fills, which the chart can't be drawn without, are kept
+        assert_eq!(
+            &svg[svg.find("<style>").unwrap()..svg.find("</style>").unwrap()],
+            concat!(
+                "<style>\n",
+                ".category-0{fill:#bc670d;stroke-width:0}\n",
+                ".category-1{fill:#009580;stroke-width:0}\n",
+                ".category-2{fill:#8b6bc8;stroke-width:0}\n",
+                ".labels { fill: teal; }\n"
+            )
+        );
+        assert!(Cli::try_parse_from(["", "--replace-css"]).is_err());
+    }
+
+    #[test]
+    fn font_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let font_path = std::env::temp_dir().join("stacked_bar_chart_font_test.woff2");
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+
+        let svg = render(&["", "--font-family", "Helvetica", "--font-size", "12"]);
+
+        assert!(svg.contains(".labels{fill:rgb(0,0,0);font-size:12;font-family:Helvetica}"));
+        assert!(svg.contains(
+            ".title{fill:rgb(0,0,0);font-family:Helvetica;font-size:14.399999999999999;"
+        ));
+        assert!(!svg.contains("@font-face"));
+
+        std::fs::write(&font_path, b"font").unwrap();
+
+        // The font is the first style so that the others can use it
+        let svg = render(&[
+            "",
+            "--font-family",
+            "Chart Sans",
+            "--embed-font",
+            font_path.to_str().unwrap(),
+        ]);
+
+        std::fs::remove_file(&font_path).unwrap();
+
+        assert!(svg.contains(concat!(
+            "<style>\n",
+            "@font-face{font-family:\"Chart Sans\";",
+            "src:url(data:font/woff2;base64,Zm9udA==) format(\"woff2\");}\n"
+        )));
+    }
+
+    #[test]
+    fn seeded_colors_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let styles = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap()
+                .styles
+        };
+
+        assert_eq!(styles(&[""]), styles(&[""]));
+        assert_eq!(styles(&["", "--seed", "42"]), styles(&["", "--seed", "42"]));
+        assert_ne!(styles(&["", "--seed", "1"]), styles(&["", "--seed", "2"]));
+    }
+
+    #[test]
+    fn chosen_palette_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let colors = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .map(|render_data| render_data.category_colors)
+        };
+        let generated = colors(&[""]).unwrap();
+        let chosen = colors(&["", "--palette", "#1b9e77,#d95f02"]).unwrap();
+
+        assert_eq!(chosen[..2], [0x1b9e77, 0xd95f02]);
+        assert_eq!(chosen[2], generated[2]);
+        assert!(colors(&["", "--palette", "#1b9e77,teal"]).is_err());
+    }
+
+    #[test]
+    fn palette_test() {
+        for (theme, background) in [(&THEMES[0], 0xffffff), (&THEMES[1], 0x1e1e1e)] {
+            let colors = color::palette(14, 0.0, theme, background);
+            let mut unique = colors.clone();
+
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 14);
+            assert_eq!(colors, color::palette(14, 0.0, theme, background));
+            assert_ne!(colors[0], colors[1]);
+
+            for (i, rgb) in colors.iter().enumerate() {
+                assert!(color::rgb_contrast(*rgb, background) >= color::MIN_CONTRAST);
+
+                if i > 0 {
+                    assert!(color::rgb_delta_e(*rgb, colors[i - 1]) >= color::MIN_DELTA_E);
+                }
+            }
+        }
+
+        // Themes at the ends of the lightness range start every color at black or white, so
+        // neighbors have to move back toward the background to be told apart
+        for (lightness, background) in [(0.0, 0xffffff), (1.0, 0x000000)] {
+            let theme = Theme {
+                lightness,
+                ..THEMES[0].clone()
+            };
+            let colors = color::palette(6, 0.0, &theme, background);
+
+            for (i, rgb) in colors.iter().enumerate() {
+                assert!(color::rgb_contrast(*rgb, background) >= color::MIN_CONTRAST);
+
+                if i > 0 {
+                    assert!(color::rgb_delta_e(*rgb, colors[i - 1]) >= color::MIN_DELTA_E);
+                }
+            }
+        }
+
+        assert!(color::palette(0, 0.0, &THEMES[0], 0xffffff).is_empty());
+        assert_eq!(color::palette(1, 0.0, &THEMES[0], 0xffffff).len(), 1);
+    }
+
+    #[test]
+    fn hidden_category_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.categories[0].hidden = true;
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(render_data.categories, vec!["Active", "Complete"]);
+        assert_eq!(render_data.bar_data[1].values, vec![1.0, 2.0]);
+        assert_eq!(render_data.bar_data[1].total, 3.0);
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--hidden-in-totals"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.bar_data[1].values, vec![1.0, 2.0]);
+        assert_eq!(render_data.bar_data[1].total, 11.0);
+        assert_eq!(render_data.y_axis_range.1, 15.0);
+    }
+
+    #[test]
+    fn automatic_gutter_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(render_data.gutter.left, 40.0);
+        assert_eq!(render_data.gutter.right, 40.0);
+
+        chart_data.items[0].values[0] = Some(123456789.0);
+        chart_data.items[1].key = "A very long item key indeed".to_string();
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert!(render_data.gutter.left > text::text_width("120000000", 10.0));
+        assert!(render_data.gutter.right > 40.0);
+        assert!(render_data.gutter.bottom > 40.0);
+    }
+
+    #[test]
+    fn summary_bar_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--summary-bar", "avg"]),
+            ))
+            .unwrap();
+        let summary = render_data.bar_data.last().unwrap();
+
+        assert!(summary.summary);
+        assert_eq!(summary.values, vec![9.0, 0.5, 1.0]);
+        assert_eq!(summary.total, 10.5);
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--summary-bar", "total"]),
+            ))
+            .unwrap();
+        let summary = render_data.bar_data.last().unwrap();
+
+        assert_eq!(summary.values, vec![18.0, 1.0, 2.0]);
+        assert_eq!(summary.x, 75.0);
+        assert_eq!(render_data.x_axis_width, 105.0);
+    }
+
+    #[test]
+    fn legend_wrapping_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(render_data.legend_entries[0], (0.0, 0.0));
+        assert_eq!(render_data.legend_entries[1], (0.0, 25.0));
+        assert_eq!(render_data.legend_height, 70.0);
+
+        chart_data.items.extend(chart_data.items.clone());
+        chart_data.items.extend(chart_data.items.clone());
+        chart_data.items.extend(chart_data.items.clone());
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(render_data.legend_entries[1].1, 0.0);
+        assert_eq!(render_data.legend_height, 20.0);
+    }
+
+    #[test]
+    fn legend_position_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap()
+        };
+        let bottom = render(&[""]);
+        let top = render(&["", "--legend", "top"]);
+        let right = render(&["", "--legend", "right"]);
+        let none = render(&["", "--legend", "none"]);
+
+        // At the top, the legend is between the title and the plot and the chart is no taller
+        assert_eq!(top.height(), bottom.height());
+        assert!(top.gutter.top > bottom.gutter.top);
+        assert!(top.legend_origin().1 > top.title_area());
+        assert!(top.legend_origin().1 + top.legend_height < top.gutter.top);
+
+        // On the right, the entries are in a column beside the plot, making the chart wider
+        assert!(right.width() > bottom.width());
+        assert!(right.legend_origin().0 >= right.plot_right() + right.gutter.right);
+        assert!(right.legend_entries.iter().all(|(x, _)| *x == 0.0));
+        assert!(right.legend_entries[1].1 > right.legend_entries[0].1);
+
+        assert!(none.legend_labels.is_empty());
+        assert!(none.height() < bottom.height());
+        assert!(!tool
+            .render_chart(&none)
+            .unwrap()
+            .to_string()
+            .contains("<title>Legend</title>"));
+
+        for render_data in [top, right] {
+            assert!(render_data.clipped_content().is_empty());
+            assert_eq!(
+                validate::check_svg(&tool.render_chart(&render_data).unwrap().to_string()),
+                Vec::<String>::new()
+            );
+        }
+    }
+
+    #[test]
+    fn orientation_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render =
+            |args: &[&str]| tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)));
+        let vertical = render(&[""]).unwrap();
+        let render_data = render(&["", "--orientation", "horizontal", "--gridlines"]).unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+        let gutter = &render_data.gutter;
+
+        // The plot turns about the bottom left corner, so the bars run right from the y-axis
+        assert_eq!(
+            render_data.plot_size(),
+            (vertical.y_axis_height, vertical.x_axis_width)
+        );
+        assert_eq!(
+            render_data.place(gutter.left, gutter.top + render_data.y_axis_height),
+            (gutter.left, gutter.top)
+        );
+        assert_eq!(
+            render_data.place(render_data.plot_right(), gutter.top),
+            (
+                gutter.left + render_data.y_axis_height,
+                gutter.top + render_data.x_axis_width
+            )
+        );
+        assert_eq!(
+            render_data.width(),
+            gutter.left + render_data.y_axis_height + gutter.right
+        );
+        assert_eq!(
+            svg.matches(&format!("transform=\"{}\"", render_data.plot_transform()))
+                .count(),
+            2
+        );
+        assert!(render_data.clipped_content().is_empty());
+        assert_eq!(validate::check_svg(&svg), Vec::<String>::new());
+
+        let mut single = test_chart_data();
+
+        single.categories.truncate(1);
+        single
+            .items
+            .iter_mut()
+            .for_each(|item| item.values.truncate(1));
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &single,
+                &Cli::parse_from(["", "--orientation", "horizontal"]),
+            ))
+            .unwrap();
+
+        assert!(render_data
+            .styles
+            .iter()
+            .any(|style| style.contains("text-anchor:start}")));
+        assert!(render_data.clipped_content().is_empty());
+
+        for (args, message) in [
+            (
+                vec!["", "--orientation", "horizontal", "--rtl"],
+                "--rtl can only be used with vertical bars",
+            ),
+            (
+                vec!["", "--orientation", "horizontal", "--change-labels"],
+                "--change-labels can only be used with vertical bars",
+            ),
+        ] {
+            assert_eq!(render(&args).unwrap_err().to_string(), message);
+        }
+
+        assert_eq!(
+            StackedBarChartTool::render_eps(&render(&["", "--orientation", "horizontal"]).unwrap())
+                .unwrap_err()
+                .to_string(),
+            "EPS output can only draw vertical bars"
+        );
+    }
+
+    #[test]
+    fn gridlines_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap()
+        };
+        let plain = render(&[""]);
+        let render_data = render(&["", "--gridlines"]);
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+        let grid = &svg[svg.find("<g aria-hidden=\"true\" class=\"grid\">").unwrap()..];
+        let grid = &grid[..grid.find("</g>").unwrap()];
+
+        assert_eq!(plain.gridlines, None);
+        assert_eq!(
+            render_data.gridlines,
+            Some(theme::mix(0xffffff, 0x000000, 0.2))
+        );
+        assert_eq!(theme::mix(0xffffff, 0x000000, 0.2), 0xcccccc);
+        assert!(svg.contains(".grid{stroke:#cccccc;stroke-width:1}"));
+        assert!(svg.find("class=\"grid\"").unwrap() < svg.find("aria-label=\"Bars\"").unwrap());
+        assert!(grid.matches("<line").count() > 0);
+        assert!(grid.contains(&format!("x2=\"{}\"", render_data.plot_right())));
+        assert!(!tool
+            .render_chart(&plain)
+            .unwrap()
+            .to_string()
+            .contains("class=\"grid\""));
+        assert!(StackedBarChartTool::render_eps(&render_data)
+            .unwrap()
+            .contains("0.800 0.800 0.800 setrgbcolor\n1 setlinewidth\n"));
+        assert_eq!(validate::check_svg(&svg), Vec::<String>::new());
+    }
+
+    #[test]
+    fn legend_totals_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[0].values[0] = Some(1990.0);
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--legend-totals", "--legend-percent"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.legend_suffixes[0], " (1,998 — 100%)");
+        assert_eq!(render_data.legend_suffixes[1], " (1 — 0%)");
+    }
+
+    #[test]
+    fn dimensions_warning_test() {
+        struct WarningLogger(std::cell::RefCell<Vec<String>>);
+
+        impl StackedBarChartLog for WarningLogger {
+            fn output(self: &Self, _args: Arguments) {}
+            fn warning(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(args.to_string());
+            }
+            fn error(self: &Self, _args: Arguments) {}
+        }
+
+        let logger = WarningLogger(Default::default());
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let (width, height) = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap()
+            .canvas_size();
+        let warnings = |args: &[&str]| {
+            logger.0.borrow_mut().clear();
+            tool.render_spec(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+            logger.0.borrow().clone()
+        };
+
+        assert!(warnings(&[""]).is_empty());
+        assert_eq!(
+            warnings(&["", "--max-width", "100"]),
+            [format!(
+                "Chart width of {}px exceeds 100px; consider charting fewer items or splitting the chart",
+                width
+            )]
+        );
+        assert_eq!(
+            warnings(&["", "--max-height", "100"]),
+            [format!(
+                "Chart height of {}px exceeds 100px; consider fewer categories or a smaller --font-size",
+                height
+            )]
+        );
+        assert_eq!(
+            warnings(&[
+                "",
+                "--max-width",
+                &width.to_string(),
+                "--max-height",
+                &height.to_string()
+            ]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn canvas_size_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let svg_tag = |args: &[&str]| {
+            let svg = tool
+                .render_spec(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            svg[..svg.find('>').unwrap() + 1].to_string()
+        };
+        let attributes = |width: &str, height: &str| {
+            format!(
+                concat!(
+                    r#"<svg aria-label="Jobs" height="{}" role="img" viewBox="0 0 140 470" "#,
+                    r#"width="{}" xmlns="http://www.w3.org/2000/svg">"#
+                ),
+                height, width
+            )
+        };
+
+        // The drawing keeps its own coordinates and is scaled to fit the canvas
+        assert_eq!(svg_tag(&[""]), attributes("140", "470"));
+        assert_eq!(svg_tag(&["", "--width", "280"]), attributes("280", "940"));
+        assert_eq!(svg_tag(&["", "--height", "235"]), attributes("70", "235"));
+        assert_eq!(
+            svg_tag(&["", "--width", "300", "--height", "300"]),
+            attributes("300", "300")
+        );
+        assert!(tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from(["", "--width", "0"])))
+            .is_err());
+    }
+
+    #[test]
+    fn delta_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[1].values = vec![Some(8.0), Some(-10.0), Some(2.0)];
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--delta", "--legend-totals"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.y_axis_range, (-10.0, 10.0));
+        assert_eq!(render_data.legend_classes, ["positive", "negative"]);
+        assert_eq!(render_data.legend_suffixes, [" (20)", " (-10)"]);
+        assert!(render_data.tooltip.is_some());
+
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert!(svg.contains(r#"class="category-1 negative" d="M77.5,340 l15,0 l0,-150"#));
+    }
+
+    #[test]
+    fn top_test() {
+        let mut chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Jobs",
+                units: "count",
+                categories: ["Ready", "Active", "Complete", "Failed"],
+                items: [
+                    { key: "Jan", values: [1, 0, 1, 0] },
+                    { key: "Feb", values: [5, 1, null, 0] },
+                    { key: "Mar", values: [2, 2, 1, 1] },
+                    { key: "Apr", values: [3, 0, null, 0] },
+                ],
+            }"#,
+        )
+        .unwrap();
+
+        chart_data.collapse_categories(2);
+
+        let names: Vec<&str> = chart_data
+            .categories
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["Ready", "Active", "Other"]);
+        assert_eq!(
+            chart_data.items[1].values,
+            [Some(5.0), Some(1.0), Some(0.0)]
+        );
+
+        chart_data.collapse_items(2);
+
+        let keys: Vec<&str> = chart_data.items.iter().map(|i| i.key.as_str()).collect();
+
+        assert_eq!(keys, ["Feb", "Mar", "Other"]);
+        assert_eq!(
+            chart_data.items[2].values,
+            [Some(4.0), Some(0.0), Some(1.0)]
+        );
+    }
+
+    #[test]
+    fn sort_test() {
+        let mut chart_data = test_chart_data();
+        let mut sorted = |sort: &str, descending: bool| {
+            chart_data
+                .sort_items(&sort.parse().unwrap(), descending)
+                .unwrap();
+            chart_data
+                .items
+                .iter()
+                .map(|item| item.key.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted("total", true), ["Feb", "Jan"]);
+        assert_eq!(sorted("key", true), ["Jan", "Feb"]);
+        assert_eq!(sorted("category:Ready", false), ["Feb", "Jan"]);
+        assert_eq!(sorted("category:Active", false), ["Jan", "Feb"]);
+        assert!("size".parse::<SortOrder>().is_err());
+        assert!(chart_data
+            .sort_items(&SortOrder::Category("Failed".to_string()), false)
+            .is_err());
+    }
+
+    #[test]
+    fn stack_order_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let process =
+            |args: &[&str]| tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)));
+        let order = |order: &str| {
+            let render_data = process(&["", "--stack-order", order]).unwrap();
+            let (segments, _) = render_data.bar_segments(&render_data.bar_data[1]);
+
+            assert_eq!(render_data.legend_labels, ["Ready", "Active", "Complete"]);
+
+            segments.iter().map(|s| s.category).collect::<Vec<_>>()
+        };
+
+        assert_eq!(order("input"), [0, 1, 2]);
+        assert_eq!(order("reverse"), [2, 1, 0]);
+        assert_eq!(order("largest"), [0, 2, 1]);
+        assert_eq!(order("Complete"), [2, 0, 1]);
+        assert!(process(&["", "--stack-order", "Failed"]).is_err());
+        assert_eq!(
+            process(&["", "--stack-order", "Ready,Ready"])
+                .unwrap_err()
+                .to_string(),
+            "Category 'Ready' is given more than once in --stack-order"
+        );
+    }
+
+    #[test]
+    fn category_filter_test() {
+        assert!(pattern::compile("web-*").unwrap().is_match("web-01"));
+        assert!(!pattern::compile("web-?").unwrap().is_match("web-01"));
+        assert!(!pattern::compile("*.com").unwrap().is_match("example-com"));
+        assert!(pattern::compile("/^(web|db)-/").unwrap().is_match("db-01"));
+        assert!(pattern::compile("/(/").is_err());
+
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let process =
+            |args: &[&str]| tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)));
+        let render_data = process(&["", "--exclude-category", "Ready"]).unwrap();
+
+        assert_eq!(render_data.categories, ["Active", "Complete"]);
+        assert_eq!(render_data.y_axis_range.1, 3.0);
+
+        let render_data = process(&[
+            "",
+            "--include-category",
+            "/e$/",
+            "--include-category",
+            "Act*",
+        ])
+        .unwrap();
+
+        assert_eq!(render_data.categories, ["Active", "Complete"]);
+        assert!(process(&["", "--include-category", "Failed"]).is_err());
+    }
+
+    #[test]
+    fn filter_key_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let process =
+            |args: &[&str]| tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)));
+        let render_data = process(&["", "--filter-key", "^J"]).unwrap();
+
+        assert_eq!(render_data.bar_data.len(), 1);
+        assert_eq!(render_data.bar_data[0].label, "Jan");
+        assert_eq!(render_data.y_axis_range.1, 10.0);
+        assert!(process(&["", "--filter-key", "("]).is_err());
+        assert_eq!(
+            process(&["", "--filter-key", "^Mar"])
+                .unwrap_err()
+                .to_string(),
+            "No items have keys matching --filter-key"
+        );
+    }
+
+    #[test]
+    fn change_labels_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items.push(ItemData {
+            key: "Mar".to_string(),
+            values: vec![Some(5.5), None, None],
+            width: None,
+            note: None,
+        });
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--change-labels", "--summary-bar", "total"]),
+            ))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert_eq!(render_data.changes, [None, Some(10.0), Some(-50.0), None]);
+        assert!(svg.contains("▲ 10%"));
+        assert!(svg.contains(r#"<text class="change decrease" x="115" y="281">"#));
+        assert!(svg.contains("▼ 50%"));
+    }
+
+    #[test]
+    fn notes_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let cli = Cli::parse_from([""]);
+        let plain = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+
+        chart_data.items[0].note = Some("Launch".to_string());
+        chart_data.items[1].note = Some("Outage\u{7}".to_string());
+
+        let render_data = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+        let (launch, outage) = (&render_data.notes[0], &render_data.notes[1]);
+
+        // The bars are next to each other, so the second note goes up a line to clear the first
+        assert_eq!(outage.text, "Outage");
+        assert!(outage.y < launch.y);
+        assert_eq!(render_data.gutter.top, plain.gutter.top);
+        assert!(svg.contains(r#"<text class="note" x="85" y="105">"#));
+        assert!(svg.contains(r#"<text class="note""#));
+        assert!(svg.contains(r#"<line class="leader""#));
+
+        // A negative value outside delta mode still leaves the note pointing into the plot
+        chart_data.items[1].values[1] = Some(-100.0);
+        chart_data.items[0].note = None;
+
+        let render_data = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+        let outage = &render_data.notes[0];
+
+        assert!((0.0..=render_data.y_axis_height).contains(&outage.bar_y));
+        assert!(render_data.gutter.top < plain.gutter.top + 50.0);
+        assert!(render_data.height() < plain.height() + 50.0);
+    }
+
+    #[test]
+    fn single_series_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Sales",
+                units: "USD",
+                categories: ["Sales"],
+                items: [{ key: "Jan", values: [10] }, { key: "Feb", values: [12] }],
+            }"#,
+        )
+        .unwrap();
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--color", "#36c"]),
+            ))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert!(render_data.legend_labels.is_empty());
+        assert_eq!(render_data.height(), 390.0);
+        assert!(svg.contains(".category-0{fill:#3366cc;stroke-width:0}"));
+        assert!(svg.contains("<text class=\"value\" x=\"85\" y=\"96\">\n12\n</text>"));
+        assert!(!svg.contains(r#"aria-label="Legend""#));
+        assert_eq!(
+            tool.process_chart_data(&spec(
+                &test_chart_data(),
+                &Cli::parse_from(["", "--color", "#36c"])
+            ))
+            .unwrap_err()
+            .to_string(),
+            "--color can only be used with a single category"
+        );
+    }
+
+    #[test]
+    fn scale_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.categories[2].scale = Some(10.0);
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--scale", "0.5", "--embed-data"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.bar_data[1].values, [4.0, 0.5, 10.0]);
+        assert!(!render_data.metadata.unwrap().contains("scale"));
+        assert_eq!(
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(["", "--scale", "0"])))
+                .unwrap_err()
+                .to_string(),
+            "Scale must be a number other than zero"
+        );
+    }
+
+    #[test]
+    fn transform_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let transform = |s: &str| s.parse::<Transform>();
+        let mut chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Disk",
+                units: "MiB",
+                categories: ["Used", { name: "Free", transform: "v * 2" }],
+                items: [{ key: "sda", values: [1048576, 4194304] }],
+            }"#,
+        )
+        .unwrap();
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--transform", "v / 1024 / 1024"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.bar_data[0].values, [1.0, 8.0]);
+        assert_eq!(transform("-(v + 1) * 2 - 3e-1").unwrap().apply(2.0), -6.3);
+        assert_eq!(
+            transform("v * (2").unwrap_err(),
+            "Transform 'v * (2' expected ')' but ended"
+        );
+        assert_eq!(
+            transform("v x").unwrap_err(),
+            "Transform 'v x' expected an operator but found 'x' at column 3"
+        );
+
+        chart_data.categories[1].transform = Some(transform("1 / (v - v)").unwrap());
+
+        assert_eq!(
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+                .unwrap_err()
+                .to_string(),
+            "Transforming 4194304 in 'sda' for category 'Free' does not give a number"
+        );
+    }
+
+    #[test]
+    fn rtl_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let render_data = tool
+            .process_chart_data(&spec(&test_chart_data(), &Cli::parse_from(["", "--rtl"])))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+        let (jan, feb) = (&render_data.bar_data[0], &render_data.bar_data[1]);
+
+        assert!(jan.x > feb.x);
+        assert_eq!(
+            render_data.y_axis_x(),
+            render_data.width() - render_data.gutter.right
+        );
+        assert!(svg.contains(r#"direction="rtl""#));
+        assert!(svg.contains(".legend{text-anchor:start;}"));
+        assert!(svg.contains("rotate(-45)"));
+        assert!(!svg.contains(".legend{text-anchor:end;}"));
+
+        // For PNG output the anchors are given again as the ends they fall at, leaving any
+        // text in the chart alone
+        let mut chart_data = test_chart_data();
+
+        chart_data.title = "Jobs (text-anchor:end)".to_string();
+
+        let mut render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from(["", "--rtl"])))
+            .unwrap();
+
+        render_data.physical_anchors = true;
+
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert!(svg.contains(".legend{text-anchor:end;}\n.caption{text-anchor:start;}"));
+        assert!(svg.contains("Jobs (text-anchor:end)"));
+
+        // Item labels hang to the left, so a long one needs room in the left gutter whether it
+        // is the first item, on the right, or the last, on the left
+        for index in [0, 1] {
+            let mut chart_data = test_chart_data();
+
+            chart_data.items[index].key = "The first month of the year in full".to_string();
+
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(["", "--rtl"])))
+                .unwrap();
+            let bar = &render_data.bar_data[index];
+            let reach = text::text_width(&bar.label, render_data.font_size)
+                * std::f64::consts::FRAC_1_SQRT_2;
+
+            assert!(render_data.gutter.left > 40.0);
+            assert!(render_data.gutter.left + bar.x + bar.width / 2.0 >= reach);
+        }
+    }
+
+    #[test]
+    fn logo_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let path = std::env::temp_dir().join("stacked_bar_chart_logo_test.svg");
+
+        std::fs::write(&path, "<svg xmlns='http://www.w3.org/2000/svg'/>").unwrap();
+
+        let cli = Cli::parse_from([
+            "",
+            "--logo",
+            path.to_str().unwrap(),
+            "--logo-position",
+            "bottom-left",
+            "--logo-opacity",
+            "0.3",
+        ]);
+        let render_data = tool
+            .process_chart_data(&spec(&test_chart_data(), &cli))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(svg.contains(r#"href="data:image/svg+xml;base64,PHN2Zy"#));
+        assert!(svg.contains(r#"opacity="0.3" preserveAspectRatio="xMinYMax meet""#));
+        assert!(svg.contains(&format!(r#"y="{}""#, render_data.height() - 37.0)));
+    }
+
+    #[test]
+    fn template_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let path = std::env::temp_dir().join("stacked_bar_chart_template_test.svg");
+        let cli = Cli::parse_from(["", "--template", path.to_str().unwrap()]);
+        let render = |template: &str| {
+            std::fs::write(&path, template).unwrap();
+            tool.render_spec(&spec(&test_chart_data(), &cli))
+        };
+        let svg = render("<svg><text>{{title}}</text>{{chart}}<g>{{legend}}</g></svg>").unwrap();
+
+        assert!(svg.starts_with("<svg><text>Jobs</text><svg "));
+        assert_eq!(svg.matches(r#"aria-label="Legend""#).count(), 1);
+        assert!(!svg.contains(r#"class="title""#));
+        assert!(svg.contains(r#"<g><g transform="translate(-40,-380)">"#));
+        assert_eq!(
+            render("<svg>{{chart}}{{footer}}</svg>")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "Template file '{}' has an unknown placeholder '{{{{footer}}}}', expected one \
+                of chart, title, legend, width, height",
+                path.to_string_lossy()
+            )
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overflow_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let process = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap()
+        };
+
+        assert!(process(&[""]).clipped_content().is_empty());
+        assert_eq!(
+            process(&["", "--margin-left", "5", "--slot-width", "80"]).clipped_content(),
+            [(
+                None,
+                "Y-axis labels run off the side of the chart".to_string()
+            )]
+        );
+        assert_eq!(
+            tool.render_spec(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--margin-left", "5", "--slot-width", "80", "--strict"])
+            ))
+            .unwrap_err()
+            .to_string(),
+            "Found 1 problem(s) with text cut off at the edge of the chart:\n  \
+            Y-axis labels run off the side of the chart"
+        );
+    }
+
+    #[test]
+    fn highlight_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .and_then(|render_data| Ok(tool.render_chart(&render_data)?.to_string()))
+        };
+        let svg = render(&["", "--highlight", "Feb", "--summary-bar", "total"]).unwrap();
+
+        assert!(svg.contains(r#"aria-label="Jan: total 10 count" class="dimmed""#));
+        assert!(svg.contains(r#"aria-label="Feb: total 11 count" class="highlighted""#));
+        assert!(svg.contains(r#"class="summary dimmed""#));
+        assert!(!render(&[""]).unwrap().contains("dimmed"));
+        assert_eq!(
+            render(&["", "--highlight", "Mar"]).unwrap_err().to_string(),
+            "There is no item 'Mar' to highlight"
+        );
+    }
+
+    #[test]
+    fn missing_values_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Jobs",
+                units: "count",
+                categories: ["Ready", "Active", "Complete"],
+                items: [
+                    { key: "Jan", values: [10, 0, 0] },
+                    { key: "Feb", values: [8, null, 2] },
+                ],
+            }"#,
+        )
+        .unwrap();
+        let process = |missing: &str| {
+            tool.process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--missing", missing]),
+            ))
+            .unwrap()
+        };
+
+        let zero = process("zero");
+
+        assert_eq!(zero.bar_data[1].total, 10.0);
+        assert_eq!(zero.bar_segments(&zero.bar_data[1]).0.len(), 3);
+
+        let skip = process("skip");
+
+        assert_eq!(skip.bar_data[1].total, 10.0);
+        assert_eq!(skip.bar_data[1].missing, [false, true, false]);
+        assert_eq!(skip.bar_segments(&skip.bar_data[1]).0.len(), 2);
+        assert!(!tool
+            .render_chart(&skip)
+            .unwrap()
+            .to_string()
+            .contains("Feb, Active"));
+
+        let placeholder = process("placeholder");
+        let (segments, _) = placeholder.bar_segments(&placeholder.bar_data[1]);
+        let svg = tool.render_chart(&placeholder).unwrap().to_string();
+
+        assert_eq!(placeholder.bar_data[1].total, 10.0);
+        assert!(segments[1].missing && segments[1].height > 0.0);
+        assert!(svg.contains(r#"<pattern height="4" id="missing""#));
+        assert!(svg.contains(r#"class="category-1 missing""#));
+        assert!(svg.contains("Feb, Active: no data"));
+    }
+
+    #[test]
+    fn bar_width_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let layout = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            (render_data.bar_data[0].width, render_data.bar_fraction)
+        };
+
+        assert_eq!(layout(&[""]), (30.0, 0.5));
+        assert_eq!(
+            layout(&["", "--bar-width", "6", "--bar-gap", "2"]),
+            (8.0, 0.75)
+        );
+        assert_eq!(layout(&["", "--bar-width", "20%"]), (30.0, 0.2));
+        assert_eq!(layout(&["", "--bar-gap", "10"]), (30.0, 20.0 / 30.0));
+        assert!(tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--bar-width", "40"])
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn redact_test() {
+        let mut chart_data = test_chart_data();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        chart_data.redact(&[Redact::Labels], &mut rng);
+
+        assert_eq!(chart_data.title, "Chart");
+        assert_eq!(chart_data.categories[2].name, "Category 3");
+        assert_eq!(chart_data.items[1].key, "Item 2");
+        assert_eq!(
+            chart_data.items[1].values,
+            [Some(8.0), Some(1.0), Some(2.0)]
+        );
+
+        chart_data.redact(&[Redact::Values], &mut rng);
+
+        let factor = chart_data.items[1].values[0].unwrap() / 8.0;
+
+        assert!((0.5..2.0).contains(&factor));
+        assert_eq!(chart_data.items[0].values[0], Some(10.0 * factor));
+        assert_eq!(chart_data.items[1].values[2], Some(2.0 * factor));
+    }
+
+    #[test]
+    fn responsive_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let svg = tool
+            .render_spec(&spec(
+                &test_chart_data(),
+                &Cli::parse_from(["", "--responsive"]),
+            ))
+            .unwrap();
+
+        // Without a fixed size the chart fills its container, keeping its shape
+        assert!(svg.starts_with(concat!(
+            r#"<svg aria-label="Jobs" preserveAspectRatio="xMidYMid meet" role="img" "#,
+            r#"viewBox="0 0 140 470" xmlns="http://www.w3.org/2000/svg">"#
+        )));
+        assert!(Cli::try_parse_from(["", "--responsive", "--width", "300"]).is_err());
+        assert!(Cli::try_parse_from(["", "--responsive", "--height", "300"]).is_err());
+    }
+
+    #[test]
+    fn multiple_charts_test() {
+        let read = |content: &str| {
+            StackedBarChartTool::read_chart_file(Box::new(io::Cursor::new(content.to_string())))
+        };
+        let chart = "{title: 'A', units: 'u', categories: ['x'], items: [{key: 'k', values: [1]}]}";
+
+        assert_eq!(read(chart).unwrap().len(), 1);
+        assert_eq!(
+            read(&format!("// Two charts\n[{0}, {0}]", chart))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(read("/* none */ []").is_err());
+        assert_eq!(
+            StackedBarChartTool::numbered_path(Path::new("out/sales.svg"), 2),
+            Path::new("out/sales-2.svg")
+        );
+    }
+
+    #[test]
+    fn numbered_output_test() {
+        let logger = TestLogger::new();
+        let mut tool = StackedBarChartTool::new(&logger);
+        let dir = std::env::temp_dir().join("stacked_bar_chart_numbered_output_test");
+        let input = dir.join("sales.json5");
+        let output = dir.join("sales.svg");
+        let chart = |title: &str| {
+            format!(
+                "{{title: '{}', units: 'u', categories: ['x'], items: [{{key: 'k', values: [1]}}]}}",
+                title
+            )
+        };
+        let mut run = |content: String| {
+            std::fs::write(&input, content).unwrap();
+            tool.run(
+                ["", input.to_str().unwrap(), output.to_str().unwrap()]
+                    .iter()
+                    .map(|arg| arg.into()),
+            )
+            .unwrap();
+        };
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        run(format!("[{}, {}]", chart("First"), chart("Second")));
+
+        // Each chart gets its own file, numbered in the order they appear
+        assert!(!output.exists());
+        assert!(std::fs::read_to_string(dir.join("sales-1.svg"))
+            .unwrap()
+            .contains("<title>First</title>"));
+        assert!(std::fs::read_to_string(dir.join("sales-2.svg"))
+            .unwrap()
+            .contains("<title>Second</title>"));
+
+        run(chart("Only"));
+
+        assert!(std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("<title>Only</title>"));
+        assert_eq!(
+            StackedBarChartTool::numbered_path(Path::new("out/sales"), 3),
+            Path::new("out/sales-3")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_test() {
+        let other: ChartData = json5::from_str(
+            r#"{
+                title: "More jobs",
+                units: "count",
+                categories: ["Failed", "Ready"],
+                items: [
+                    { key: "Feb", values: [1, 2] },
+                    { key: "Mar", values: [3, null] },
+                ],
+            }"#,
+        )
+        .unwrap();
+        let mut chart_data = test_chart_data();
+
+        chart_data.merge(other.clone(), MergePolicy::Sum).unwrap();
+
+        let names: Vec<&str> = chart_data
+            .categories
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["Ready", "Active", "Complete", "Failed"]);
+        assert_eq!(chart_data.title, "Jobs");
+        assert_eq!(
+            chart_data.items[0].values,
+            [Some(10.0), Some(0.0), Some(0.0), None]
+        );
+        assert_eq!(
+            chart_data.items[1].values,
+            [Some(10.0), Some(1.0), Some(2.0), Some(1.0)]
+        );
+        assert_eq!(chart_data.items[2].values, [None, None, None, Some(3.0)]);
+
+        let mut chart_data = test_chart_data();
+
+        chart_data
+            .merge(other.clone(), MergePolicy::Concat)
+            .unwrap();
+
+        assert_eq!(chart_data.categories.len(), 5);
+        assert_eq!(
+            chart_data.items[1].values,
+            [Some(8.0), Some(1.0), Some(2.0), Some(1.0), Some(2.0)]
+        );
+
+        let mut chart_data = test_chart_data();
+        let other = ChartData {
+            units: "hours".to_string(),
+            ..other
+        };
+
+        assert_eq!(
+            chart_data
+                .merge(other, MergePolicy::Sum)
+                .unwrap_err()
+                .to_string(),
+            "Charts in 'hours' cannot be merged with charts in 'count'"
+        );
+        assert_eq!(chart_data.categories.len(), 3);
+    }
+
+    #[test]
+    fn batch_test() {
+        let logger = TestLogger::new();
+        let dir = std::env::temp_dir().join("stacked_bar_chart_batch_test");
+        let out_dir = dir.join("out");
+        let chart = "{title: 'A', units: 'u', categories: ['x'], items: [{key: 'k', values: [1]}]}";
+        let run = |pattern: &Path| {
+            StackedBarChartTool::new(&logger).run([
+                "".into(),
+                "--batch".into(),
+                pattern.as_os_str().to_owned(),
+                "--out-dir".into(),
+                out_dir.as_os_str().to_owned(),
+            ])
+        };
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json5"), chart).unwrap();
+        std::fs::write(dir.join("b.json5"), "{title: 'B'}").unwrap();
+        std::fs::write(dir.join("c.json5"), chart).unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let result = run(&dir.join("*.json5"));
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "1 of 3 charts failed to render:\n  {}",
+                dir.join("b.json5").to_string_lossy()
+            )
+        );
+        assert!(out_dir.join("a.svg").exists());
+        assert!(!out_dir.join("b.svg").exists());
+        assert!(out_dir.join("c.svg").exists());
+        assert!(run(&dir.join("*.csv")).is_err());
+        assert!(run(&dir.join("*").join("a.json5")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn watch_test() {
+        struct WatchLogger(std::cell::RefCell<Vec<String>>);
+
+        impl StackedBarChartLog for WatchLogger {
+            fn output(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(args.to_string());
+            }
+            fn warning(self: &Self, _args: Arguments) {}
+            fn error(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(format!("error: {}", args));
+            }
+        }
+
+        let logger = WatchLogger(Default::default());
+        let tool = StackedBarChartTool::new(&logger);
+        let input = std::env::temp_dir().join("stacked_bar_chart_watch_test.json5");
+        let output = std::env::temp_dir().join("stacked_bar_chart_watch_test.svg");
+        let cli = Cli::parse_from([
+            "",
+            "--watch",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+        ]);
+        let mut files = watch::WatchedFiles::new(vec![input.clone()]);
+        let mut render =
+            || tool.render_changed(&cli, OutputFormat::Svg, &mut files, &input, &output);
+        // Set the modified time rather than rely on the clock moving on between writes
+        let write = |content: &str, seconds: u64| {
+            std::fs::write(&input, content).unwrap();
+            File::options()
+                .write(true)
+                .open(&input)
+                .unwrap()
+                .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+                .unwrap();
+        };
+        let chart = |key: &str| {
+            format!(
+                "{{ title: 'x', units: '', categories: ['a'], items: [{{ key: '{}', values: [1] }}] }}",
+                key
+            )
+        };
+
+        write(&chart("Jan"), 1);
+
+        assert!(render());
+        assert!(!render());
+        assert!(std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("Jan, a: 1"));
+
+        write(&chart("Feb"), 2);
+
+        assert!(render());
+        assert!(std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("Feb, a: 1"));
+
+        // A half-finished edit is reported and the next one still renders
+        write("{ title: 'x', units", 3);
+
+        assert!(render());
+        assert!(logger.0.borrow().last().unwrap().starts_with("error: "));
+
+        write(&chart("Mar"), 4);
+
+        assert!(render());
+        assert!(std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("Mar, a: 1"));
+        assert_eq!(
+            logger.0.borrow().last().unwrap(),
+            &format!("Rendered '{}'", output.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn stream_test() {
+        let csv = "key,Ready,\"Active, running\"\nJan,10,0\n\nFeb,8,\n";
+        let chart_data =
+            stream::read_stream(io::Cursor::new(csv), InputFormat::Csv, 10, "jobs").unwrap();
+
+        assert_eq!(chart_data.title, "jobs");
+        assert_eq!(chart_data.categories[1].name, "Active, running");
+        assert_eq!(chart_data.items[1].values, [Some(8.0), None]);
+        assert!(
+            stream::read_stream(io::Cursor::new("key,a\nJan,x\n"), InputFormat::Csv, 10, "")
+                .is_err()
+        );
+
+        let mut ndjson = "{\"title\": \"Jobs\", \"categories\": [\"Ready\"]}\n".to_string();
+
+        for i in 0..1001 {
+            ndjson += &format!("{{\"key\": \"{}\", \"values\": [1]}}\n", i);
+        }
+
+        let chart_data =
+            stream::read_stream(io::Cursor::new(ndjson), InputFormat::Ndjson, 100, "").unwrap();
+        let sizes: Vec<f64> = chart_data
+            .items
+            .iter()
+            .map(|item| item.values[0].unwrap())
+            .collect();
+
+        assert_eq!(chart_data.title, "Jobs");
+        assert!(sizes.len() <= 100);
+        assert_eq!(sizes.iter().sum::<f64>(), 1001.0);
+        assert_eq!(chart_data.items[1].key, sizes[0].to_string());
+    }
+
+    #[test]
+    fn caption_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let cli = Cli::parse_from([""]);
+        let height = tool
+            .process_chart_data(&spec(&chart_data, &cli))
+            .unwrap()
+            .height();
+
+        chart_data.caption = Some("Source: internal telemetry, Q3 2024".to_string());
+
+        let render_data = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert_eq!(render_data.height(), height + 19.0);
+        assert!(svg.contains(r#"class="caption" x="130" y="479""#));
+    }
+
+    #[test]
+    fn stack_groups_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Budget",
+                units: "$",
+                categories: [
+                    { name: "Salary", stack: "income" },
+                    { name: "Rent", stack: "expenses" },
+                    { name: "Bonus", stack: "income" },
+                ],
+                items: [{ key: "Jan", values: [50, 30, 20] }],
+            }"#,
+        )
+        .unwrap();
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert_eq!(render_data.category_stacks, [0, 1, 0]);
+        assert_eq!(render_data.y_axis_range, (0.0, 70.0));
+        assert!(svg.contains(r#"class="category-1" d="M55,340 l7.5,0 l0,-128.57143"#));
+        assert!(svg.contains(r#"class="category-2" d="M47.5,125.71429 l7.5,0 l0,-85.71429"#));
+    }
+
+    #[test]
+    fn truncate_label_test() {
+        assert_eq!(text::truncate("January", Some(4)), Some("Jan…".to_string()));
+        assert_eq!(text::truncate("January", Some(7)), None);
+        assert_eq!(text::truncate("January", None), None);
+    }
+
+    #[test]
+    fn item_width_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data: ChartData = json5::from_str(
+            r#"{
+                title: "Jobs",
+                units: "count",
+                categories: ["Ready", "Active"],
+                items: [
+                    { key: "Jan", values: [1, 2] },
+                    { key: "Total", values: [3, 4], width: 2 },
+                    { key: "Feb", values: [5, 6], width: 0.5 },
+                ],
+            }"#,
+        )
+        .unwrap();
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+        let geometry: Vec<(f64, f64)> = render_data
+            .bar_data
+            .iter()
+            .map(|bar| (bar.x, bar.width))
+            .collect();
+
+        // Each bar advances the axis by its own width, and keeps the same share of it
+        assert_eq!(geometry, [(0.0, 30.0), (30.0, 60.0), (90.0, 15.0)]);
+
+        let (segments, _) = render_data.bar_segments(&render_data.bar_data[1]);
+
+        assert_eq!(segments[0].width, 30.0);
+        assert_eq!(segments[0].x, render_data.gutter.left + 30.0 + 15.0);
+
+        let mut chart_data = chart_data;
+
+        chart_data.items[0].width = Some(0.0);
+
+        assert!(tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .is_err());
+    }
+
+    #[test]
+    fn title_wrap_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        assert_eq!(
+            text::wrap("Jobs  by month", 12.0, 50.0),
+            ["Jobs by", "month"]
+        );
+        assert_eq!(
+            text::wrap("Jobs  by month", 12.0, 500.0),
+            ["Jobs  by month"]
+        );
+
+        chart_data.title = "Jobs completed across the regional processing centers".to_string();
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(render_data.title_lines.len(), 3);
+        assert_eq!(render_data.gutter.top, 70.0);
+    }
+
+    #[test]
+    fn date_format_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let french = dates::Locale::find("fr-CA").unwrap();
+
+        assert_eq!(dates::Date::parse("2024-02-30"), None);
+        assert_eq!(dates::Date::parse("Jan"), None);
+        assert_eq!(
+            dates::Date::parse("2024-02-29")
+                .unwrap()
+                .format("%A %e %B %Y, %a %d/%m/%y %%", french),
+            "jeudi 29 février 2024, jeu. 29/02/24 %"
+        );
+
+        chart_data.items[0].key = "2024-01".to_string();
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--date-format", "%b %Y", "--locale", "de"]),
+            ))
+            .unwrap();
+
+        assert_eq!(render_data.bar_data[0].label, "Jan 2024");
+        assert_eq!(render_data.bar_data[1].label, "Feb");
+        assert!(tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from(["", "--locale", "xx"])))
+            .is_err());
+    }
+
+    #[test]
+    fn date_axis_test() {
+        let english = dates::Locale::find("en").unwrap();
+        let date = |text: &str, format: &str| dates::Date::parse_format(text, format, english);
+
+        assert_eq!(date("5/3/24", "%d/%m/%y"), dates::Date::parse("2024-03-05"));
+        assert_eq!(date("June 2024", "%B %Y"), dates::Date::parse("2024-06"));
+        assert_eq!(date("Jun 2024", "%b %Y"), dates::Date::parse("2024-06"));
+        assert_eq!(date("31/02/2024", "%d/%m/%Y"), None);
+        assert_eq!(date("2024-03-05x", "%Y-%m-%d"), None);
+
+        let sunday = dates::Date::parse("2024-03-03").unwrap();
+
+        assert_eq!(sunday.bucket(Bucket::Week).iso(), "2024-02-26");
+        assert_eq!(sunday.bucket(Bucket::Quarter).iso(), "2024-01");
+
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[0].key = "14/03/2024".to_string();
+        chart_data.items[1].key = "02/03/2024".to_string();
+
+        let process =
+            |args: &[&str]| tool.process_chart_data(&spec(&chart_data, &Cli::parse_from(args)));
+        let render_data = process(&["", "--key-format", "%d/%m/%Y"]).unwrap();
+        let labels: Vec<&str> = render_data
+            .bar_data
+            .iter()
+            .map(|b| b.label.as_str())
+            .collect();
+
+        assert_eq!(labels, ["02/03/2024", "14/03/2024"]);
+
+        let render_data =
+            process(&["", "--key-format", "%d/%m/%Y", "--bucket", "quarter"]).unwrap();
+
+        assert_eq!(render_data.bar_data.len(), 1);
+        assert_eq!(render_data.bar_data[0].label, "Q1 2024");
+        assert_eq!(render_data.bar_data[0].values, [18.0, 1.0, 2.0]);
+        assert!(process(&["", "--bucket", "month"]).is_err());
+    }
+
+    #[test]
+    fn multiple_errors_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let cli = Cli::parse_from([""]);
+
+        chart_data.items[0].values.pop();
+        chart_data.items[1].values[2] = Some(f64::NAN);
+        chart_data.items[1].width = Some(0.0);
+
+        assert_eq!(
+            tool.process_chart_data(&spec(&chart_data, &cli))
+                .unwrap_err()
+                .to_string(),
+            "Found 3 problems in the chart data:\n  \
+            Item 0 needs 3 values and has 2\n  \
+            Item 1 value 2 is not a finite number\n  \
+            Item 1 width must be greater than zero"
+        );
+
+        chart_data.items.truncate(1);
+
+        assert_eq!(
+            tool.process_chart_data(&spec(&chart_data, &cli))
+                .unwrap_err()
+                .to_string(),
+            "Item 0 needs 3 values and has 2"
+        );
+    }
+
+    #[test]
+    fn animate_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            tool.render_spec(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap()
+        };
+
+        assert!(!render(&[""]).contains("animat"));
+
+        let svg = render(&[
+            "",
+            "--animate",
+            "--animation-duration",
+            "1.5",
+            "--animation-stagger",
+            "0.25",
+        ]);
+
+        assert!(svg.contains(concat!(
+            "@keyframes grow{from{transform:scaleY(0)}to{transform:scaleY(1)}}\n",
+            ".animated{animation:grow 1.5s ease-out both}\n",
+            "@media (prefers-reduced-motion:reduce){.animated{animation:none}}\n"
+        )));
+
+        // Each bar grows up from the baseline, one after another
+        assert!(svg.contains(concat!(
+            r#"<g aria-label="Jan: total 10 count" class="animated" role="group" "#,
+            r#"style="transform-origin:0 340px;animation-delay:0s">"#
+        )));
+        assert!(svg.contains(concat!(
+            r#"<g aria-label="Feb: total 11 count" class="animated" role="group" "#,
+            r#"style="transform-origin:0 340px;animation-delay:0.25s">"#
+        )));
+        assert!(Cli::try_parse_from(["", "--animation-stagger", "0.25"]).is_err());
+    }
+
+    #[test]
+    fn format_number_test() {
+        assert_eq!(text::format_number(1240.0, 0), "1,240");
+        assert_eq!(text::format_number(-1234567.891, 2), "-1,234,567.89");
+        assert_eq!(text::format_number(999.0, 1), "999.0");
+        assert_eq!(text::format_number(-0.001, 1), "0.0");
+    }
+
+    #[test]
+    fn number_format_test() {
+        assert_eq!(NumberFormat::Plain.format(1234567.25, 1), "1234567.2");
+        assert_eq!(NumberFormat::Grouped.format(1234567.25, 1), "1,234,567.2");
+        assert_eq!(NumberFormat::Compact.format(1234567.25, 1), "1.2M");
+        assert_eq!(text::format_compact(-2500.0, 0), "-2.5k");
+        assert_eq!(text::format_compact(3e9, 0), "3B");
+        assert_eq!(text::format_compact(999.0, 1), "999.0");
+        assert_eq!(text::format_compact(999.96, 1), "1k");
+        assert_eq!(text::format_compact(999_940.0, 0), "999.9k");
+        assert_eq!(text::format_compact(999_950.0, 0), "1M");
+        assert_eq!(text::format_compact(-999_950_000.0, 0), "-1B");
+        assert_eq!(text::format_compact(2e15, 0), "2000T");
+
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        for item in chart_data.items.iter_mut() {
+            for value in item.values.iter_mut() {
+                *value = value.map(|v| v * 1000.0);
+            }
+        }
+
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+        let plain = render(&["", "--legend-totals", "--tooltip", "{total}"]);
+        let compact = render(&[
+            "",
+            "--legend-totals",
+            "--tooltip",
+            "{total}",
+            "--number-format",
+            "compact",
+        ]);
+
+        assert!(plain.contains("\n15000\n"));
+        assert!(plain.contains("Ready (18,000)"));
+        assert!(plain.contains("<title>11000</title>"));
+        assert!(compact.contains("\n15k\n"));
+        assert!(compact.contains("Ready (18k)"));
+        assert!(compact.contains("<title>11k</title>"));
+    }
+
+    #[test]
+    fn margins_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+        let gutter = |chart_data: &ChartData, args: &[&str]| {
+            let gutter = tool
+                .process_chart_data(&spec(chart_data, &Cli::parse_from(args)))
+                .unwrap()
+                .gutter;
+
+            (gutter.top, gutter.right, gutter.bottom, gutter.left)
+        };
+
+        assert_eq!(
+            gutter(&chart_data, &["", "--margin", "5"]),
+            (5.0, 5.0, 5.0, 5.0)
+        );
+        assert_eq!(
+            gutter(
+                &chart_data,
+                &["", "--margin", "1,2,3", "--margin-left", "9"]
+            ),
+            (1.0, 2.0, 3.0, 9.0)
+        );
+
+        chart_data.margins.top = Some(7.0);
+
+        assert_eq!(gutter(&chart_data, &[""]), (7.0, 40.0, 40.0, 40.0));
+        assert_eq!(
+            gutter(&chart_data, &["", "--margin", "5,6"]),
+            (5.0, 6.0, 5.0, 6.0)
+        );
+        assert!(tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--margin", "1,2,3,4,5"])
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn fragment_test() {
+        let logger = TestLogger::new();
+        let mut tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let svg = tool
+            .render_spec(&spec(&chart_data, &Cli::parse_from(["", "--fragment"])))
+            .unwrap();
+
+        // A fragment takes its size and namespace from the page it's put in
+        assert!(svg.starts_with(r#"<svg aria-label="Jobs" role="img" viewBox="0 0 140 470">"#));
+        assert!(svg.ends_with("</svg>"));
+
+        let input = std::env::temp_dir().join("stacked_bar_chart_fragment_test.json5");
+        let output = std::env::temp_dir().join("stacked_bar_chart_fragment_test.svg");
+
+        std::fs::write(&input, serde_json::to_string(&chart_data).unwrap()).unwrap();
+        tool.run(
+            [
+                "",
+                "--inner-only",
+                input.to_str().unwrap(),
+                output.to_str().unwrap(),
+            ]
+            .iter()
+            .map(|arg| arg.into()),
+        )
+        .unwrap();
+
+        let inner = std::fs::read_to_string(&output).unwrap();
+
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        // Only the contents are written, for putting inside an <svg> element of the page's own
+        assert!(inner.starts_with("<title>Jobs</title>\n"));
+        assert!(inner.ends_with("</g>\n"));
+        assert!(!inner.contains("<svg"));
+        assert!(!inner.contains("</svg>"));
+    }
+
+    #[test]
+    fn tooltip_template_test() {
+        assert_eq!(
+            StackedBarChartTool::expand_tooltip(
+                "{item}: {value} {units} ({percent}% of {total}) {category}",
+                "Feb",
+                "Active",
+                1.0,
+                "count",
+                4.0,
+                |n| n.to_string()
+            ),
+            "Feb: 1 count (25% of 4) Active"
+        );
+
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = |args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+
+        assert!(render(&[""]).contains("Feb, Complete: 2 count (18%)"));
+        assert!(!render(&["", "--tooltip", ""]).contains("Feb, Complete"));
+    }
+
+    #[test]
+    fn y_axis_interval_test() {
+        assert_eq!(StackedBarChartTool::y_axis_interval(1000.0), (50.0, 0));
+        assert_eq!(StackedBarChartTool::y_axis_interval(1001.0), (500.0, 0));
+        assert_eq!(StackedBarChartTool::y_axis_interval(15.0), (5.0, 0));
+        assert_eq!(StackedBarChartTool::y_axis_interval(10.0), (0.5, 1));
+        assert_eq!(StackedBarChartTool::y_axis_interval(0.3), (0.05, 2));
+        assert_eq!(StackedBarChartTool::y_axis_interval(0.0), (0.05, 2));
+    }
+
+    #[test]
+    fn reproducible_output_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let chart_data = test_chart_data();
+        let render = || {
+            let cli = Cli::parse_from(["", "--seed", "1"]);
+            let render_data = tool.process_chart_data(&spec(&chart_data, &cli)).unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+        let output = render();
+
+        assert_eq!(output, render());
+        assert!(output.contains(".category-0{fill:#bc598b;stroke-width:0}"));
+        assert!(output.contains("viewBox=\"0 0 140 470\""));
+
+        let dir = std::env::temp_dir();
+        let input = dir.join("stacked_bar_chart_reproducible_output_test.json5");
+        let output = dir.join("stacked_bar_chart_reproducible_output_test.svg");
+        let run = || {
+            StackedBarChartTool::new(&logger)
+                .run(
+                    [
+                        "",
+                        "--deterministic",
+                        "--redact",
+                        "values",
+                        input.to_str().unwrap(),
+                        output.to_str().unwrap(),
+                    ]
+                    .iter()
+                    .map(|arg| arg.into()),
+                )
+                .unwrap();
+            std::fs::read_to_string(&output).unwrap()
+        };
+
+        std::fs::write(
+            &input,
+            "{title: 'A', units: 'u', categories: ['x'], \
+            items: [{key: 'k', values: [0.1]}, {key: 'l', values: [0.2]}]}",
+        )
+        .unwrap();
+
+        let first = run();
+
+        assert_eq!(first, run());
+        assert!(first.contains("\n<path class=\"category-0\" d=\"M"));
+        assert!(!first.contains("0000"));
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn conformance_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[1].values[1] = Some(-4.0);
+
+        for args in [
+            vec![""],
+            vec![
+                "",
+                "--summary-bar",
+                "avg",
+                "--zebra",
+                "--segment-separator",
+                "1",
+            ],
+            vec![
+                "",
+                "--segment-radius",
+                "4",
+                "--gradient",
+                "--tooltip",
+                "{item}",
+            ],
+            vec!["", "--bar-radius", "6", "--delta", "--segment-radius", "2"],
+            vec!["", "--fill", "both", "--theme", "dark", "--fragment"],
+            vec!["", "--background", "transparent", "--inner-only"],
+            vec!["", "--responsive"],
+            vec!["", "--animate", "--animation-stagger", "0.25"],
+            vec!["", "--delta", "--legend-totals", "--legend-percent"],
+            vec!["", "--bar-width", "100%", "--width", "600"],
+        ] {
+            let render_data = tool
+                .process_chart_data(&spec(&chart_data, &Cli::parse_from(&args)))
+                .unwrap();
+            let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+            assert_eq!(
+                validate::check_svg(&svg),
+                Vec::<String>::new(),
+                "{:?}",
+                args
+            );
+        }
+
+        assert_eq!(
+            validate::check_svg(
+                r#"<svg viewBox="0 0 10 0"><rect width="-1" height="2"/><path d="M0,NaN"/><g></svg>"#
+            ),
+            [
+                "<svg viewBox>: '0 0 10 0' is not a box with a positive size",
+                "<rect width>: -1 cannot be negative",
+                "<path d>: 'M0,NaN' is not valid path data",
+                "Closing tag </svg> does not match <g>",
+                "Tag <svg> is never closed",
+            ]
+        );
+    }
+
+    #[test]
+    fn gallery_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let html = tool.render_gallery(Path::new("example/gallery")).unwrap();
+
+        assert_eq!(html.matches("<section>").count(), 5);
+        assert_eq!(html.matches("data:image/svg+xml").count(), 5 * THEMES.len());
+        assert!(!html.contains("class=\"error\""));
+    }
+
+    #[test]
+    fn html_output_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.title = "Jobs <2024>".to_string();
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--format", "html"]),
+            ))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+        let page = html::page(&render_data.title, &svg, true);
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<title>Jobs &lt;2024&gt;</title>"));
+        assert_eq!(page.matches("<svg").count(), 1);
+        assert!(page.contains("<script>"));
+        assert!(!html::page(&render_data.title, &svg, false).contains("<script>"));
+    }
+
+    #[test]
+    fn compensated_sum_test() {
+        assert_eq!(
+            StackedBarChartTool::compensated_sum(&[1e16, 1.0, -1e16]),
+            1.0
+        );
+        assert_eq!(
+            StackedBarChartTool::compensated_sum(&[1e100, 1.0, -1e100, 1.0]),
+            2.0
+        );
+        assert_eq!(StackedBarChartTool::compensated_sum(&[0.1; 10]), 1.0);
+        assert_eq!(
+            StackedBarChartTool::compensated_sum(&vec![1_000_000.01; 100_000]),
+            100_000_001_000.0
+        );
+    }
+
+    #[test]
+    fn large_magnitude_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[0].values = vec![Some(9007199254740992.0), Some(1.0), Some(1.0)];
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert!(render_data.y_axis_range.1 >= 9007199254740994.0);
+
+        chart_data.items[0].values = vec![Some(f64::MAX), Some(f64::MAX), Some(0.0)];
+
+        assert!(tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .is_err());
+
+        chart_data.items[0].values = vec![Some(1.5e308), Some(0.0), Some(0.0)];
+
+        assert_eq!(
+            tool.process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+                .unwrap_err()
+                .to_string(),
+            "Values span more than 1e307 so cannot be drawn on an axis"
+        );
+        assert!(StackedBarChartTool::y_axis_interval(1.5e308).0.is_finite());
+    }
+
+    #[test]
+    fn options_builder_test() {
+        let tool = StackedBarChartTool::new(&TestLogger {});
+        let options = ChartOptions::builder()
+            .plot_height(200.0)
+            .slot_width(40)
+            .bar_width(Length::Percent(25.0))
+            .legend_swatch(12.0)
+            .exclude_category(["Inactive"])
+            .palette(["#1b9e77"])
+            .number_format(NumberFormat::Compact)
+            .build()
+            .unwrap();
+        let render_data = tool
+            .process_chart_data(&ChartSpec::new(test_chart_data(), options.clone()).unwrap())
+            .unwrap();
+
+        assert_eq!(render_data.y_axis_height, 200.0);
+        assert_eq!(render_data.bar_data[0].width, 40.0);
+        assert_eq!(render_data.bar_fraction, 0.25);
+        assert_eq!(render_data.legend_rect_size, 12.0);
+        assert_eq!(render_data.category_colors[0], 0x1b9e77);
+        assert_eq!(render_data.number_format, Some(NumberFormat::Compact));
+        assert_eq!(
+            serde_json::to_string(&options).unwrap(),
+            serde_json::to_string(
+                &Cli::parse_from([
+                    "",
+                    "--plot-height",
+                    "200",
+                    "--slot-width",
+                    "40",
+                    "--bar-width",
+                    "25%",
+                    "--legend-swatch",
+                    "12",
+                    "--exclude-category",
+                    "Inactive",
+                    "--palette",
+                    "#1b9e77",
+                    "--number-format",
+                    "compact",
+                ])
+                .options
+            )
+            .unwrap()
+        );
+        assert!(ChartOptions::builder().slot_width(0).build().is_err());
+        assert!(ChartOptions::builder()
+            .include_category(["/(/"])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn builtin_defaults_test() {
+        assert_eq!(
+            serde_json::to_value(ChartOptions::builtin_defaults()).unwrap(),
+            serde_json::to_value(Cli::parse_from([""]).options).unwrap()
+        );
+    }
+
+    #[test]
+    fn file_options_test() {
+        let chart_data: ChartData = json5::from_str(
+            r#"{ title: "Jobs", units: "count", categories: ["Ready"], items: [],
+            options: { plot_height: 100, font_size: 12, bar_width: "40%", sort: "total" } }"#,
+        )
+        .unwrap();
+        let parse = |args: &[&str]| Cli::parse_args(args.iter().map(|arg| arg.into())).unwrap();
+        let options = parse(&["", "--plot-height", "200", "--font-size", "10"])
+            .get_spec(chart_data.clone())
+            .unwrap()
+            .options;
+
+        assert_eq!(options.plot_height, 200.0);
+        assert_eq!(options.font_size, 10.0);
+        assert_eq!(options.bar_width, Some(Length::Percent(40.0)));
+        assert_eq!(options.sort, Some(SortOrder::Total));
+
+        let spec = parse(&["", "--title", "Jobs (staging)", "--units", "jobs"])
+            .get_spec(chart_data)
+            .unwrap();
+
+        assert_eq!(spec.data.title, "Jobs (staging)");
+        assert_eq!(spec.data.units, "jobs");
+        assert_eq!(spec.options.plot_height, 100.0);
+        assert_eq!(spec.options.font_size, 12.0);
+        assert!(spec.data.options.is_none());
+
+        // The theme, palette and legend can all be kept with the chart too
+        let chart_data: ChartData = json5::from_str(
+            r##"{ title: "Jobs", units: "count", categories: ["Ready", "Active"],
+            items: [{ key: "Jan", values: [1, 2] }],
+            options: { theme: "dark", palette: ["#1b9e77", "#d95f02"], legend: "right" } }"##,
+        )
+        .unwrap();
+        let spec = parse(&[""]).get_spec(chart_data.clone()).unwrap();
+
+        assert_eq!(spec.theme.name, "dark");
+        assert_eq!(spec.options.palette, ["#1b9e77", "#d95f02"]);
+        assert_eq!(spec.options.legend, LegendPosition::Right);
+
+        let render_data = StackedBarChartTool::new(&TestLogger {})
+            .process_chart_data(&spec)
+            .unwrap();
+
+        assert_eq!(render_data.theme.name, "dark");
+        assert_eq!(render_data.category_colors, [0x1b9e77, 0xd95f02]);
+        assert_eq!(render_data.legend_position, LegendPosition::Right);
+
+        let spec = parse(&[
+            "",
+            "--theme",
+            "light",
+            "--palette",
+            "#000",
+            "--legend",
+            "top",
+        ])
+        .get_spec(chart_data.clone())
+        .unwrap();
+
+        assert_eq!(spec.theme.name, "light");
+        assert_eq!(spec.options.palette, ["#000"]);
+        assert_eq!(spec.options.legend, LegendPosition::Top);
+
+        let mut chart_data = chart_data;
+
+        chart_data.options.as_mut().unwrap().theme = "sepia".to_string();
+
+        assert_eq!(
+            parse(&[""]).get_spec(chart_data).unwrap_err().to_string(),
+            "Unknown theme 'sepia', expected one of light, dark"
+        );
+        assert!(json5::from_str::<ChartData>(
+            r#"{ title: "", units: "", categories: [], items: [], options: { colour: "red" } }"#
+        )
+        .is_err());
+
+        for key in FILE_OPTIONS {
+            let error = json5::from_str::<ChartData>(&format!(
+                r#"{{ title: "", units: "", categories: [], items: [], options: {{ {}: "/etc/passwd" }} }}"#,
+                key
+            ))
+            .unwrap_err();
+
+            assert!(error
+                .to_string()
+                .contains(&format!("'{}' names a file", key)));
+        }
+    }
+
+    #[test]
+    fn print_config_test() {
+        struct OutputLogger(std::cell::RefCell<String>);
+
+        impl StackedBarChartLog for OutputLogger {
+            fn output(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push_str(&args.to_string());
+            }
+            fn warning(self: &Self, _args: Arguments) {}
+            fn error(self: &Self, _args: Arguments) {}
+        }
+
+        let logger = OutputLogger(Default::default());
+        let dir = std::env::temp_dir();
+        let config_path = dir.join("stacked_bar_chart_print_config_test.toml");
+        let chart_path = dir.join("stacked_bar_chart_print_config_test.json5");
+
+        std::fs::write(&config_path, "theme = 'dark'\nslot_width = 30\n").unwrap();
+        std::fs::write(
+            &chart_path,
+            "{ title: '', units: '', categories: [], items: [], \
+            options: { theme: 'light', plot_height: 100 } }",
+        )
+        .unwrap();
+        StackedBarChartTool::new(&logger)
+            .run(
+                [
+                    "",
+                    "--print-config",
+                    "--config",
+                    config_path.to_str().unwrap(),
+                    "--font-size",
+                    "14",
+                    chart_path.to_str().unwrap(),
+                ]
+                .iter()
+                .map(|arg| arg.into()),
+            )
+            .unwrap();
+
+        let config: serde_json::Value = serde_json::from_str(&logger.0.borrow()).unwrap();
+
+        assert_eq!(config["font_size"], 14.0);
+        assert_eq!(config["plot_height"], 100.0);
+        assert_eq!(config["theme"], "light");
+        assert_eq!(config["slot_width"], 30.0);
+        assert_eq!(config["legend_swatch"], 20.0);
+    }
+
+    #[test]
+    fn config_test() {
+        let path = std::env::temp_dir().join("stacked_bar_chart_config_test.toml");
+        let parse = |args: &[&str]| {
+            let mut cli = Cli::parse_args(
+                ["", "--config", path.to_str().unwrap()]
+                    .iter()
+                    .chain(args)
+                    .map(|arg| arg.into()),
+            )
+            .unwrap();
+
+            cli.apply_config().map(|_| cli)
+        };
+
+        std::fs::write(
+            &path,
+            "theme = 'dark'\nfont_size = 12\nplot_height = 150\nmargin = [20, 40]\n\
+            palette = ['#1b9e77', '#d95f02']\n",
+        )
+        .unwrap();
+
+        let cli = parse(&["--font-size", "14"]).unwrap();
+
+        assert_eq!(cli.options.theme, "dark");
+        assert_eq!(cli.options.palette, ["#1b9e77", "#d95f02"]);
+        assert_eq!(
+            parse(&["--palette", "#000"]).unwrap().options.palette,
+            ["#000"]
+        );
+        assert_eq!(cli.options.font_size, 14.0);
+        assert_eq!(cli.options.plot_height, 150.0);
+        assert_eq!(cli.options.margin, vec![20.0, 40.0]);
+
+        let chart_data: ChartData = json5::from_str(
+            "{ title: '', units: '', categories: [], items: [], options: { plot_height: 100 } }",
+        )
+        .unwrap();
+        let options = cli.get_spec(chart_data).unwrap().options;
+
+        assert_eq!(options.plot_height, 100.0);
+        assert_eq!(options.font_size, 14.0);
+        assert_eq!(options.palette, ["#1b9e77", "#d95f02"]);
+
+        // A palette in the chart file replaces the house palette rather than adding to it
+        let chart_data: ChartData = json5::from_str(
+            "{ title: '', units: '', categories: [], items: [], options: { palette: ['#fff'] } }",
+        )
+        .unwrap();
+
+        assert_eq!(cli.get_spec(chart_data).unwrap().options.palette, ["#fff"]);
+
+        let chart_path = std::env::temp_dir().join("stacked_bar_chart_config_test.json5");
+
+        std::fs::write(
+            &chart_path,
+            "{ title: '', units: '', categories: [], items: [], options: { plot_height: 100 } }",
+        )
+        .unwrap();
+
+        let config = parse(&[chart_path.to_str().unwrap(), "--font-size", "14"])
+            .unwrap()
+            .get_effective_config()
+            .unwrap();
+
+        assert_eq!(config["theme"], "dark");
+        assert_eq!(config["font_size"], 14.0);
+        assert_eq!(config["plot_height"], 100.0);
+        assert_eq!(config["margin"], serde_json::json!([20.0, 40.0]));
+        assert_eq!(config["palette"], serde_json::json!(["#1b9e77", "#d95f02"]));
+
+        // The chart file wins over the config file even when it gives the default
+        std::fs::write(
+            &chart_path,
+            "{ title: '', units: '', categories: [], items: [], options: { theme: 'light' } }",
+        )
+        .unwrap();
+
+        let cli = parse(&[chart_path.to_str().unwrap()]).unwrap();
+
+        assert_eq!(cli.get_effective_config().unwrap()["theme"], "light");
+
+        let chart_data = StackedBarChartTool::read_input(&cli, Some(&chart_path))
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(cli.get_spec(chart_data).unwrap().theme.name, "light");
+
+        std::fs::write(&path, "colour = 'red'\n").unwrap();
+
+        assert!(parse(&[]).is_err());
+
+        std::fs::write(&path, "palette = '#1b9e77'\n").unwrap();
+
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn error_kind_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.items[1].values.pop();
+
+        match tool.render(&chart_data, &ChartOptions::default()) {
+            Err(StackedBarChartError::Validation { item, .. }) => {
+                assert_eq!(item.as_deref(), Some(chart_data.items[1].key.as_str()))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let run = |args: &[&str]| {
+            StackedBarChartTool::new(&logger)
+                .run(args.iter().map(|arg| arg.into()))
+                .unwrap_err()
+        };
+        let path = std::env::temp_dir().join("stacked_bar_chart_error_test.json5");
+
+        std::fs::write(&path, "{\n  title: 'x',\n  ]").unwrap();
+
+        assert_eq!(run(&["", "--bogus"]).exit_code(), 64);
+        assert!(matches!(
+            run(&["", path.to_str().unwrap()]),
+            StackedBarChartError::Parse { line: Some(3), .. }
+        ));
+        assert!(matches!(
+            run(&["", "missing.json5"]),
+            StackedBarChartError::Io { .. }
+        ));
+        assert_eq!(
+            run(&["", "missing.json5"]).to_string(),
+            "Unable to open file 'missing.json5'"
+        );
+
+        std::fs::write(
+            &path,
+            "{ title: 'x', units: '', categories: ['a'], items: [{ key: 'k', values: [1] }] }",
+        )
+        .unwrap();
+
+        let error = run(&["", "--fragment", path.to_str().unwrap(), "out.png"]);
+
+        assert_eq!(error.exit_code(), 64);
+        assert_eq!(
+            error.to_string(),
+            "PNG output needs a complete SVG document so cannot be a fragment"
+        );
+        assert!(matches!(
+            run(&["", "--logo", "logo.bmp", path.to_str().unwrap()]),
+            StackedBarChartError::Usage(_)
+        ));
+    }
+
+    #[test]
+    fn diagnostic_test() {
+        struct DiagnosticLogger(std::cell::RefCell<Vec<String>>);
+
+        impl StackedBarChartLog for DiagnosticLogger {
+            fn output(self: &Self, _args: Arguments) {}
+            fn warning(self: &Self, _args: Arguments) {}
+            fn error(self: &Self, _args: Arguments) {}
+            fn info(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(args.to_string());
+            }
+            fn diagnostic(self: &Self, diagnostic: &Diagnostic, format: ErrorFormat) {
+                assert_eq!(format, ErrorFormat::Json);
+                self.0.borrow_mut().push(diagnostic.to_json());
+            }
+        }
+
+        let logger = DiagnosticLogger(Default::default());
+        let mut tool = StackedBarChartTool::new(&logger);
+        let path = std::env::temp_dir().join("stacked_bar_chart_diagnostic_test.json5");
+
+        std::fs::write(
+            &path,
+            "{title: 'x', units: '', categories: ['a'], items: [{key: 'k', values: []}]}",
+        )
+        .unwrap();
+
+        let error = tool
+            .run(
+                ["", "--error-format", "json", path.to_str().unwrap()]
+                    .iter()
+                    .map(|arg| arg.into()),
+            )
+            .unwrap_err();
+
+        tool.report(&error);
+
+        assert_eq!(
+            logger.0.borrow()[..],
+            [format!(
+                concat!(
+                    r#"{{"severity":"error","code":"validation","#,
+                    r#""message":"Item 0 needs 1 values and has 0","#,
+                    r#""location":{{"file":{},"item":"k"}}}}"#
+                ),
+                serde_json::to_string(&path).unwrap()
+            )]
+        );
+
+        let report = |args: &[&str]| {
+            let mut tool = StackedBarChartTool::new(&logger);
+            let error = tool.run(args.iter().map(|arg| arg.into())).unwrap_err();
+
+            logger.0.borrow_mut().clear();
+            tool.report(&error);
+            serde_json::from_str::<serde_json::Value>(&logger.0.borrow()[0]).unwrap()
+        };
+
+        std::fs::write(&path, "{\n  title: 'x',\n  ]").unwrap();
+
+        let diagnostic = report(&["", "--error-format", "json", path.to_str().unwrap()]);
+
+        assert_eq!(diagnostic["code"], "parse");
+        assert_eq!(diagnostic["message"], "expected identifier or string");
+        assert_eq!(diagnostic["location"]["file"], path.to_str().unwrap());
+        assert_eq!(diagnostic["location"]["line"], 3);
+
+        let diagnostic = report(&["", "--bogus", "--error-format=json"]);
+
+        assert_eq!(diagnostic["code"], "usage");
+        assert_eq!(diagnostic["message"], "unexpected argument '--bogus' found");
+        assert!(diagnostic.get("location").is_none());
+    }
+
+    #[test]
+    fn log_level_test() {
+        struct LevelLogger(std::cell::RefCell<Vec<String>>);
+
+        impl StackedBarChartLog for LevelLogger {
+            fn output(self: &Self, _args: Arguments) {}
+            fn warning(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(args.to_string());
+            }
+            fn error(self: &Self, _args: Arguments) {}
+            fn info(self: &Self, args: Arguments) {
+                self.0.borrow_mut().push(args.to_string());
+            }
+        }
+
+        let logger = LevelLogger(Default::default());
+        let run = |args: &[&str]| {
+            logger.0.borrow_mut().clear();
+            StackedBarChartTool::new(&logger)
+                .run(args.iter().map(|arg| arg.into()))
+                .unwrap();
+            logger.0.borrow().clone()
+        };
+        let path = std::env::temp_dir().join("stacked_bar_chart_log_level_test.json5");
+        let output = std::env::temp_dir().join("stacked_bar_chart_log_level_test.svg");
+        let (path, output) = (path.to_str().unwrap(), output.to_str().unwrap());
+
+        std::fs::write(
+            path,
+            "{title: 'x', units: '', categories: ['a'], items: [{key: 'k', values: [1]}]}",
+        )
+        .unwrap();
+
+        let args = ["--max-width", "10", path, output];
+        let warning = run(&[&[""][..], &args].concat());
+
+        assert_eq!(warning.len(), 1);
+        assert!(warning[0].starts_with("Chart width of "));
+        assert!(run(&[&["", "--quiet"][..], &args].concat()).is_empty());
+
+        let verbose = run(&[&["", "--verbose"][..], &args].concat());
+
+        assert!(verbose.contains(&warning[0]));
+        assert!(verbose.contains(&"Y axis runs from 0 to 1 in steps of 0.05".to_string()));
+        assert!(Cli::try_parse_from(["", "--quiet", "--verbose"]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "capi")]
+    fn capi_test() {
+        use std::ffi::{CStr, CString};
+
+        let input = CString::new(serde_json::to_string(&test_chart_data()).unwrap()).unwrap();
+        let options = CString::new("{ plot_height: 100 }").unwrap();
+
+        unsafe {
+            let svg = capi::sbc_render(input.as_ptr(), options.as_ptr());
+
+            let tool = StackedBarChartTool::new(&TestLogger {});
+            let short = ChartOptions::builder().plot_height(100.0).build().unwrap();
+
+            assert!(capi::sbc_last_error().is_null());
+            assert_eq!(
+                CStr::from_ptr(svg).to_str().unwrap(),
+                tool.render(&test_chart_data(), &short).unwrap()
+            );
+            assert_ne!(
+                CStr::from_ptr(svg).to_str().unwrap(),
+                tool.render(&test_chart_data(), &ChartOptions::default())
+                    .unwrap()
+            );
+            capi::sbc_free(svg);
+
+            let options = CString::new("{ colour: 'red' }").unwrap();
+
+            assert!(capi::sbc_render(input.as_ptr(), options.as_ptr()).is_null());
+            assert!(CStr::from_ptr(capi::sbc_last_error())
+                .to_str()
+                .unwrap()
+                .starts_with("unknown field `colour`"));
+        }
+
+        assert_eq!(
+            capi::panic_message(std::panic::catch_unwind(|| panic!("at the disco")).unwrap_err()),
+            "The renderer panicked: at the disco"
+        );
+    }
+
+    #[test]
+    fn chart_spec_test() {
+        let tool = StackedBarChartTool::new(&TestLogger {});
+        let mut dark = ChartSpec::new(test_chart_data(), ChartOptions::default()).unwrap();
+
+        assert_eq!(dark.options.font_size, 10.0);
+
+        dark.theme = Theme::find("dark").unwrap().clone();
+        dark.options.fragment = true;
+
+        let svg = tool.render_spec(&dark).unwrap();
+
+        assert!(svg.starts_with("<svg") && !svg.contains("xmlns"));
+        assert!(svg.contains("rgb(30,30,30)"));
+        assert_eq!(
+            tool.render(&test_chart_data(), &ChartOptions::default())
+                .unwrap(),
+            tool.render_spec(&spec(&test_chart_data(), &Cli::parse_from([""])))
+                .unwrap()
+        );
+        assert_eq!(
+            tool.render_spec(&dark).unwrap(),
+            tool.render_spec(&spec(
+                &test_chart_data(),
+                &Cli::parse_from(["", "--theme", "dark", "--fragment"])
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            tool.render_spec(&dark).unwrap(),
+            tool.render(
+                &test_chart_data(),
+                &ChartOptions::builder()
+                    .theme("dark")
+                    .fragment(true)
+                    .build()
+                    .unwrap()
+            )
+            .unwrap()
+        );
+        assert!(ChartOptions::builder().theme("sepia").build().is_err());
+
+        let sepia = ChartOptions {
+            theme: "sepia".to_string(),
+            ..ChartOptions::default()
+        };
+
+        assert_eq!(
+            ChartSpec::new(test_chart_data(), sepia)
+                .unwrap_err()
+                .to_string(),
+            "Unknown theme 'sepia', expected one of light, dark"
+        );
+    }
+
+    #[test]
+    fn accessibility_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let render_data = tool
+            .process_chart_data(&spec(&test_chart_data(), &Cli::parse_from([""])))
+            .unwrap();
+        let svg = tool.render_chart(&render_data).unwrap().to_string();
+
+        assert!(svg.contains(r#"aria-label="Jobs""#) && svg.contains(r#"role="img""#));
+        assert!(svg.contains(
+            "<desc>Stacked bar chart of 2 items from Jan to Feb in count, split into 3 \
+            categories: Ready, Active, Complete. The largest total is 11 count for Feb.</desc>"
+        ));
+        assert!(svg.contains("<title>Legend</title>"));
+
+        let mut chart_data = test_chart_data();
+
+        for category in chart_data.categories.iter_mut() {
+            category.hidden = true;
+        }
+
+        let render_data = tool
+            .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+            .unwrap();
+
+        assert!(StackedBarChartTool::describe(&render_data).starts_with(
+            "Stacked bar chart of 2 items from Jan to Feb in count, with no categories shown."
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn png_output_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let format = |args: &[&str]| Cli::parse_from(args).get_format();
+
+        assert_eq!(format(&["", "in.json5", "out.PNG"]), OutputFormat::Png);
+        assert_eq!(format(&["", "in.json5", "out.svg"]), OutputFormat::Svg);
+        assert_eq!(
+            format(&["", "--format", "svg", "in.json5", "out.png"]),
+            OutputFormat::Svg
+        );
+
+        let spec = spec(&test_chart_data(), &Cli::parse_from([""]));
+        let render_data = tool.process_chart_data(&spec).unwrap();
+        let document = tool.render_chart(&render_data).unwrap().to_string();
+        let path = std::env::temp_dir().join("stacked_bar_chart_png_output_test.png");
+
+        StackedBarChartTool::write_png_file(
+            Box::new(File::create(&path).unwrap()),
+            &document,
+            &spec.options,
+        )
+        .unwrap();
+
+        let png = std::fs::read(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // The IHDR chunk holds the size of the image
+        assert_eq!(png[16..24], [0, 0, 0, 140, 0, 0, 1, 214]);
+
+        // At twice the density the image is twice the size and says so, at 7559 pixels a metre
+        let mut options = spec.options.clone();
+
+        options.dpi = Some("2x".parse().unwrap());
+        StackedBarChartTool::write_png_file(
+            Box::new(File::create(&path).unwrap()),
+            &document,
+            &options,
+        )
+        .unwrap();
+
+        let png = std::fs::read(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(png[16..24], [0, 0, 1, 24, 0, 0, 3, 172]);
+        assert_eq!(png[37..49], *b"pHYs\0\0\x1d\x87\0\0\x1d\x87");
+    }
+
+    #[test]
+    fn eps_output_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.title = "Café (2024)".to_string();
+
+        let render_data = tool
+            .process_chart_data(&spec(
+                &chart_data,
+                &Cli::parse_from(["", "--bar-radius", "4"]),
+            ))
+            .unwrap();
+        let eps = StackedBarChartTool::render_eps(&render_data).unwrap();
+
+        assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 140 470\n"));
+        assert!(eps.contains("(Caf\\351 \\(2024\\)) 70 450 C"));
+        assert!(eps.contains("58.5 330 curveto"));
+        assert!(eps.ends_with("showpage\n%%EOF\n"));
+        assert!(eps.contains("\n%%Title: Caf? (2024)\n%%Creator"));
+
+        let mut render_data = render_data;
+
+        render_data.title_lines = vec!["Sales\r\n%%EOF".to_string(), "x".repeat(300)];
+
+        // Text that could end the comment early, or a title too long for one, is kept out of it
+        let title = StackedBarChartTool::render_eps(&render_data)
+            .unwrap()
+            .lines()
+            .find(|line| line.starts_with("%%Title:"))
+            .unwrap()
+            .to_string();
+
+        assert!(title.starts_with("%%Title: Sales??%%EOF xxx"));
+        assert_eq!(title.len(), 255);
+        assert_eq!(
+            Cli::parse_from(["", "in.json5", "out.eps"]).get_format(),
+            OutputFormat::Eps
+        );
+    }
+
+    #[test]
+    fn term_output_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let render_data = tool
+            .process_chart_data(&spec(&test_chart_data(), &Cli::parse_from([""])))
+            .unwrap();
+
+        assert_eq!(
+            StackedBarChartTool::render_term(&render_data, false, 30),
+            "Jobs\n\n\
+            Jan │███████████████████   10\n\
+            Feb │███████████████▓▓▒▒▒▒ 11\n    \
+            └─────────────────────\n\n\
+            █ Ready  ▓ Active  ▒ Complete\n"
+        );
+        assert!(StackedBarChartTool::render_term(&render_data, true, 30).contains("\x1b["));
+    }
+
+    #[test]
+    fn minify_test() {
+        let svg = r#"<svg viewBox="0 0 100.123 50">
+<!-- comment -->
+<style>
+.a{fill:red}
+.b{animation:grow 1s ease-out}
+</style>
+<path d="M10.004,20 l15,0 l0,-36.333 q0,-4,-4,-4 z"/>
+<text transform="translate(1.005,2) rotate(45)">
+A  label
+</text>
+</svg>"#;
+
+        assert_eq!(
+            minify::minify(svg, 1),
+            "<svg viewBox=\"0 0 100.1 50\"><style>.a{fill:red}.b{animation:grow 1s ease-out}\
+            </style><path d=\"M10 20h15v-36.3q0-4-4-4z\"/><text transform=\"translate(1,2) \
+            rotate(45)\">A  label</text></svg>"
+        );
+    }
+
+    #[test]
+    fn embed_data_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let mut chart_data = test_chart_data();
+
+        chart_data.title = "Jobs <&> \"more\"".to_string();
+
+        let render = |chart_data: &ChartData, args: &[&str]| {
+            let render_data = tool
+                .process_chart_data(&spec(chart_data, &Cli::parse_from(args)))
+                .unwrap();
+
+            tool.render_chart(&render_data).unwrap().to_string()
+        };
+        let svg = render(&chart_data, &["", "--embed-data"]);
+
+        assert!(!render(&chart_data, &[""]).contains("<metadata>"));
+        assert!(svg.contains(r#""categories":["Ready","Active","Complete"]"#));
+        assert!(svg.contains(&format!(r#"version="{}""#, env!("CARGO_PKG_VERSION"))));
+
+        let charts = StackedBarChartTool::read_chart_file(Box::new(io::Cursor::new(svg.clone())));
+        let chart_data = charts.unwrap().remove(0);
+
+        assert_eq!(chart_data.title, "Jobs <&> \"more\"");
+        assert_eq!(render(&chart_data, &["", "--embed-data"]), svg);
+        assert!(
+            StackedBarChartTool::read_chart_file(Box::new(io::Cursor::new(render(
+                &chart_data,
+                &[""]
+            ))))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn hostile_input_test() {
+        let logger = TestLogger::new();
+        let tool = StackedBarChartTool::new(&logger);
+        let hostile = [
+            "<script>alert(1)</script>",
+            "\" onload=\"alert(1)",
+            "' onmouseover='alert(1)",
+            "]]><!-- & &amp; &#x3c;",
+            "\u{0}\u{1b}[31mred\u{7}\u{fffe}",
+            "</text></svg><svg>",
+        ];
+
+        for text in hostile {
+            let mut chart_data = test_chart_data();
+
+            chart_data.title = text.to_string();
+            chart_data.units = text.to_string();
+            chart_data.caption = Some(text.to_string());
+            chart_data.categories[0].name = text.to_string();
+            chart_data.items[0].key = text.to_string();
+
+            for args in [
+                vec![""],
+                vec![
+                    "",
+                    "--max-label-chars",
+                    "5",
+                    "--legend-totals",
+                    "--embed-data",
+                ],
+                vec!["", "--tooltip", "{item} {category} {units}"],
+            ] {
+                let render_data = tool
+                    .process_chart_data(&spec(&chart_data, &Cli::parse_from(args)))
+                    .unwrap();
+                let svg = tool.render_chart(&render_data).unwrap().to_string();
+                let mut texts = vec![];
+
+                assert_eq!(validate::check_svg(&svg), Vec::<String>::new(), "{}", text);
+                assert!(!svg.contains("<script") && !svg.contains('\u{1b}'));
+
+                for event in svg::parser::Parser::new(&svg) {
+                    if let svg::parser::Event::Tag(name, _, attributes) = event {
+                        assert_ne!(name, "script");
+                        assert!(attributes.keys().all(|key| !key.starts_with("on")));
+                    }
+                }
+
+                for event in svg::parser::Parser::new(&svg) {
+                    if let svg::parser::Event::Text(text) = event {
+                        texts.push(text.trim().to_string());
+                    }
+                }
+
+                assert!(texts
+                    .contains(&text::escape_html(&text::sanitize(text)).replace("&quot;", "\"")));
+
+                let page = html::page(&render_data.title, &svg, true);
+
+                assert!(!page.contains("<script>alert"));
+            }
+
+            assert!(!StackedBarChartTool::render_term(
+                &tool
+                    .process_chart_data(&spec(&chart_data, &Cli::parse_from([""])))
+                    .unwrap(),
+                false,
+                80
+            )
+            .contains('\u{1b}'));
+        }
+
+        assert_eq!(
+            validate::check_svg("<svg viewBox=\"0 0 1 1\">\u{1}</svg>"),
+            vec!["Character U+0001 is not allowed in XML"]
+        );
     }
 }
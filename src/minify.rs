@@ -0,0 +1,182 @@
+use svg::{
+    node::element::{
+        path::{Command, Data, Position},
+        tag::Type,
+    },
+    parser::{Event, Parser},
+};
+
+/// Attributes holding only numbers, which are rounded
+#[rustfmt::skip]
+const NUMBER_ATTRIBUTES: &[&str] = &[
+    "x", "y", "x1", "y1", "x2", "y2", "cx", "cy", "dy", "width", "height", "rx", "ry", "r",
+    "viewBox", "points", "transform",
+];
+
+/// Shrink a rendered SVG by rounding coordinates to `precision` decimal places, writing path
+/// data compactly and dropping comments and the whitespace between elements
+pub fn minify(content: &str, precision: usize) -> String {
+    rewrite(content, precision, true)
+}
+
+/// Round the numbers in a rendered SVG to `precision` decimal places so that tiny differences
+/// in floating point arithmetic don't change the output, keeping each tag on its own line
+pub fn round(content: &str, precision: usize) -> String {
+    rewrite(content, precision, false)
+}
+
+fn rewrite(content: &str, precision: usize, compact: bool) -> String {
+    let mut lines = vec![];
+    let mut in_style = false;
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Tag(name, tag_type, attributes) => {
+                in_style = name == "style" && tag_type == Type::Start;
+
+                if tag_type == Type::End {
+                    lines.push(format!("</{}>", name));
+                    continue;
+                }
+
+                let mut names: Vec<&String> = attributes.keys().collect();
+                let mut tag = format!("<{}", name);
+
+                // Attributes are kept in a hash map, so sort them for stable output
+                names.sort();
+
+                for attribute in names {
+                    let value = &attributes[attribute];
+                    let value = if attribute == "d" && compact {
+                        path_data(value, precision)
+                    } else if attribute == "d" || NUMBER_ATTRIBUTES.contains(&attribute.as_str()) {
+                        round_numbers(value, precision)
+                    } else {
+                        value.to_string()
+                    };
+
+                    tag += &format!(" {}=\"{}\"", attribute, value);
+                }
+
+                tag += if tag_type == Type::Empty { "/>" } else { ">" };
+                lines.push(tag);
+            }
+            // Style rules are one per line, so the lines can be run together
+            Event::Text(text) if in_style && compact => {
+                lines.push(text.lines().map(str::trim).collect())
+            }
+            Event::Text(text) if !text.trim().is_empty() => lines.push(text.trim().to_string()),
+            _ => (),
+        }
+    }
+
+    lines.join(if compact { "" } else { "\n" })
+}
+
+fn round_number(n: f64, precision: usize) -> String {
+    let rounded = format!("{:.1$}", n, precision);
+    let trimmed = if rounded.contains('.') {
+        rounded.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &rounded
+    };
+
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// Round every number in a list such as `translate(10.25,20) rotate(45)`
+fn round_numbers(value: &str, precision: usize) -> String {
+    let mut output = String::new();
+    let mut number = String::new();
+    let flush = |number: &mut String, output: &mut String| {
+        match number.parse::<f64>() {
+            Ok(n) => *output += &round_number(n, precision),
+            Err(_) => *output += number,
+        }
+        number.clear();
+    };
+
+    for c in value.chars() {
+        let in_exponent = number.ends_with(['e', 'E']);
+
+        if c.is_ascii_digit()
+            || c == '.'
+            || (c == '-' && (number.is_empty() || in_exponent))
+            || ((c == 'e' || c == 'E') && !number.is_empty())
+        {
+            number.push(c);
+        } else {
+            flush(&mut number, &mut output);
+            output.push(c);
+        }
+    }
+
+    flush(&mut number, &mut output);
+
+    output
+}
+
+/// Rewrite path data with rounded numbers, single letter commands for horizontal and vertical
+/// lines, and no separators where a minus sign will do
+fn path_data(value: &str, precision: usize) -> String {
+    let data = match Data::parse(value) {
+        Ok(data) => data,
+        Err(_) => return value.to_string(),
+    };
+    let mut output = String::new();
+
+    for command in data.iter() {
+        let (letter, position, parameters) = match command {
+            Command::Move(position, parameters) => ('m', position, parameters),
+            Command::Line(position, parameters) => match (position, &parameters[..]) {
+                (Position::Relative, [dx, dy]) if *dy == 0.0 && *dx != 0.0 => {
+                    output += &numbers('h', &[*dx], precision);
+                    continue;
+                }
+                (Position::Relative, [dx, dy]) if *dx == 0.0 => {
+                    output += &numbers('v', &[*dy], precision);
+                    continue;
+                }
+                _ => ('l', position, parameters),
+            },
+            Command::HorizontalLine(position, parameters) => ('h', position, parameters),
+            Command::VerticalLine(position, parameters) => ('v', position, parameters),
+            Command::QuadraticCurve(position, parameters) => ('q', position, parameters),
+            Command::SmoothQuadraticCurve(position, parameters) => ('t', position, parameters),
+            Command::CubicCurve(position, parameters) => ('c', position, parameters),
+            Command::SmoothCubicCurve(position, parameters) => ('s', position, parameters),
+            Command::EllipticalArc(position, parameters) => ('a', position, parameters),
+            Command::Close => {
+                output.push('z');
+                continue;
+            }
+        };
+        let letter = match position {
+            Position::Absolute => letter.to_ascii_uppercase(),
+            Position::Relative => letter,
+        };
+
+        output += &numbers(letter, parameters, precision);
+    }
+
+    output
+}
+
+fn numbers(letter: char, parameters: &[f32], precision: usize) -> String {
+    let mut output = letter.to_string();
+
+    for (i, n) in parameters.iter().enumerate() {
+        let n = round_number(*n as f64, precision);
+
+        if i > 0 && !n.starts_with('-') {
+            output.push(' ');
+        }
+
+        output += &n;
+    }
+
+    output
+}
@@ -0,0 +1,37 @@
+use crate::StackedBarChartError;
+use regex::Regex;
+use std::error::Error;
+
+/// Compile a pattern which is either a regular expression between slashes such as
+/// '/^(web|db)-/', or a glob where '*' matches any run of characters and '?' any one
+/// character, which must match the whole of the text
+pub fn compile(pattern: &str) -> Result<Regex, Box<dyn Error>> {
+    let expression = match pattern
+        .strip_prefix('/')
+        .and_then(|rest| rest.strip_suffix('/'))
+    {
+        Some(expression) => expression.to_string(),
+        None => {
+            let mut expression = "^".to_string();
+
+            for c in pattern.chars() {
+                match c {
+                    '*' => expression += ".*",
+                    '?' => expression.push('.'),
+                    _ => expression += &regex::escape(&c.to_string()),
+                }
+            }
+
+            expression + "$"
+        }
+    };
+
+    match Regex::new(&expression) {
+        Ok(regex) => Ok(regex),
+        Err(error) => Err(StackedBarChartError::Usage(format!(
+            "Pattern '{}' is not valid: {}",
+            pattern, error
+        ))
+        .into()),
+    }
+}
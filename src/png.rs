@@ -0,0 +1,103 @@
+use crate::{ChartOptions, StackedBarChartError, StackedBarChartTool};
+use easy_error::ResultExt;
+use resvg::{tiny_skia, usvg};
+use std::{error::Error, io::Write};
+
+/// The PNG with a pHYs chunk after the IHDR chunk giving its density, so that it prints at the
+/// size of the SVG
+fn with_density(png: Vec<u8>, dpi: f64) -> Vec<u8> {
+    // The 8 byte signature and the 25 byte IHDR chunk always come first
+    let (head, tail) = png.split_at(33);
+    let pixels_per_metre = (dpi / 0.0254).round() as u32;
+    let mut chunk = b"pHYs".to_vec();
+
+    chunk.extend(pixels_per_metre.to_be_bytes());
+    chunk.extend(pixels_per_metre.to_be_bytes());
+    chunk.push(1);
+
+    let crc = crc32(&chunk);
+
+    [head, &9u32.to_be_bytes(), &chunk, &crc.to_be_bytes(), tail].concat()
+}
+
+/// The CRC-32 checksum of a PNG chunk's type and data
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Rasterize the chart using the system fonts, plus the embedded font if there is one so
+    /// that the image matches the SVG
+    pub(crate) fn write_png_file(
+        mut writer: Box<dyn Write>,
+        content: &str,
+        options: &ChartOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut svg_options = usvg::Options::default();
+        let fontdb = svg_options.fontdb_mut();
+
+        fontdb.load_system_fonts();
+
+        if let Some(path) = &options.embed_font {
+            fontdb.load_font_file(path).context(format!(
+                "Unable to load font file '{}'",
+                path.to_string_lossy()
+            ))?;
+        }
+
+        let tree = usvg::Tree::from_str(content, &svg_options)?;
+        // The layout is drawn scaled up so that it looks the same at any density
+        let ratio = options.dpi.map_or(1.0, |dpi| dpi.ratio());
+        let size = tree.size().to_int_size().scale_by(ratio as f32);
+        let size = match size {
+            Some(size) => size,
+            None => {
+                return Err(StackedBarChartError::Render(format!(
+                    "Unable to scale the image to {} times its size",
+                    ratio
+                ))
+                .into())
+            }
+        };
+        let mut pixmap = match tiny_skia::Pixmap::new(size.width(), size.height()) {
+            Some(pixmap) => pixmap,
+            None => {
+                return Err(StackedBarChartError::Render(format!(
+                    "Unable to create a {}x{} image",
+                    size.width(),
+                    size.height()
+                ))
+                .into())
+            }
+        };
+
+        resvg::render(
+            &tree,
+            tiny_skia::Transform::from_scale(ratio as f32, ratio as f32),
+            &mut pixmap.as_mut(),
+        );
+
+        let png = pixmap.encode_png()?;
+
+        match options.dpi {
+            Some(dpi) => writer.write_all(&with_density(png, dpi.dpi()))?,
+            None => writer.write_all(&png)?,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{CategoryData, ChartData, ItemData, MarginData, StackedBarChartError};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, io::BufRead};
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// A chart or array of charts
+    Json5,
+    /// A header row of 'key' and the category names, then a row of values for each item
+    Csv,
+    /// A chart without items on the first line, then an item on each line
+    Ndjson,
+}
+
+/// The first line of NDJSON input
+#[derive(Deserialize)]
+struct Header {
+    title: String,
+    #[serde(default)]
+    units: String,
+    categories: Vec<CategoryData>,
+    #[serde(default)]
+    caption: Option<String>,
+}
+
+/// Sums runs of consecutive items into bins so that any number of items can be read using a
+/// bounded amount of memory. Whenever there are more than twice the wanted number of bins,
+/// neighbouring bins are summed, doubling the number of items in each.
+pub struct Binner {
+    max_bins: usize,
+    bin_size: usize,
+    bins: Vec<(ItemData, usize)>,
+}
+
+impl Binner {
+    pub fn new(max_bins: usize) -> Binner {
+        Binner {
+            max_bins: max_bins.max(1),
+            bin_size: 1,
+            bins: vec![],
+        }
+    }
+
+    pub fn push(&mut self, item: ItemData) {
+        match self.bins.last_mut() {
+            Some((bin, count)) if *count < self.bin_size => {
+                Self::add(bin, item);
+                *count += 1;
+            }
+            _ => self.bins.push((item, 1)),
+        }
+
+        if self.bins.len() > self.max_bins * 2 {
+            self.halve();
+        }
+    }
+
+    /// The bins, each keyed by its first item
+    pub fn finish(mut self) -> Vec<ItemData> {
+        while self.bins.len() > self.max_bins {
+            self.halve();
+        }
+
+        self.bins.into_iter().map(|(bin, _)| bin).collect()
+    }
+
+    fn halve(&mut self) {
+        let mut bins = vec![];
+
+        for (bin, count) in std::mem::take(&mut self.bins) {
+            match bins.last_mut() {
+                Some((last, last_count)) if *last_count + count <= self.bin_size * 2 => {
+                    Self::add(last, bin);
+                    *last_count += count;
+                }
+                _ => bins.push((bin, count)),
+            }
+        }
+
+        self.bins = bins;
+        self.bin_size *= 2;
+    }
+
+    fn add(bin: &mut ItemData, item: ItemData) {
+        if bin.values.len() < item.values.len() {
+            bin.values.resize(item.values.len(), None);
+        }
+
+        for (sum, value) in bin.values.iter_mut().zip(item.values) {
+            ChartData::add_value(sum, value);
+        }
+
+        bin.width = None;
+    }
+}
+
+/// Read a chart from CSV or NDJSON one line at a time, summing the items into at most
+/// `max_bars` bars. CSV has no title so is given `title`.
+pub fn read_stream(
+    reader: impl BufRead,
+    format: InputFormat,
+    max_bars: usize,
+    title: &str,
+) -> Result<ChartData, Box<dyn Error>> {
+    let mut lines = reader
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()));
+    let mut chart_data = match lines.next() {
+        Some((number, line)) => header(&line?, number, format, title)?,
+        None => {
+            return Err(StackedBarChartError::validation("The input is empty".to_string()).into())
+        }
+    };
+    let mut binner = Binner::new(max_bars);
+
+    for (number, line) in lines {
+        let line = line?;
+
+        binner.push(match format {
+            InputFormat::Csv => {
+                let mut fields = csv_fields(&line).into_iter();
+                let key = fields.next().unwrap_or_default().trim().to_string();
+                let mut values = vec![];
+
+                for (column, field) in fields.enumerate() {
+                    values.push(match field.trim() {
+                        "" => None,
+                        field => match field.parse() {
+                            Ok(value) => Some(value),
+                            Err(_) => {
+                                return Err(StackedBarChartError::Parse {
+                                    line: Some(number),
+                                    col: None,
+                                    message: format!(
+                                        "Line {} column {} is not a number: '{}'",
+                                        number,
+                                        column + 2,
+                                        field
+                                    ),
+                                }
+                                .into())
+                            }
+                        },
+                    });
+                }
+
+                ItemData {
+                    key,
+                    values,
+                    width: None,
+                    note: None,
+                }
+            }
+            _ => match serde_json::from_str(&line) {
+                Ok(item) => item,
+                Err(err) => {
+                    return Err(StackedBarChartError::Parse {
+                        line: Some(number),
+                        col: Some(err.column()),
+                        message: format!("Line {}: {}", number, err),
+                    }
+                    .into())
+                }
+            },
+        });
+    }
+
+    chart_data.items = binner.finish();
+
+    Ok(chart_data)
+}
+
+fn header(
+    line: &str,
+    number: usize,
+    format: InputFormat,
+    title: &str,
+) -> Result<ChartData, Box<dyn Error>> {
+    let header = match format {
+        InputFormat::Csv => Header {
+            title: title.to_string(),
+            units: String::new(),
+            categories: csv_fields(line)
+                .into_iter()
+                .skip(1)
+                .map(|name| CategoryData {
+                    name: name.trim().to_string(),
+                    hidden: false,
+                    stack: None,
+                    scale: None,
+                    transform: None,
+                })
+                .collect(),
+            caption: None,
+        },
+        _ => match serde_json::from_str(line) {
+            Ok(header) => header,
+            Err(err) => {
+                return Err(StackedBarChartError::Parse {
+                    line: Some(number),
+                    col: Some(err.column()),
+                    message: format!("Line {}: {}", number, err),
+                }
+                .into())
+            }
+        },
+    };
+
+    Ok(ChartData {
+        title: header.title,
+        units: header.units,
+        categories: header.categories,
+        items: vec![],
+        margins: MarginData::default(),
+        caption: header.caption,
+        options: None,
+    })
+}
+
+/// Split a line of CSV into fields, which can be quoted to contain commas, with doubled quotes
+/// inside quoted fields standing for one
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    fields.push(field);
+
+    fields
+}
@@ -0,0 +1,103 @@
+use crate::{text, ChartOptions, RenderData, StackedBarChartError, StackedBarChartTool};
+use std::error::Error;
+
+/// The placeholders a template can use, such as `{{chart}}`
+const PLACEHOLDERS: [&str; 5] = ["chart", "title", "legend", "width", "height"];
+
+/// Whether `template` has the placeholder for `name`
+pub(crate) fn uses(template: &str, name: &str) -> bool {
+    template.contains(&format!("{{{{{}}}}}", name))
+}
+
+/// Replace each `{{name}}` in `template` with its value, or give the first unknown name
+fn fill(template: &str, value: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+
+        output += &rest[..start];
+        output += &value(name).ok_or_else(|| name.to_string())?;
+        rest = &rest[end + 2..];
+    }
+
+    output += rest;
+
+    Ok(output)
+}
+
+impl ChartOptions {
+    /// The template file, checked for unknown placeholders
+    pub(crate) fn get_template(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let path = match self.template {
+            Some(ref path) => path,
+            None => return Ok(None),
+        };
+        let template = String::from_utf8(ChartOptions::read_file(path, "template")?)?;
+
+        if let Err(name) = fill(&template, |name| {
+            PLACEHOLDERS.contains(&name).then(String::new)
+        }) {
+            return Err(StackedBarChartError::validation(format!(
+                "Template file '{}' has an unknown placeholder '{{{{{}}}}}', expected one of {}",
+                path.to_string_lossy(),
+                name,
+                PLACEHOLDERS.join(", ")
+            ))
+            .into());
+        }
+
+        if !uses(&template, "chart") {
+            return Err(StackedBarChartError::validation(format!(
+                "Template file '{}' has no '{{{{chart}}}}' placeholder",
+                path.to_string_lossy()
+            ))
+            .into());
+        }
+
+        Ok(Some(template))
+    }
+}
+
+impl RenderData {
+    /// Whether the template places `name` itself, so it is left out of the chart
+    pub(crate) fn template_uses(&self, name: &str) -> bool {
+        self.template
+            .as_ref()
+            .is_some_and(|template| uses(template, name))
+    }
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// The chart as SVG, composed into the template when there is one. The chart goes in as a
+    /// nested SVG element, the title as plain text, and the legend as a group with its top left
+    /// corner at the origin.
+    pub(crate) fn render_svg(self: &Self, rd: &RenderData) -> Result<String, Box<dyn Error>> {
+        let document = self.render_chart(rd)?.to_string();
+        let template = match &rd.template {
+            Some(template) => template,
+            None => return Ok(document),
+        };
+        let (left, top) = rd.legend_origin();
+        let legend = format!(
+            "<g transform=\"translate({},{})\">{}</g>",
+            -left,
+            -top,
+            Self::legend(rd)
+        );
+
+        Ok(fill(template, |name| match name {
+            "chart" => Some(document.clone()),
+            "title" => Some(text::escape_html(&rd.title)),
+            "legend" => Some(legend.clone()),
+            "width" => Some(rd.width().to_string()),
+            "height" => Some(rd.height().to_string()),
+            _ => None,
+        })?)
+    }
+}
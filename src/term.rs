@@ -0,0 +1,135 @@
+use crate::{text, theme, RenderData, StackedBarChartTool};
+use yansi::Paint;
+
+/// Block characters which tell categories apart when there is no color
+const SHADES: &[char] = &['█', '▓', '▒', '░', '▚', '▞', '▙', '▟'];
+
+/// The width used when the terminal doesn't give one in `COLUMNS`
+const DEFAULT_COLUMNS: usize = 80;
+
+impl<'a> StackedBarChartTool<'a> {
+    /// The width of the terminal, for fitting the bars to it
+    pub(crate) fn term_columns() -> usize {
+        std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_COLUMNS)
+    }
+
+    /// Draw the chart as text `columns` wide with one row of blocks per bar, for a quick look at
+    /// the data in a terminal. Segments are told apart by color, or by shading when `color` is
+    /// false.
+    pub(crate) fn render_term(rd: &RenderData, color: bool, columns: usize) -> String {
+        let positive = theme::parse_color(rd.theme.positive).unwrap_or(0);
+        let negative = theme::parse_color(rd.theme.negative).unwrap_or(0);
+        let block = |index: usize, rgb: u32, n: usize| -> String {
+            if n == 0 {
+                String::new()
+            } else if color {
+                "█"
+                    .repeat(n)
+                    .rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+                    .to_string()
+            } else {
+                SHADES[index % SHADES.len()].to_string().repeat(n)
+            }
+        };
+        let labels: Vec<String> = rd
+            .bar_data
+            .iter()
+            .map(|bar| text::truncate(&bar.label, rd.max_label_chars).unwrap_or(bar.label.clone()))
+            .collect();
+        let totals: Vec<String> = rd
+            .bar_data
+            .iter()
+            .map(|bar| rd.format_total(bar.total))
+            .collect();
+        let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let total_width = totals.iter().map(|t| t.chars().count()).max().unwrap_or(0);
+        let bar_columns = columns
+            .saturating_sub(label_width + total_width + 4)
+            .max(10);
+        let longest = rd
+            .bar_data
+            .iter()
+            .map(|bar| bar.values.iter().map(|n| n.abs()).sum::<f64>())
+            .fold(0.0, f64::max);
+        let mut output = String::new();
+
+        for line in &rd.title_lines {
+            output += &format!("{}\n", line);
+        }
+
+        output.push('\n');
+
+        for (i, bar) in rd.bar_data.iter().enumerate() {
+            let mut row = String::new();
+            let (mut sum, mut drawn) = (0.0, 0);
+
+            for j in rd.stack_order.iter().copied() {
+                let value = &bar.values[j];
+
+                // Round where each segment ends rather than its length so that rounding errors
+                // don't add up along the bar
+                sum += value.abs();
+
+                let end = if longest > 0.0 {
+                    (sum / longest * bar_columns as f64).round() as usize
+                } else {
+                    0
+                };
+                let (index, rgb) = match (rd.delta, *value < 0.0) {
+                    (false, _) => (j, rd.category_colors[j]),
+                    (true, false) => (0, positive),
+                    (true, true) => (3, negative),
+                };
+
+                row += &block(index, rgb, end - drawn);
+                drawn = end;
+            }
+
+            output += &format!(
+                "{:>label_width$} │{}{} {:>total_width$}\n",
+                labels[i],
+                row,
+                " ".repeat(bar_columns - drawn),
+                totals[i],
+            );
+        }
+
+        output += &format!("{:>label_width$} └{}\n\n", "", "─".repeat(bar_columns));
+
+        let entries: Vec<String> = (0..rd.legend_labels.len())
+            .map(|i| {
+                let class = &rd.legend_classes[i];
+                let (index, rgb) = if class.contains("negative") {
+                    (3, negative)
+                } else if class.contains("positive") {
+                    (0, positive)
+                } else {
+                    let index = class
+                        .trim_start_matches("category-")
+                        .parse::<usize>()
+                        .unwrap_or(0);
+
+                    (index, rd.category_colors[index])
+                };
+
+                format!(
+                    "{} {}{}",
+                    block(index, rgb, 1),
+                    rd.legend_labels[i],
+                    rd.legend_suffixes[i]
+                )
+            })
+            .collect();
+
+        output += &format!("{}\n", entries.join("  "));
+
+        if let Some(caption) = &rd.caption {
+            output += &format!("{}\n", caption);
+        }
+
+        output
+    }
+}
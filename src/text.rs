@@ -0,0 +1,160 @@
+/// Advance widths of the printable ASCII characters in Helvetica/Arial, in thousandths of
+/// an em, starting at the space character.
+#[rustfmt::skip]
+const ASCII_WIDTHS: [u16; 95] = [
+    // ' ' to '/'
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0' to '?'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    // '@' to 'O'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    // 'P' to '_'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    // '`' to 'o'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    // 'p' to '~'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Width of characters not in the table, roughly that of a digit
+const DEFAULT_WIDTH: u16 = 556;
+
+/// Width of East Asian wide characters
+const WIDE_WIDTH: u16 = 1000;
+
+/// Estimate the rendered width of `text` at `font_size` pixels. This is only an approximation
+/// as the actual font used depends on the viewer, but it is close enough for layout.
+pub fn text_width(text: &str, font_size: f64) -> f64 {
+    let thousandths: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => ASCII_WIDTHS[c as usize - ' ' as usize],
+            '\u{2e80}'..='\u{a4cf}' | '\u{ac00}'..='\u{d7a3}' | '\u{ff00}'..='\u{ff60}' => {
+                WIDE_WIDTH
+            }
+            _ => DEFAULT_WIDTH,
+        } as u32)
+        .sum();
+
+    thousandths as f64 * font_size / 1000.0
+}
+
+/// Shorten `text` to at most `max_chars` characters, replacing the end with an ellipsis.
+/// Returns `None` when the text already fits.
+pub fn truncate(text: &str, max_chars: Option<usize>) -> Option<String> {
+    match max_chars {
+        Some(max_chars) if text.chars().count() > max_chars => Some(
+            text.chars()
+                .take(max_chars.saturating_sub(1))
+                .chain(std::iter::once('…'))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Split `text` into lines at spaces so that each line is at most `max_width` pixels wide at
+/// `font_size`, except for single words that are wider than that on their own.
+pub fn wrap(text: &str, font_size: f64, max_width: f64) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if text_width(&format!("{} {}", line, word), font_size) <= max_width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+
+    if lines.len() <= 1 {
+        return vec![text.to_string()];
+    }
+
+    lines
+}
+
+/// Format `value` with `decimal_places` after the point and commas between each group of
+/// three digits before it
+pub fn format_number(value: f64, decimal_places: usize) -> String {
+    let formatted = format!("{:.1$}", value.abs(), decimal_places);
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+    let mut result = String::new();
+
+    if value < 0.0 && formatted.chars().any(|c| c != '0' && c != '.') {
+        result.push('-');
+    }
+
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+
+        result.push(c);
+    }
+
+    if let Some(fraction) = fraction {
+        result.push('.');
+        result.push_str(fraction);
+    }
+
+    result
+}
+
+/// Format `value` scaled down with a k, M, B or T suffix and at most one decimal place, or
+/// like [format_number] when it is under a thousand
+pub fn format_compact(value: f64, decimal_places: usize) -> String {
+    const SUFFIXES: [(f64, &str); 4] = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "k")];
+
+    // Choose the suffix by the value as it would be shown with the next smaller one, so that
+    // rounding carries 999,950 over to 1M rather than showing 1000k
+    let shown = |scale: f64, decimal_places: usize| {
+        let rounded: f64 = format!("{:.1$}", value.abs() / scale, decimal_places)
+            .parse()
+            .unwrap_or(f64::NAN);
+
+        rounded * scale
+    };
+    let suffix = SUFFIXES.iter().enumerate().find(|(i, (scale, _))| {
+        let value = match SUFFIXES.get(i + 1) {
+            Some((smaller, _)) => shown(*smaller, 1),
+            None => shown(1.0, decimal_places),
+        };
+
+        value >= *scale
+    });
+
+    match suffix {
+        Some((_, (scale, suffix))) => {
+            let scaled = format!("{:.1}", value / scale);
+
+            scaled.trim_end_matches(".0").to_string() + suffix
+        }
+        None => format_number(value, decimal_places),
+    }
+}
+
+/// Escape text for inclusion in HTML content
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Whether XML allows `c` in a document, which excludes most control characters
+pub fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | ' '..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..)
+}
+
+/// Remove control characters, which are invalid in XML and could drive a terminal, from text
+/// that comes from the chart data
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| is_xml_char(*c) && !c.is_control())
+        .collect()
+}
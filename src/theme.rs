@@ -0,0 +1,92 @@
+/// Colors used for the non-data parts of a chart, plus the OKLCH lightness and chroma used when
+/// generating category colors so that they contrast with the background.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: &'static str,
+    pub foreground: &'static str,
+    pub zebra: &'static str,
+    pub separator: &'static str,
+    pub positive: &'static str,
+    pub negative: &'static str,
+    pub lightness: f64,
+    pub chroma: f64,
+}
+
+/// All built-in themes. The first entry is the default.
+pub const THEMES: &[Theme] = &[
+    Theme {
+        name: "light",
+        background: "rgb(255,255,255)",
+        foreground: "rgb(0,0,0)",
+        zebra: "rgb(242,242,242)",
+        separator: "rgb(255,255,255)",
+        positive: "rgb(46,139,87)",
+        negative: "rgb(200,55,55)",
+        lightness: 0.6,
+        chroma: 0.14,
+    },
+    Theme {
+        name: "dark",
+        background: "rgb(30,30,30)",
+        foreground: "rgb(230,230,230)",
+        zebra: "rgb(44,44,44)",
+        separator: "rgb(30,30,30)",
+        positive: "rgb(92,190,120)",
+        negative: "rgb(235,100,100)",
+        lightness: 0.75,
+        chroma: 0.12,
+    },
+];
+
+impl Theme {
+    pub fn find(name: &str) -> Option<&'static Theme> {
+        THEMES.iter().find(|theme| theme.name == name)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        THEMES.iter().map(|theme| theme.name).collect()
+    }
+}
+
+/// Parse an `rgb(r,g,b)`, `#rgb` or `#rrggbb` color, as used by the themes, into `0xrrggbb`
+pub fn parse_color(color: &str) -> Option<u32> {
+    let color = color.trim();
+    let components: Vec<u32> = if let Some(rgb) = color
+        .strip_prefix("rgb(")
+        .and_then(|rgb| rgb.strip_suffix(')'))
+    {
+        rgb.split(',')
+            .map(|c| c.trim().parse().ok())
+            .collect::<Option<_>>()?
+    } else {
+        let hex = color.strip_prefix('#')?;
+
+        match hex.len() {
+            3 => hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|n| n * 17))
+                .collect::<Option<_>>()?,
+            6 => {
+                let rgb = u32::from_str_radix(hex, 16).ok()?;
+
+                vec![rgb >> 16 & 0xff, rgb >> 8 & 0xff, rgb & 0xff]
+            }
+            _ => return None,
+        }
+    };
+
+    match components[..] {
+        [r, g, b] if r < 256 && g < 256 && b < 256 => Some(r << 16 | g << 8 | b),
+        _ => None,
+    }
+}
+
+/// Blend `0xrrggbb` color `from` towards `to` by `amount` between 0 and 1
+pub(crate) fn mix(from: u32, to: u32, amount: f64) -> u32 {
+    [16, 8, 0].iter().fold(0, |rgb, shift| {
+        let (a, b) = ((from >> shift & 0xff) as f64, (to >> shift & 0xff) as f64);
+
+        rgb | ((a + (b - a) * amount).round() as u32) << shift
+    })
+}
@@ -0,0 +1,125 @@
+use crate::text;
+use svg::{
+    node::element::{path, tag::Type},
+    parser::{Event, Parser},
+};
+
+/// Attributes holding a single coordinate or length
+const NUMBER_ATTRIBUTES: &[&str] = &[
+    "x", "y", "x1", "y1", "x2", "y2", "cx", "cy", "width", "height", "rx", "ry", "r",
+];
+
+/// Of the above, the attributes which cannot be negative
+const LENGTH_ATTRIBUTES: &[&str] = &["width", "height", "rx", "ry", "r"];
+
+/// Check that `content` is well-formed SVG with sane geometry, returning a description of each
+/// problem found. An empty result means the output is safe to hand to a browser.
+pub fn check_svg(content: &str) -> Vec<String> {
+    let mut problems = vec![];
+    let mut open_tags: Vec<&str> = vec![];
+    let mut num_roots = 0;
+
+    if let Some(c) = content.chars().find(|c| !text::is_xml_char(*c)) {
+        problems.push(format!(
+            "Character U+{:04X} is not allowed in XML",
+            c as u32
+        ));
+    }
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Error(error) => problems.push(format!("Malformed markup: {}", error)),
+            Event::Tag(name, tag_type, attributes) => {
+                if open_tags.is_empty() && tag_type != Type::End {
+                    num_roots += 1;
+                }
+
+                match tag_type {
+                    Type::Start => open_tags.push(name),
+                    Type::End => match open_tags.pop() {
+                        Some(open_tag) if open_tag == name => (),
+                        Some(open_tag) => problems.push(format!(
+                            "Closing tag </{}> does not match <{}>",
+                            name, open_tag
+                        )),
+                        None => problems.push(format!("Closing tag </{}> was never opened", name)),
+                    },
+                    Type::Empty => (),
+                }
+
+                if tag_type == Type::End {
+                    continue;
+                }
+
+                let mut names: Vec<&String> = attributes.keys().collect();
+
+                // Attributes are kept in a hash map, so sort them to report problems in a
+                // stable order
+                names.sort();
+
+                for attribute in names {
+                    if let Some(problem) = check_attribute(attribute, &attributes[attribute]) {
+                        problems.push(format!("<{} {}>: {}", name, attribute, problem));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for open_tag in open_tags.iter().rev() {
+        problems.push(format!("Tag <{}> is never closed", open_tag));
+    }
+
+    if num_roots != 1 {
+        problems.push(format!("Expected one root element and found {}", num_roots));
+    }
+
+    problems
+}
+
+fn check_attribute(name: &str, value: &str) -> Option<String> {
+    let numbers = |value: &str| -> Option<Vec<f64>> {
+        value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f64>().ok().filter(|n| n.is_finite()))
+            .collect()
+    };
+
+    if NUMBER_ATTRIBUTES.contains(&name) {
+        let number = value
+            .strip_suffix('%')
+            .unwrap_or(value)
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite());
+
+        return match number {
+            None => Some(format!("'{}' is not a finite number", value)),
+            Some(n) if n < 0.0 && LENGTH_ATTRIBUTES.contains(&name) => {
+                Some(format!("{} cannot be negative", n))
+            }
+            Some(_) => None,
+        };
+    }
+
+    match name {
+        "viewBox" => match numbers(value).as_deref() {
+            Some([_, _, width, height]) if *width > 0.0 && *height > 0.0 => None,
+            _ => Some(format!("'{}' is not a box with a positive size", value)),
+        },
+        "points" => match numbers(value) {
+            Some(points) if points.len() % 2 == 0 => None,
+            _ => Some(format!("'{}' is not a list of points", value)),
+        },
+        "d" => match path::Data::parse(value) {
+            Ok(_) if !value.contains("NaN") && !value.contains("inf") => None,
+            _ => Some(format!("'{}' is not valid path data", value)),
+        },
+        "transform" if value.contains("NaN") || value.contains("inf") => {
+            Some(format!("'{}' contains a non-finite number", value))
+        }
+        _ => None,
+    }
+}
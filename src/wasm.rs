@@ -0,0 +1,22 @@
+use crate::{ChartData, StackedBarChartLog, StackedBarChartTool};
+use core::fmt::Arguments;
+use wasm_bindgen::prelude::*;
+
+/// Drops messages, as there is nowhere to show them in the browser
+struct NullLog;
+
+impl StackedBarChartLog for NullLog {
+    fn output(self: &Self, _args: Arguments) {}
+    fn warning(self: &Self, _args: Arguments) {}
+    fn error(self: &Self, _args: Arguments) {}
+}
+
+/// Render a chart given as JSON5, using the options in its options block, to an SVG document.
+/// Errors are thrown as JavaScript `Error`s.
+#[wasm_bindgen]
+pub fn render_chart_json(input: &str) -> Result<String, JsError> {
+    let mut chart: ChartData = json5::from_str(input)?;
+    let options = chart.options.take().unwrap_or_default();
+
+    Ok(StackedBarChartTool::new(&NullLog).render(&chart, &options)?)
+}
@@ -0,0 +1,98 @@
+use crate::{output, Cli, LogLevel, OutputFormat, StackedBarChartError, StackedBarChartTool};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// How often to check whether the input files have changed
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The files being watched and when each was last seen to be modified
+pub(crate) struct WatchedFiles {
+    paths: Vec<PathBuf>,
+    last_modified: Option<Vec<Option<SystemTime>>>,
+}
+
+impl WatchedFiles {
+    pub(crate) fn new(paths: Vec<PathBuf>) -> WatchedFiles {
+        WatchedFiles {
+            paths,
+            last_modified: None,
+        }
+    }
+
+    /// Whether any file has been modified, created or removed since the last check, which is
+    /// always so for the first
+    pub(crate) fn changed(&mut self) -> bool {
+        let modified = Self::modified_times(&self.paths);
+        let changed = self.last_modified.as_ref() != Some(&modified);
+
+        self.last_modified = Some(modified);
+
+        changed
+    }
+
+    /// When each file was last modified, or none for files which cannot be read
+    fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+        paths
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+impl<'a> StackedBarChartTool<'a> {
+    /// Render the input, then render it again each time it or one of the files merged into it
+    /// is modified. Errors are logged rather than returned so that a half-finished edit
+    /// doesn't end the watch, which only stops when the process is interrupted.
+    pub(crate) fn watch(
+        self: &Self,
+        cli: &Cli,
+        format: OutputFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        let (input_file, output_file) = match (&cli.input_file, &cli.output_file) {
+            (Some(input_file), Some(output_file)) => (input_file, output_file),
+            _ => {
+                return Err(StackedBarChartError::Usage(
+                    "--watch needs both an input and an output file".to_string(),
+                )
+                .into())
+            }
+        };
+        let mut files = WatchedFiles::new(
+            std::iter::once(input_file)
+                .chain(cli.merge.iter())
+                .cloned()
+                .collect(),
+        );
+
+        loop {
+            self.render_changed(cli, format, &mut files, input_file, output_file);
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+    }
+
+    /// Render the input again if the watched files have changed, logging how it went. Returns
+    /// whether it was rendered, successfully or not.
+    pub(crate) fn render_changed(
+        self: &Self,
+        cli: &Cli,
+        format: OutputFormat,
+        files: &mut WatchedFiles,
+        input_file: &Path,
+        output_file: &Path,
+    ) -> bool {
+        if !files.changed() {
+            return false;
+        }
+
+        match self.render_input(cli, format, Some(input_file), Some(output_file)) {
+            Ok(()) if self.log_level == LogLevel::Quiet => (),
+            Ok(()) => output!(self.log, "Rendered '{}'", output_file.to_string_lossy()),
+            Err(err) => self.report_file_error(&input_file.to_string_lossy(), err),
+        }
+
+        true
+    }
+}